@@ -0,0 +1,64 @@
+//! Evaluation time of the standard rinha programs under each interpreter
+//! configuration, run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rinha::{host::CaptureHost, parser::parse, Context, Interpreter};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("fib", include_str!("programs/fib.rinha")),
+    // the same fib under 64 bindings and 200 pending calls, a call links to
+    // its caller's scope so this should cost about what `fib` does
+    ("deep", include_str!("programs/deep.rinha")),
+    ("sum", include_str!("programs/sum.rinha")),
+    ("combination", include_str!("programs/combination.rinha")),
+    ("strings", include_str!("programs/strings.rinha")),
+];
+
+#[derive(Clone, Copy)]
+struct Strategy {
+    name: &'static str,
+    intrinsics: bool,
+    lazy: bool,
+}
+
+const STRATEGIES: &[Strategy] = &[
+    Strategy {
+        name: "default",
+        intrinsics: true,
+        lazy: false,
+    },
+    Strategy {
+        name: "no-intrinsics",
+        intrinsics: false,
+        lazy: false,
+    },
+    Strategy {
+        name: "lazy",
+        intrinsics: true,
+        lazy: true,
+    },
+];
+
+fn eval(c: &mut Criterion) {
+    for (name, source) in PROGRAMS {
+        let prog = parse(source, name).unwrap();
+        let mut group = c.benchmark_group(*name);
+
+        for strategy in STRATEGIES {
+            group.bench_with_input(BenchmarkId::from_parameter(strategy.name), strategy, |b, strategy| {
+                b.iter(|| {
+                    let mut interpreter = Interpreter::new(CaptureHost::default());
+
+                    interpreter.set_intrinsics(strategy.intrinsics);
+                    interpreter.set_lazy(strategy.lazy);
+                    interpreter.eval(&prog.expression, &mut Context::default()).unwrap()
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, eval);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+//! Arbitrary ASTs evaluated with a budget of terms and of heap bytes: they
+//! may fail in any way but a panic, and stop once either budget is spent.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rinha::{ast::Term, host::CaptureHost, memory::Counting, CancellationToken, EvalHooks, Interpreter};
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting::new();
+
+/// Terms a program may evaluate.
+const FUEL: usize = 100_000;
+
+/// Bytes on the heap past which a program is stopped. A term only checks
+/// before it runs, so a single `+` or `*` may still double what is there.
+const MEMORY: usize = 64 << 20;
+
+/// Cancels the evaluation once it has spent its fuel or memory.
+struct Budget {
+    fuel: usize,
+    token: CancellationToken,
+}
+
+impl EvalHooks for Budget {
+    fn on_enter(&mut self, _term: &Term) {
+        self.fuel = self.fuel.saturating_sub(1);
+
+        if self.fuel == 0 || ALLOCATOR.usage().current > MEMORY {
+            self.token.cancel();
+        }
+    }
+}
+
+fuzz_target!(|term: Term| {
+    let token = CancellationToken::new();
+    let mut interpreter = Interpreter::new(CaptureHost::default());
+
+    // the native fib has no terms to spend fuel on
+    interpreter.set_intrinsics(false);
+    interpreter.set_hooks(Budget {
+        fuel: FUEL,
+        token: token.clone(),
+    });
+
+    let _ = interpreter.eval_cancellable(&term, token);
+
+    assert!(ALLOCATOR.usage().peak < 4 * MEMORY, "used {} bytes", ALLOCATOR.usage().peak);
+});
@@ -0,0 +1,18 @@
+//! Arbitrary bytes read as a JSON AST: the loader must turn down whatever is
+//! not one with an error, and what it reads must survive being written back.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rinha::encoding::Encoding;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(prog) = Encoding::Json.decode(data) else {
+        return;
+    };
+
+    let json = serde_json::to_vec(&prog).expect("a loaded AST can be written");
+    let again = Encoding::Json.decode(&json).expect("a written AST can be read back");
+
+    assert_eq!(again.expression, prog.expression);
+});
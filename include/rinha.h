@@ -0,0 +1,80 @@
+#ifndef RINHA_H
+#define RINHA_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    RINHA_VOID,
+    RINHA_BOOL,
+    RINHA_INT,
+    RINHA_STR,
+    RINHA_TUPLE,
+    RINHA_CLOSURE,
+    RINHA_ERROR,
+    RINHA_MODULE,
+    RINHA_TASK,
+    RINHA_CHANNEL,
+} RinhaKind;
+
+/* values cross the boundary in their printed form: "true"/"false" for bools,
+ * decimal digits for ints and the raw contents for strings */
+typedef struct {
+    RinhaKind kind;
+    const char *text;
+} RinhaValue;
+
+/* return a RINHA_ERROR value to fail the call, `text` being the message */
+typedef RinhaValue (*RinhaBuiltin)(void *userdata, const RinhaValue *args, size_t argc);
+
+typedef struct {
+    char *message;
+    char *filename; /* NULL when the program could not be loaded */
+    size_t start;
+    size_t end;
+} RinhaError;
+
+typedef struct {
+    bool ok;
+    RinhaKind kind;
+    char *value;  /* NULL on error */
+    char *output; /* everything printed by the program */
+    RinhaError error;
+} RinhaResult;
+
+typedef struct RinhaInterpreter RinhaInterpreter;
+
+RinhaInterpreter *rinha_interpreter_new(void);
+void rinha_interpreter_free(RinhaInterpreter *interpreter);
+bool rinha_interpreter_register(RinhaInterpreter *interpreter, const char *name,
+                                RinhaBuiltin builtin, void *userdata);
+RinhaResult rinha_interpreter_eval_json(RinhaInterpreter *interpreter, const char *json);
+
+RinhaResult rinha_eval_json(const char *json);
+void rinha_result_free(RinhaResult result);
+
+/* plugins, loaded with `interpreter --plugin libfoo.so` when built with the
+ * `plugins` feature, export both functions below */
+#define RINHA_PLUGIN_ABI 1
+
+typedef struct {
+    uint32_t abi; /* RINHA_PLUGIN_ABI of the interpreter */
+    void *registrar;
+    bool (*register_builtin)(void *registrar, const char *name, RinhaBuiltin builtin, void *userdata);
+} RinhaPluginApi;
+
+/* must return RINHA_PLUGIN_ABI, plugins built for another one are refused */
+uint32_t rinha_plugin_abi(void);
+/* registers the builtins through `api`, false to refuse loading */
+bool rinha_plugin_init(const RinhaPluginApi *api);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,19 +1,65 @@
-use serde::Deserialize;
+use num_bigint::BigInt;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeSet, fmt, sync::Arc};
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+use crate::symbol::Symbol;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Location {
     pub start: usize,
     pub end: usize,
     pub filename: String,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+impl Location {
+    /// Location of nodes built in code rather than read from a file, the
+    /// whole of a file called `<builder>`.
+    pub fn synthetic() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            filename: String::from("<builder>"),
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset, columns counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// None when `offset` is not a position of `source`, e.g. the location
+    /// comes from another version of the file.
+    pub fn of(source: &str, offset: usize) -> Option<Self> {
+        let before = source.get(..offset)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Some(Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
     Rem,
+    /// `&`, `|` and `^` work on the infinite two's complement of ints.
+    BitAnd,
+    BitOr,
+    BitXor,
+    /// `x <<< n` and `x >>> n`, a negative `n` shifting the other way.
+    Shl,
+    Shr,
     Eq,
     Neq,
     Lt,
@@ -22,9 +68,23 @@ pub enum BinaryOp {
     Gte,
     And,
     Or,
+    /// `x |> f` calls `f(x)`.
+    Pipe,
+    /// `f >> g` is a function calling `g(f(x))`.
+    Compose,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+/// Qualified access to a binding exported by a module, e.g. `math.gcd`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Access {
+    pub module: Box<Term>,
+    pub name: String,
+    pub location: Location,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Binary {
     pub lhs: Box<Term>,
     pub op: BinaryOp,
@@ -32,45 +92,106 @@ pub struct Binary {
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Bool {
     pub value: bool,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Call {
     pub callee: Box<Term>,
     pub arguments: Vec<Term>,
+    /// Arguments given as `name = value`, after the positional ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub named: Vec<NamedArgument>,
+    pub location: Location,
+}
+
+/// Argument matched to the parameter called `name` rather than by position.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct NamedArgument {
+    pub name: Symbol,
+    pub value: Term,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct First {
     pub value: Box<Term>,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Var {
-    pub text: String,
+    pub text: Symbol,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Parameter {
-    pub text: String,
+    pub text: Symbol,
     pub location: Location,
+    /// Only on the last parameter of a function, `...name` collects every
+    /// argument past the other parameters into a tuple.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rest: bool,
+    /// Value of a function parameter the caller left out, evaluated in the
+    /// scope of the function along with the parameters before it. Only
+    /// parameters with a default, or a rest parameter, may follow one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Box<Term>>,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Function {
-    pub parameters: Vec<Parameter>,
-    pub value: Box<Term>,
+    pub parameters: Arc<[Parameter]>,
+    /// Shared with every closure made from it, so making one copies nothing.
+    pub value: Arc<Term>,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+impl Function {
+    /// Defaults of the parameters having one, in order.
+    pub fn defaults(&self) -> impl Iterator<Item = &Term> {
+        self.parameters.iter().filter_map(|param| param.default.as_deref())
+    }
+
+    /// Names read by the body or the defaults that are not parameters,
+    /// sorted, the ones a closure has to capture.
+    pub fn free_variables(&self) -> Vec<Symbol> {
+        let mut free = BTreeSet::new();
+
+        self.free(&mut Vec::new(), &mut free);
+
+        free.into_iter().collect()
+    }
+
+    fn free(&self, bound: &mut Vec<Symbol>, free: &mut BTreeSet<Symbol>) {
+        let mark = bound.len();
+
+        for param in self.parameters.iter() {
+            if let Some(default) = &param.default {
+                default.free(bound, free);
+            }
+
+            bound.push(param.text);
+        }
+
+        self.value.free(bound, free);
+        bound.truncate(mark);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct If {
     pub condition: Box<Term>,
     pub then: Box<Term>,
@@ -78,66 +199,677 @@ pub struct If {
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Import {
+    pub path: String,
+    /// Imports with an alias evaluate to a module instead of bringing every
+    /// binding into scope.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub location: Location,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Int {
-    pub value: i32,
+    /// Written as a JSON number, or as a string of digits for literals past
+    /// the 64 bits JSON parsers keep exact.
+    #[serde(deserialize_with = "big_int", serialize_with = "write_big_int")]
+    pub value: BigInt,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+fn big_int<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = BigInt;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an integer or a string of decimal digits")
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<BigInt, E> {
+            Ok(value.into())
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<BigInt, E> {
+            Ok(value.into())
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<BigInt, E> {
+            value
+                .parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+
+    deserializer.deserialize_any(Visitor)
+}
+
+/// As a JSON number when it fits 64 bits, as a string of digits otherwise.
+fn write_big_int<S: Serializer>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+    match i64::try_from(value) {
+        Ok(value) => serializer.serialize_i64(value),
+        Err(_) => serializer.serialize_str(&value.to_string()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TuplePattern {
+    pub elements: Vec<Pattern>,
+    pub location: Location,
+}
+
+/// Left-hand side of a destructuring `let`, e.g. `(a, (b, c))`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(tag = "kind")]
+pub enum Pattern {
+    Var(Parameter),
+    Tuple(TuplePattern),
+}
+
+impl Pattern {
+    pub fn location(&self) -> &Location {
+        match self {
+            Pattern::Var(x) => &x.location,
+            Pattern::Tuple(x) => &x.location,
+        }
+    }
+
+    /// Names bound by the pattern, left to right.
+    pub fn names(&self) -> Vec<&Parameter> {
+        match self {
+            Pattern::Var(x) => vec![x],
+            Pattern::Tuple(x) => x.elements.iter().flat_map(Pattern::names).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Let {
     pub name: Parameter,
+    /// When present the value is destructured into the names of the pattern
+    /// and `name` is left unused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Pattern>,
     pub value: Box<Term>,
     pub next: Box<Term>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub export: bool,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Print {
     pub value: Box<Term>,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Second {
     pub value: Box<Term>,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+/// Expressions evaluated in order, the sequence evaluating to the value of
+/// the last one, e.g. `{ print(1); print(2); 3 }`. Never empty.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Seq {
+    #[serde(deserialize_with = "expressions")]
+    pub expressions: Vec<Term>,
+    pub location: Location,
+}
+
+fn expressions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Term>, D::Error> {
+    let expressions = Vec::<Term>::deserialize(deserializer)?;
+
+    if expressions.is_empty() {
+        return Err(de::Error::invalid_length(0, &"at least one expression"));
+    }
+
+    Ok(expressions)
+}
+
+/// At least one expression, as a JSON AST may hold.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Seq {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut expressions = vec![u.arbitrary()?];
+
+        expressions.extend(u.arbitrary::<Vec<Term>>()?);
+
+        Ok(Self {
+            expressions,
+            location: u.arbitrary()?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Str {
     pub value: String,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+/// `try { body } catch handler`, the value of `body`, or when running it
+/// fails, that of calling `handler` with the message of the error.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Try {
+    pub body: Box<Term>,
+    pub handler: Box<Term>,
+    pub location: Location,
+}
+
+/// Tuple of two or more values. Pairs keep the `first`/`second` fields of the
+/// original AST format, larger tuples list their `elements`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "RawTuple", into = "RawTuple")]
 pub struct Tuple {
-    pub first: Box<Term>,
-    pub second: Box<Term>,
+    pub elements: Vec<Term>,
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+struct RawTuple {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first: Option<Box<Term>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    second: Option<Box<Term>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    elements: Vec<Term>,
+    location: Location,
+}
+
+/// Pairs are written with `first` and `second`, as readers of the original
+/// format expect.
+impl From<Tuple> for RawTuple {
+    fn from(tuple: Tuple) -> Self {
+        let location = tuple.location;
+        let mut elements = tuple.elements;
+
+        if elements.len() != 2 {
+            return Self {
+                first: None,
+                second: None,
+                elements,
+                location,
+            };
+        }
+
+        let second = elements.pop().map(Box::new);
+
+        Self {
+            first: elements.pop().map(Box::new),
+            second,
+            elements,
+            location,
+        }
+    }
+}
+
+impl TryFrom<RawTuple> for Tuple {
+    type Error = String;
+
+    fn try_from(raw: RawTuple) -> Result<Self, Self::Error> {
+        let elements = match (raw.first, raw.second) {
+            (Some(first), Some(second)) if raw.elements.is_empty() => vec![*first, *second],
+            (None, None) if raw.elements.len() >= 2 => raw.elements,
+            _ => return Err(String::from("tuples need either first and second or at least two elements")),
+        };
+
+        Ok(Self {
+            elements,
+            location: raw.location,
+        })
+    }
+}
+
+/// At least two elements, as a JSON AST may hold.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Tuple {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut elements = vec![u.arbitrary()?, u.arbitrary()?];
+
+        elements.extend(u.arbitrary::<Vec<Term>>()?);
+
+        Ok(Self {
+            elements,
+            location: u.arbitrary()?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[serde(tag = "kind")]
 pub enum Term {
+    Access(Access),
     Binary(Binary),
     Bool(Bool),
     Call(Call),
     First(First),
     Function(Function),
     If(If),
+    Import(Import),
     Int(Int),
     Let(Let),
     Print(Print),
+    /// A call a function makes to itself in tail position, run as the next
+    /// iteration of the running call rather than a nested one. Only made by
+    /// `tail::optimize`, never read from JSON.
+    #[serde(skip)]
+    #[cfg_attr(feature = "arbitrary", arbitrary(skip))]
+    Recur(Call),
     Second(Second),
+    Seq(Seq),
     Str(Str),
+    Try(Try),
     Tuple(Tuple),
     Var(Var),
 }
 
-#[derive(Debug, Deserialize)]
+impl Term {
+    pub fn location(&self) -> &Location {
+        match self {
+            Term::Access(x) => &x.location,
+            Term::Binary(x) => &x.location,
+            Term::Bool(x) => &x.location,
+            Term::Call(x) => &x.location,
+            Term::First(x) => &x.location,
+            Term::Function(x) => &x.location,
+            Term::If(x) => &x.location,
+            Term::Import(x) => &x.location,
+            Term::Int(x) => &x.location,
+            Term::Let(x) => &x.location,
+            Term::Print(x) => &x.location,
+            Term::Recur(x) => &x.location,
+            Term::Second(x) => &x.location,
+            Term::Seq(x) => &x.location,
+            Term::Str(x) => &x.location,
+            Term::Try(x) => &x.location,
+            Term::Tuple(x) => &x.location,
+            Term::Var(x) => &x.location,
+        }
+    }
+
+    pub fn location_mut(&mut self) -> &mut Location {
+        match self {
+            Term::Access(x) => &mut x.location,
+            Term::Binary(x) => &mut x.location,
+            Term::Bool(x) => &mut x.location,
+            Term::Call(x) | Term::Recur(x) => &mut x.location,
+            Term::First(x) => &mut x.location,
+            Term::Function(x) => &mut x.location,
+            Term::If(x) => &mut x.location,
+            Term::Import(x) => &mut x.location,
+            Term::Int(x) => &mut x.location,
+            Term::Let(x) => &mut x.location,
+            Term::Print(x) => &mut x.location,
+            Term::Second(x) => &mut x.location,
+            Term::Seq(x) => &mut x.location,
+            Term::Str(x) => &mut x.location,
+            Term::Try(x) => &mut x.location,
+            Term::Tuple(x) => &mut x.location,
+            Term::Var(x) => &mut x.location,
+        }
+    }
+
+    /// Names read by the term that it does not bind itself, sorted.
+    pub fn free_variables(&self) -> Vec<Symbol> {
+        let mut free = BTreeSet::new();
+
+        self.free(&mut Vec::new(), &mut free);
+
+        free.into_iter().collect()
+    }
+
+    fn free(&self, bound: &mut Vec<Symbol>, free: &mut BTreeSet<Symbol>) {
+        match self {
+            Term::Var(x) => {
+                if !bound.contains(&x.text) {
+                    free.insert(x.text);
+                }
+            }
+            Term::Let(x) => {
+                let mark = bound.len();
+
+                match &x.pattern {
+                    Some(pattern) => {
+                        x.value.free(bound, free);
+                        bound.extend(pattern.names().into_iter().map(|name| name.text));
+                    }
+                    None => {
+                        bound.push(x.name.text);
+                        x.value.free(bound, free);
+                    }
+                }
+
+                x.next.free(bound, free);
+                bound.truncate(mark);
+            }
+            Term::Function(x) => x.free(bound, free),
+            term => term.children().into_iter().for_each(|child| child.free(bound, free)),
+        }
+    }
+
+    /// Name of the variant, as in the `kind` field of the JSON AST.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Term::Access(_) => "Access",
+            Term::Binary(_) => "Binary",
+            Term::Bool(_) => "Bool",
+            Term::Call(_) => "Call",
+            Term::First(_) => "First",
+            Term::Function(_) => "Function",
+            Term::If(_) => "If",
+            Term::Import(_) => "Import",
+            Term::Int(_) => "Int",
+            Term::Let(_) => "Let",
+            Term::Print(_) => "Print",
+            Term::Recur(_) => "Recur",
+            Term::Second(_) => "Second",
+            Term::Seq(_) => "Seq",
+            Term::Str(_) => "Str",
+            Term::Try(_) => "Try",
+            Term::Tuple(_) => "Tuple",
+            Term::Var(_) => "Var",
+        }
+    }
+
+    /// Direct subterms, in evaluation order.
+    pub fn children(&self) -> Vec<&Term> {
+        match self {
+            Term::Access(x) => vec![&x.module],
+            Term::Binary(x) => vec![&x.lhs, &x.rhs],
+            Term::Call(x) | Term::Recur(x) => std::iter::once(&*x.callee)
+                .chain(&x.arguments)
+                .chain(x.named.iter().map(|arg| &arg.value))
+                .collect(),
+            Term::First(x) => vec![&x.value],
+            Term::Function(x) => x.defaults().chain([&*x.value]).collect(),
+            Term::If(x) => vec![&x.condition, &x.then, &x.otherwise],
+            Term::Let(x) => vec![&x.value, &x.next],
+            Term::Print(x) => vec![&x.value],
+            Term::Second(x) => vec![&x.value],
+            Term::Seq(x) => x.expressions.iter().collect(),
+            Term::Try(x) => vec![&x.body, &x.handler],
+            Term::Tuple(x) => x.elements.iter().collect(),
+            Term::Bool(_) | Term::Import(_) | Term::Int(_) | Term::Str(_) | Term::Var(_) => vec![],
+        }
+    }
+}
+
+/// Builders of terms for Rust code, such as tests, code generators and
+/// embedders, every node at `Location::synthetic`, e.g.
+/// `Term::let_("x", Term::int(1), Term::print(Term::var("x")))`.
+impl Term {
+    pub fn int(value: impl Into<BigInt>) -> Self {
+        Term::Int(Int {
+            value: value.into(),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn str(value: impl Into<String>) -> Self {
+        Term::Str(Str {
+            value: value.into(),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Term::Bool(Bool {
+            value,
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn var(name: &str) -> Self {
+        Term::Var(Var {
+            text: Symbol::new(name),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn binary(lhs: Term, op: BinaryOp, rhs: Term) -> Self {
+        Term::Binary(Binary {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn call(callee: Term, arguments: impl IntoIterator<Item = Term>) -> Self {
+        Term::Call(Call {
+            callee: Box::new(callee),
+            arguments: arguments.into_iter().collect(),
+            named: Vec::new(),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `fn (parameters..) => { body }`, every parameter plain: no default
+    /// and no rest.
+    pub fn function<'a>(parameters: impl IntoIterator<Item = &'a str>, body: Term) -> Self {
+        Term::Function(Function {
+            parameters: parameters.into_iter().map(Parameter::synthetic).collect(),
+            value: Arc::new(body),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn if_(condition: Term, then: Term, otherwise: Term) -> Self {
+        Term::If(If {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `let name = value; next`
+    pub fn let_(name: &str, value: Term, next: Term) -> Self {
+        Term::Let(Let {
+            name: Parameter::synthetic(name),
+            pattern: None,
+            value: Box::new(value),
+            next: Box::new(next),
+            export: false,
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `{ e1; e2; .. }`, given at least one expression.
+    pub fn seq(expressions: impl IntoIterator<Item = Term>) -> Self {
+        Term::Seq(Seq {
+            expressions: expressions.into_iter().collect(),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `try { body } catch handler`
+    pub fn try_(body: Term, handler: Term) -> Self {
+        Term::Try(Try {
+            body: Box::new(body),
+            handler: Box::new(handler),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn tuple(elements: impl IntoIterator<Item = Term>) -> Self {
+        Term::Tuple(Tuple {
+            elements: elements.into_iter().collect(),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn first(value: Term) -> Self {
+        Term::First(First {
+            value: Box::new(value),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn second(value: Term) -> Self {
+        Term::Second(Second {
+            value: Box::new(value),
+            location: Location::synthetic(),
+        })
+    }
+
+    pub fn print(value: Term) -> Self {
+        Term::Print(Print {
+            value: Box::new(value),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `import "path" as alias`, or without an alias when None.
+    pub fn import(path: &str, alias: Option<&str>) -> Self {
+        Term::Import(Import {
+            path: String::from(path),
+            alias: alias.map(String::from),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// `module.name`
+    pub fn access(module: Term, name: &str) -> Self {
+        Term::Access(Access {
+            module: Box::new(module),
+            name: String::from(name),
+            location: Location::synthetic(),
+        })
+    }
+
+    /// The term at `location` instead, e.g. for errors to point into the
+    /// source a code generator read.
+    pub fn at(mut self, location: Location) -> Self {
+        *self.location_mut() = location;
+        self
+    }
+}
+
+impl Parameter {
+    fn synthetic(name: &str) -> Self {
+        Self {
+            text: Symbol::new(name),
+            location: Location::synthetic(),
+            rest: false,
+            default: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct File {
     pub name: String,
     pub expression: Term,
     pub location: Location,
 }
+
+impl File {
+    /// A program made of `expression`, in the file `Location::synthetic`
+    /// names.
+    pub fn new(expression: Term) -> Self {
+        let location = Location::synthetic();
+
+        Self {
+            name: location.filename.clone(),
+            expression,
+            location,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canonical::normalize, host::CaptureHost, parser::parse, Context, Interpreter};
+
+    #[test]
+    fn builders() {
+        let fib = Term::let_(
+            "fib",
+            Term::function(
+                ["n"],
+                Term::if_(
+                    Term::binary(Term::var("n"), BinaryOp::Lt, Term::int(2)),
+                    Term::var("n"),
+                    Term::binary(
+                        Term::call(Term::var("fib"), [Term::binary(Term::var("n"), BinaryOp::Sub, Term::int(1))]),
+                        BinaryOp::Add,
+                        Term::call(Term::var("fib"), [Term::binary(Term::var("n"), BinaryOp::Sub, Term::int(2))]),
+                    ),
+                ),
+            ),
+            Term::print(Term::tuple([Term::call(Term::var("fib"), [Term::int(10)]), Term::str("fib")])),
+        );
+        let parsed = parse(
+            "let fib = fn (n) => { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; print((fib(10), \"fib\"))",
+            "a.rinha",
+        )
+        .unwrap();
+
+        assert_eq!(normalize(&fib), normalize(&parsed.expression));
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = File::new(fib);
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "(55, fib)\n");
+
+        let err = interpreter.eval(&Term::var("nope"), &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.filename, "<builder>");
+
+        let location = Location {
+            start: 3,
+            end: 7,
+            filename: String::from("gen.rinha"),
+        };
+
+        assert_eq!(Term::var("nope").at(location.clone()).location(), &location);
+    }
+
+    /// Fixtures written as rinha source must hold the AST of their JSON
+    /// sibling, as `to-json` writes it.
+    #[test]
+    fn fixtures() {
+        let mut compared = 0;
+
+        for entry in std::fs::read_dir("json").unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.extension().is_none_or(|extension| extension != "rinha") {
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            let parsed = parse(&source, &path.display().to_string()).unwrap();
+            let json = std::fs::read_to_string(path.with_extension("json")).unwrap();
+
+            let fixture = crate::parse_json(&json).unwrap();
+
+            assert_eq!(fixture.expression, parsed.expression, "{} is out of date", path.display());
+            assert_eq!((fixture.name, fixture.location), (parsed.name.clone(), parsed.location.clone()));
+            assert_eq!(serde_json::to_string_pretty(&parsed).unwrap() + "\n", json);
+
+            compared += 1;
+        }
+
+        assert!(compared > 0);
+    }
+}
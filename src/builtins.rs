@@ -0,0 +1,269 @@
+//! Builtins every interpreter starts with, embedders can override any of them
+//! with `Interpreter::register`.
+
+use num_bigint::BigInt;
+
+use crate::{ast::Location, host::Host, Error, Interpreter, Output};
+
+pub fn register<H: Host + 'static>(interpreter: &mut Interpreter<H>) {
+    interpreter.register("getenv", getenv);
+    interpreter.register("argc", argc);
+    interpreter.register("argv", argv);
+    interpreter.register("now", now);
+    interpreter.register("assert", assert);
+    interpreter.register("panic", panic);
+    interpreter.register("len", len);
+    interpreter.register("slice", slice);
+    interpreter.register("to_string", to_string);
+    interpreter.register("format", format);
+    interpreter.register("parse_int", parse_int);
+    interpreter.register("nth", nth);
+    interpreter.register("typeof", type_of);
+    interpreter.native("exit", exit);
+    interpreter.native("eprint", eprint);
+    interpreter.native("write", write);
+    interpreter.native("inspect", inspect);
+}
+
+/// `getenv(name)` evaluates to the variable's value, or `false` when unset.
+fn getenv<H: Host>(host: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [Output::Str(name)] => match host.getenv(name)? {
+            Some(value) => Ok(Output::Str(value.into())),
+            None => Ok(Output::Bool(false)),
+        },
+        _ => Err(String::from("getenv expects a single string argument")),
+    }
+}
+
+/// `argc()` evaluates to the number of program arguments.
+fn argc<H: Host>(host: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    if !args.is_empty() {
+        return Err(String::from("argc expects no arguments"));
+    }
+
+    Ok(Output::Int(host.args().len().into()))
+}
+
+/// `argv(i)` evaluates to the i-th program argument, or `false` past the end.
+fn argv<H: Host>(host: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [Output::Int(i)] => match usize::try_from(i).ok().and_then(|i| host.args().get(i)) {
+            Some(arg) => Ok(Output::Str(arg.as_str().into())),
+            None => Ok(Output::Bool(false)),
+        },
+        _ => Err(String::from("argv expects a single int argument")),
+    }
+}
+
+/// `now()` evaluates to the host clock in milliseconds (since the epoch for
+/// native builds).
+fn now<H: Host>(host: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    if !args.is_empty() {
+        return Err(String::from("now expects no arguments"));
+    }
+
+    Ok(Output::Int(host.now()?.into()))
+}
+
+/// `assert(cond, message)` evaluates to Void, or fails at the call site with
+/// `message` when `cond` is false.
+fn assert<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [Output::Bool(true), _] => Ok(Output::Void),
+        [Output::Bool(false), message] => Err(format!("Assertion failed: {}", message)),
+        _ => Err(String::from("assert expects a bool and a message")),
+    }
+}
+
+/// `panic(message)` fails at the call site with `message`, rendered the way
+/// `print` shows it, unless a `try` around the call catches it.
+fn panic<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [message] => Err(message.to_string()),
+        _ => Err(String::from("panic expects a single message argument")),
+    }
+}
+
+/// `len(s)` counts the Unicode scalar values of `s`, not its bytes, and
+/// `len(t)` the elements of a tuple.
+fn len<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [Output::Str(s)] => Ok(Output::Int(s.chars().count().into())),
+        [Output::Tuple(elements)] => Ok(Output::Int(elements.len().into())),
+        _ => Err(String::from("len expects a single string or tuple argument")),
+    }
+}
+
+/// `slice(s, start, end)` takes the scalar values of `s` in `start..end`, so
+/// a code point is never split.
+fn slice<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    let [Output::Str(s), Output::Int(start), Output::Int(end)] = &args[..] else {
+        return Err(String::from("slice expects a string and two int arguments"));
+    };
+
+    let len = s.chars().count();
+
+    match (usize::try_from(start), usize::try_from(end)) {
+        (Ok(start), Ok(end)) if start <= end && end <= len => {
+            Ok(Output::Str(s.chars().skip(start).take(end - start).collect::<String>().into()))
+        }
+        _ => Err(format!("Slice {}..{} is out of range for a string of length {}", start, end, len)),
+    }
+}
+
+/// `to_string(x)` renders an int, bool or string the way `print` does.
+fn to_string<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [x @ (Output::Int(_) | Output::Bool(_) | Output::Str(_))] => Ok(Output::Str(x.to_string().into())),
+        _ => Err(String::from("to_string expects a single int, bool or string argument")),
+    }
+}
+
+/// `format(template, ..)` replaces each `{}` of `template` with the next
+/// argument, rendered the way `print` does. `{{` and `}}` stand for braces.
+fn format<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    let Some((Output::Str(template), values)) = args.split_first() else {
+        return Err(String::from("format expects a template string"));
+    };
+
+    let mut values = values.iter();
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                out.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+
+                let Some(value) = values.next() else {
+                    return Err(format!("format has more placeholders than the {} values given", args.len() - 1));
+                };
+
+                out.push_str(&value.printed().unwrap_or_default());
+            }
+            ('{' | '}', _) => return Err(format!("Unmatched {:?} in format template, write {}{} for a brace", c, c, c)),
+            (c, _) => out.push(c),
+        }
+    }
+
+    if values.next().is_some() {
+        return Err(format!("format was given more values than the placeholders of {:?}", template.as_str()));
+    }
+
+    Ok(Output::Str(out.into()))
+}
+
+/// `parse_int(s)` evaluates to `(true, n)`, or to `(false, reason)` when `s`
+/// is not a decimal integer, so programs can recover from bad input.
+fn parse_int<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    let [Output::Str(s)] = &args[..] else {
+        return Err(String::from("parse_int expects a single string argument"));
+    };
+
+    Ok(Output::Tuple(match s.trim().parse::<BigInt>() {
+        Ok(n) => vec![Output::Bool(true), Output::Int(n)],
+        Err(_) => vec![Output::Bool(false), Output::Str(format!("{:?} is not an integer", s.as_str()).into())],
+    }))
+}
+
+/// `nth(t, i)` is the i-th element of the tuple `t`, counting from 0, so
+/// `first(t)` is `nth(t, 0)`.
+fn nth<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    let [Output::Tuple(elements), Output::Int(i)] = &args[..] else {
+        return Err(String::from("nth expects a tuple and an int argument"));
+    };
+
+    usize::try_from(i)
+        .ok()
+        .and_then(|i| elements.get(i))
+        .cloned()
+        .ok_or_else(|| format!("Index {} is out of range for a tuple of {} elements", i, elements.len()))
+}
+
+/// `typeof(x)` names the kind of `x`: `"int"`, `"bool"`, `"str"`, `"tuple"`,
+/// `"closure"`, `"module"`, `"task"`, `"channel"` or `"void"`.
+fn type_of<H: Host>(_: &mut H, args: Vec<Output>) -> Result<Output, String> {
+    match &args[..] {
+        [x] => Ok(Output::Str(x.type_name().into())),
+        _ => Err(String::from("typeof expects a single argument")),
+    }
+}
+
+/// `exit(code)` stops the program at once, the process exiting with `code`.
+/// Embedders get an error of kind `ErrorKind::Exit` back instead.
+fn exit<H: Host>(_: &mut Interpreter<H>, args: Vec<Output>, location: &Location) -> Result<Output, Error> {
+    match &args[..] {
+        [Output::Int(code)] => match u8::try_from(code) {
+            Ok(code) => Err(Error::exit(i32::from(code), location.clone())),
+            Err(_) => Err(Error::new(&format!("Exit status {} is out of range 0..=255", code), location.clone())),
+        },
+        _ => Err(Error::new("exit expects a single int argument", location.clone())),
+    }
+}
+
+/// `eprint(x)` writes `x` to the error output of the host, the way `print`
+/// writes to its output, and evaluates to what `print` would.
+fn eprint<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let Ok([value]) = <[Output; 1]>::try_from(args) else {
+        return Err(Error::new("eprint expects a single argument", location.clone()));
+    };
+
+    if let Some(text) = value.printed() {
+        interpreter.host_mut().eprint(&text);
+    }
+
+    if interpreter.semantics().print_returns_value {
+        Ok(value)
+    } else {
+        Ok(Output::Void)
+    }
+}
+
+/// `write(x)` writes `x` the way `print` does without ending the line, e.g.
+/// for a progress bar or a row of a table built one cell at a time.
+fn write<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let Ok([value]) = <[Output; 1]>::try_from(args) else {
+        return Err(Error::new("write expects a single argument", location.clone()));
+    };
+
+    if let Some(text) = value.printed() {
+        interpreter.host_mut().write(&text);
+    }
+
+    if interpreter.semantics().print_returns_value {
+        Ok(value)
+    } else {
+        Ok(Output::Void)
+    }
+}
+
+/// `inspect(x)` writes where it is called and what `x` is made of to the
+/// error output of the host, see `Output::inspected`, and evaluates to `x`.
+fn inspect<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let Ok([value]) = <[Output; 1]>::try_from(args) else {
+        return Err(Error::new("inspect expects a single argument", location.clone()));
+    };
+
+    let text = format!("{}:{}:{}: {}", location.filename, location.start, location.end, value.inspected());
+
+    interpreter.host_mut().eprint(&text);
+
+    Ok(value)
+}
@@ -0,0 +1,117 @@
+//! Results of pure programs kept across runs in a directory, keyed by the
+//! canonical hash of everything that was evaluated.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{ast::Term, canonical};
+
+/// Builtins reading the world outside of the program, writing to stderr,
+/// which an entry does not keep, or running tasks, whose prints interleave
+/// differently from run to run.
+const IMPURE: &[&str] = &[
+    "argc", "argv", "getenv", "now", "eprint", "inspect", "spawn", "join", "channel", "send", "recv",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Final value, as printed by `Output`'s Display.
+    pub value: String,
+    /// Everything the program printed, newlines included.
+    pub output: String,
+}
+
+/// Whether the result only depends on the program text: it imports nothing
+/// and calls no builtin that reads the environment, arguments or clock,
+/// that writes to stderr or that runs tasks.
+pub fn pure(term: &Term) -> bool {
+    fn imports(term: &Term) -> bool {
+        matches!(term, Term::Import(_)) || term.children().into_iter().any(imports)
+    }
+
+    !imports(term) && !term.free_variables().iter().any(|name| IMPURE.contains(&name.as_str()))
+}
+
+/// Key for running `programs` in order (preludes first) with the evaluation
+/// settings in `flags`.
+pub fn key(programs: &[&Term], flags: &str) -> String {
+    let mut text = String::from(flags);
+
+    for term in programs {
+        text.push_str(&format!(" {:016x}", canonical::hash(term)));
+    }
+
+    format!("{:016x}", canonical::fnv(&text))
+}
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// None on a miss, including entries that cannot be read back.
+    pub fn get(&self, key: &str) -> Option<Entry> {
+        let text = fs::read_to_string(self.path(key)).ok()?;
+
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Written to a temporary file first so concurrent runs never read half
+    /// an entry.
+    pub fn put(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let tmp = self.dir.join(format!("{}.{}.tmp", key, std::process::id()));
+
+        fs::write(&tmp, serde_json::to_string(entry)?)?;
+        fs::rename(tmp, self.path(key))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn cache() {
+        let a = parse("let x = 1; print(x + 1)", "a").unwrap().expression;
+        let b = parse("let y = 1;\nprint(y + 1)", "b").unwrap().expression;
+
+        assert!(pure(&a));
+        assert!(!pure(&parse("print(now())", "c").unwrap().expression));
+        assert!(!pure(&parse("let x = eprint(1); print(x)", "c").unwrap().expression));
+        assert!(!pure(&parse("inspect(1)", "c").unwrap().expression));
+        assert!(!pure(&parse("join(spawn(fn () => { print(1) }))", "c").unwrap().expression));
+        assert!(pure(&parse("let now = fn () => { 0 }; print(now())", "d").unwrap().expression));
+        assert_eq!(key(&[&a], "lazy"), key(&[&b], "lazy"));
+        assert_ne!(key(&[&a], "lazy"), key(&[&a], "strict"));
+
+        let cache = Cache::new(std::env::temp_dir().join(format!("rinha-cache-{}", std::process::id())));
+        let entry = Entry {
+            value: String::from("Void"),
+            output: String::from("2\n"),
+        };
+
+        assert_eq!(cache.get("k"), None);
+        cache.put("k", &entry).unwrap();
+        assert_eq!(cache.get("k"), Some(entry));
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+//! Stopping a running evaluation from the outside, see
+//! `Interpreter::eval_cancellable`.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Flag shared between an evaluation and whoever may stop it, e.g. a server
+/// timing requests out or a GUI stop button. Copies are the same token.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks evaluations holding the token to stop, from any thread.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Lets evaluations holding the token run again, e.g. the next entry of
+    /// a REPL after Ctrl-C stopped the last one.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,198 @@
+//! Canonical form of a program, equal for programs that differ only in
+//! layout, locations or the names of their local bindings.
+
+use crate::ast::*;
+use crate::symbol::Symbol;
+use std::sync::Arc;
+
+fn blank(location: &mut Location) {
+    *location = Location {
+        start: 0,
+        end: 0,
+        filename: String::new(),
+    };
+}
+
+/// Bound names in scope, innermost last, along with their canonical name.
+#[derive(Default)]
+struct Renamer {
+    scopes: Vec<(Symbol, Symbol)>,
+    next: usize,
+    /// Named arguments refer to parameters by name, so those keep theirs.
+    parameters: bool,
+}
+
+impl Renamer {
+    fn bind(&mut self, name: &mut Parameter) {
+        let canonical = Symbol::from(format!("_{}", self.next));
+
+        self.next += 1;
+        self.scopes.push((std::mem::replace(&mut name.text, canonical), canonical));
+        blank(&mut name.location);
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Var(x) => self.bind(x),
+            Pattern::Tuple(x) => {
+                blank(&mut x.location);
+                x.elements.iter_mut().for_each(|element| self.pattern(element));
+            }
+        }
+    }
+
+    fn term(&mut self, term: &mut Term) {
+        let mark = self.scopes.len();
+
+        match term {
+            Term::Var(x) => {
+                // free names (builtins, preludes, imports) are kept
+                if let Some((_, canonical)) = self.scopes.iter().rev().find(|(name, _)| *name == x.text) {
+                    x.text = *canonical;
+                }
+            }
+            Term::Let(x) => {
+                match &mut x.pattern {
+                    Some(pattern) => {
+                        self.term(&mut x.value);
+                        self.pattern(pattern);
+                        x.name.text = Symbol::from("");
+                        blank(&mut x.name.location);
+                    }
+                    // importers see exported bindings by name
+                    None if x.export => {
+                        self.scopes.push((x.name.text, x.name.text));
+                        blank(&mut x.name.location);
+                        self.term(&mut x.value);
+                    }
+                    None => {
+                        self.bind(&mut x.name);
+                        self.term(&mut x.value);
+                    }
+                }
+
+                self.term(&mut x.next);
+            }
+            Term::Function(x) => {
+                for param in Arc::make_mut(&mut x.parameters) {
+                    if let Some(default) = &mut param.default {
+                        self.term(default);
+                    }
+
+                    if self.parameters {
+                        self.scopes.push((param.text, param.text));
+                        blank(&mut param.location);
+                    } else {
+                        self.bind(param);
+                    }
+                }
+
+                self.term(Arc::make_mut(&mut x.value));
+            }
+            Term::Access(x) => self.term(&mut x.module),
+            Term::Binary(x) => {
+                self.term(&mut x.lhs);
+                self.term(&mut x.rhs);
+            }
+            Term::Call(x) | Term::Recur(x) => {
+                self.term(&mut x.callee);
+                x.arguments.iter_mut().for_each(|arg| self.term(arg));
+
+                for arg in x.named.iter_mut() {
+                    self.term(&mut arg.value);
+                    blank(&mut arg.location);
+                }
+            }
+            Term::If(x) => {
+                self.term(&mut x.condition);
+                self.term(&mut x.then);
+                self.term(&mut x.otherwise);
+            }
+            Term::Seq(x) => x.expressions.iter_mut().for_each(|expression| self.term(expression)),
+            Term::Try(x) => {
+                self.term(&mut x.body);
+                self.term(&mut x.handler);
+            }
+            Term::Tuple(x) => x.elements.iter_mut().for_each(|element| self.term(element)),
+            Term::First(x) => self.term(&mut x.value),
+            Term::Second(x) => self.term(&mut x.value),
+            Term::Print(x) => self.term(&mut x.value),
+            Term::Bool(_) | Term::Import(_) | Term::Int(_) | Term::Str(_) => (),
+        }
+
+        self.scopes.truncate(mark);
+        blank(term.location_mut());
+    }
+}
+
+/// `term` with every location blank and its local bindings renamed `_0`,
+/// `_1`, .. in the order they are bound. Free names and exported bindings
+/// keep theirs, as do names only resolved at runtime from a caller's scope
+/// and parameters when the program names arguments.
+pub fn normalize(term: &Term) -> Term {
+    fn names(term: &Term) -> bool {
+        matches!(term, Term::Call(x) if !x.named.is_empty()) || term.children().into_iter().any(names)
+    }
+
+    let mut term = term.clone();
+    let mut renamer = Renamer {
+        parameters: names(&term),
+        ..Renamer::default()
+    };
+
+    renamer.term(&mut term);
+
+    term
+}
+
+/// 64-bit FNV-1a, small and stable across runs and platforms.
+pub(crate) fn fnv(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// FNV-1a hash of the canonical form of `term`, the same across runs and
+/// platforms for a given version of the AST.
+pub fn hash(term: &Term) -> u64 {
+    fnv(&format!("{:?}", normalize(term)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn canonical(source: &str) -> u64 {
+        hash(&parse(source, "test").unwrap().expression)
+    }
+
+    #[test]
+    fn alpha_equivalence() {
+        let a = canonical("let f = fn (n) => { n + 1 }; print(f(2))");
+
+        assert_eq!(a, canonical("let   g = fn (x) => {\n  x + 1\n};\nprint(g(2))"));
+        assert_ne!(a, canonical("let f = fn (n) => { n + 2 }; print(f(2))"));
+        // free names are part of the program
+        assert_ne!(canonical("let x = 1; len(x)"), canonical("let x = 1; argv(x)"));
+        assert_ne!(canonical("export let x = 1; x"), canonical("export let y = 1; y"));
+    }
+
+    #[test]
+    fn named_arguments() {
+        let a = canonical("let f = fn (a, b) => { a - b }; f(b = 1, a = 2)");
+
+        // swapping the parameters swaps what the names refer to
+        assert_ne!(a, canonical("let f = fn (b, a) => { b - a }; f(b = 1, a = 2)"));
+        assert_eq!(a, canonical("let g = fn (a, b) => { a - b }; g(b = 1, a = 2)"));
+    }
+
+    #[test]
+    fn shadowing() {
+        let prog = parse("let x = 1; let (x, y) = (x, 2); x + y", "test").unwrap();
+
+        assert_eq!(
+            crate::format::format(&normalize(&prog.expression)),
+            crate::format::format(&parse("let _0 = 1; let (_1, _2) = (_0, 2); _1 + _2", "other").unwrap().expression)
+        );
+    }
+}
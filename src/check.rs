@@ -0,0 +1,598 @@
+//! Errors found without running the program. Unlike evaluation the walk goes
+//! on after a problem, so every one of them is reported at once.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{ast::*, interpreter::Suspension, Error, Output, Semantics, Symbol};
+
+/// What a term is known to evaluate to, from its literal or the literal bound
+/// to a variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Int,
+    Str,
+    Bool,
+    Tuple,
+    /// A function taking that many arguments.
+    Function(usize),
+}
+
+impl Kind {
+    /// Kind of a value already computed, None for the ones the checker has
+    /// no kind for, e.g. modules and thunks not yet forced.
+    pub fn of(value: &Output) -> Option<Kind> {
+        match value {
+            Output::Int(_) => Some(Kind::Int),
+            Output::Str(_) => Some(Kind::Str),
+            Output::Bool(_) => Some(Kind::Bool),
+            Output::Tuple(_) => Some(Kind::Tuple),
+            Output::Closure(x) if x.args.iter().any(|param| param.rest || param.default.is_some()) => None,
+            Output::Closure(x) => Some(Kind::Function(x.args.len())),
+            Output::Thunk(x) => match &*x.suspension() {
+                Suspension::Done(value) => Kind::of(value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Int => write!(f, "int"),
+            Kind::Str => write!(f, "str"),
+            Kind::Bool => write!(f, "bool"),
+            Kind::Tuple => write!(f, "tuple"),
+            Kind::Function(arity) => write!(f, "closure/{}", arity),
+        }
+    }
+}
+
+/// Operand pairs each operator accepts, the ones missing accept anything.
+fn operands(op: &BinaryOp) -> Option<&'static [(Kind, Kind)]> {
+    match op {
+        BinaryOp::Add => Some(&[
+            (Kind::Int, Kind::Int),
+            (Kind::Str, Kind::Str),
+            (Kind::Str, Kind::Int),
+            (Kind::Int, Kind::Str),
+        ]),
+        BinaryOp::Sub
+        | BinaryOp::Mul
+        | BinaryOp::Div
+        | BinaryOp::Rem
+        | BinaryOp::BitAnd
+        | BinaryOp::BitOr
+        | BinaryOp::BitXor
+        | BinaryOp::Shl
+        | BinaryOp::Shr => Some(&[(Kind::Int, Kind::Int)]),
+        BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Lte | BinaryOp::Gte => {
+            Some(&[(Kind::Int, Kind::Int), (Kind::Str, Kind::Str)])
+        }
+        // any function, see `matches`
+        BinaryOp::Compose => Some(&[(Kind::Function(1), Kind::Function(1))]),
+        BinaryOp::Eq | BinaryOp::Neq | BinaryOp::And | BinaryOp::Or | BinaryOp::Pipe => None,
+    }
+}
+
+fn name(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "add",
+        BinaryOp::Sub => "sub",
+        BinaryOp::Mul => "mul",
+        BinaryOp::Div => "div",
+        BinaryOp::Rem => "rem",
+        BinaryOp::BitAnd => "bitand",
+        BinaryOp::BitOr => "bitor",
+        BinaryOp::BitXor => "bitxor",
+        BinaryOp::Shl => "shl",
+        BinaryOp::Shr => "shr",
+        BinaryOp::Lt => "lt",
+        BinaryOp::Gt => "gt",
+        BinaryOp::Lte => "lte",
+        BinaryOp::Gte => "gte",
+        BinaryOp::Compose => "compose",
+        BinaryOp::Eq | BinaryOp::Neq | BinaryOp::And | BinaryOp::Or | BinaryOp::Pipe => unreachable!(),
+    }
+}
+
+/// Functions with a rest parameter or defaults take a range of argument
+/// counts, their arity is left unknown.
+fn function(f: &Function) -> Option<Kind> {
+    if f.parameters.iter().any(|param| param.rest || param.default.is_some()) {
+        None
+    } else {
+        Some(Kind::Function(f.parameters.len()))
+    }
+}
+
+/// Same kind, any function matching any other regardless of arity.
+fn matches(kind: Option<Kind>, expected: Kind) -> bool {
+    match (kind, expected) {
+        (None, _) => true,
+        (Some(Kind::Function(_)), Kind::Function(_)) => true,
+        (Some(kind), expected) => kind == expected,
+    }
+}
+
+struct Checker<'a, F> {
+    /// Lexical scopes of the walk, innermost binding last.
+    bindings: Vec<(&'a str, Option<Kind>)>,
+    known: F,
+    /// Imports without an alias bring names nobody can see statically.
+    open: bool,
+    semantics: Semantics,
+    errors: Vec<Error>,
+}
+
+impl<'a, F: Fn(&str) -> bool> Checker<'a, F> {
+    fn error(&mut self, message: &str, location: &Location) {
+        self.errors.push(Error::new(message, location.clone()));
+    }
+
+    fn lookup(&self, name: &str) -> Option<Option<Kind>> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(binding, _)| *binding == name)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// Checks `term` and returns what it is known to evaluate to.
+    fn term(&mut self, term: &'a Term) -> Option<Kind> {
+        match term {
+            Term::Int(_) => Some(Kind::Int),
+            Term::Str(_) => Some(Kind::Str),
+            Term::Bool(_) => Some(Kind::Bool),
+            Term::Var(x) => match self.lookup(&x.text) {
+                Some(kind) => kind,
+                None => {
+                    if !self.open && !(self.known)(&x.text) {
+                        self.error(&format!("Variable {} is not declared", x.text), &x.location);
+                    }
+
+                    None
+                }
+            },
+            Term::Tuple(x) => {
+                x.elements.iter().for_each(|element| {
+                    self.term(element);
+                });
+
+                Some(Kind::Tuple)
+            }
+            Term::Function(x) => {
+                let mark = self.bindings.len();
+
+                for (i, param) in x.parameters.iter().enumerate() {
+                    match &param.default {
+                        Some(default) => {
+                            self.term(default);
+                        }
+                        None if !param.rest && x.parameters[..i].iter().any(|param| param.default.is_some()) => {
+                            let msg = format!("Parameter {} follows one with a default, it needs a default too", param.text);
+
+                            self.error(&msg, &param.location);
+                        }
+                        None => (),
+                    }
+
+                    self.bindings.push((param.text.as_str(), param.rest.then_some(Kind::Tuple)));
+                }
+
+                self.term(&x.value);
+                self.bindings.truncate(mark);
+
+                function(x)
+            }
+            Term::Let(x) => {
+                let mark = self.bindings.len();
+
+                match &x.pattern {
+                    Some(pattern) => {
+                        self.term(&x.value);
+                        self.bindings.extend(pattern.names().into_iter().map(|name| (name.text.as_str(), None)));
+                    }
+                    None => {
+                        // visible in its own value for recursion
+                        let kind = match &*x.value {
+                            Term::Function(f) => function(f),
+                            _ => None,
+                        };
+
+                        self.bindings.push((&x.name.text, kind));
+                        self.bindings[mark].1 = self.term(&x.value);
+                    }
+                }
+
+                let kind = self.term(&x.next);
+                self.bindings.truncate(mark);
+
+                kind
+            }
+            Term::Binary(x) => {
+                let (lhs, rhs) = (self.term(&x.lhs), self.term(&x.rhs));
+
+                if let Some(pairs) = operands(&x.op) {
+                    if !pairs.iter().any(|(a, b)| matches(lhs, *a) && matches(rhs, *b)) {
+                        self.error(&format!("Cannot perform {} operation", name(&x.op)), &x.location);
+                    }
+                }
+
+                let compares = matches!(x.op, BinaryOp::Eq | BinaryOp::Neq);
+
+                if compares && [lhs, rhs].iter().any(|kind| matches!(kind, Some(Kind::Function(_)))) {
+                    self.error("Cannot compare closures", &x.location);
+                }
+
+                if x.op == BinaryOp::Pipe && !matches(rhs, Kind::Function(1)) {
+                    self.error("Calling a not callable", &x.location);
+                }
+
+                match x.op {
+                    BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Rem
+                    | BinaryOp::BitAnd
+                    | BinaryOp::BitOr
+                    | BinaryOp::BitXor
+                    | BinaryOp::Shl
+                    | BinaryOp::Shr => Some(Kind::Int),
+                    BinaryOp::Eq | BinaryOp::Neq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Lte | BinaryOp::Gte => {
+                        Some(Kind::Bool)
+                    }
+                    BinaryOp::Add if lhs == Some(Kind::Int) && rhs == Some(Kind::Int) => Some(Kind::Int),
+                    BinaryOp::Add if lhs == Some(Kind::Str) || rhs == Some(Kind::Str) => Some(Kind::Str),
+                    BinaryOp::Compose => Some(Kind::Function(1)),
+                    _ => None,
+                }
+            }
+            Term::If(x) => {
+                if !matches(self.term(&x.condition), Kind::Bool) && !self.semantics.truthy_conditions {
+                    self.error("Condition expression not resolve to a boolean primitive", &x.location);
+                }
+
+                let (then, otherwise) = (self.term(&x.then), self.term(&x.otherwise));
+
+                (then == otherwise).then_some(then).flatten()
+            }
+            Term::First(x) => {
+                if !matches(self.term(&x.value), Kind::Tuple) {
+                    self.error("Cannot access first of a non tuple argument", &x.location);
+                }
+
+                None
+            }
+            Term::Second(x) => {
+                if !matches(self.term(&x.value), Kind::Tuple) {
+                    self.error("Cannot access second of a non tuple argument", &x.location);
+                }
+
+                None
+            }
+            Term::Call(x) => {
+                let callee = self.term(&x.callee);
+
+                x.arguments.iter().for_each(|arg| {
+                    self.term(arg);
+                });
+
+                x.named.iter().for_each(|arg| {
+                    self.term(&arg.value);
+                });
+
+                let given = x.arguments.len() + x.named.len();
+
+                match callee {
+                    Some(Kind::Function(arity)) if x.named.is_empty() && self.semantics.partial(arity, given) => {
+                        Some(Kind::Function(arity - given))
+                    }
+                    Some(Kind::Function(arity)) if arity != given => {
+                        self.error("Arguments declaration differs parameters declaration", &x.location);
+
+                        None
+                    }
+                    Some(Kind::Function(_)) | None => None,
+                    Some(_) => {
+                        self.error("Calling a not callable", &x.location);
+
+                        None
+                    }
+                }
+            }
+            Term::Seq(x) => x.expressions.iter().fold(None, |_, expression| self.term(expression)),
+            Term::Try(x) => {
+                self.term(&x.body);
+
+                if !matches(self.term(&x.handler), Kind::Function(1)) {
+                    self.error("Calling a not callable", x.handler.location());
+                }
+
+                None
+            }
+            term => {
+                term.children().into_iter().for_each(|child| {
+                    self.term(child);
+                });
+
+                None
+            }
+        }
+    }
+}
+
+/// Unbound variables, arity mismatches and operations on values of the wrong
+/// type, sorted by location. `known` tells the names defined outside of the
+/// program, e.g. builtins and preludes, and `semantics` the kinds conditions
+/// may have and whether calls may leave out arguments.
+pub fn check(term: &Term, semantics: &Semantics, known: impl Fn(&str) -> bool) -> Vec<Error> {
+    match infer(term, semantics, &[], known) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors,
+    }
+}
+
+/// What `term` is known to evaluate to, None when only running it tells, or
+/// the errors `check` finds in it. `scope` gives the kinds of names bound
+/// before the program, e.g. by the earlier entries of a REPL.
+pub fn infer<'a>(
+    term: &'a Term,
+    semantics: &Semantics,
+    scope: &'a [(String, Option<Kind>)],
+    known: impl Fn(&str) -> bool,
+) -> Result<Option<Kind>, Vec<Error>> {
+    let mut checker = Checker {
+        bindings: scope.iter().map(|(name, kind)| (name.as_str(), *kind)).collect(),
+        known,
+        semantics: *semantics,
+        open: contains_open_import(term),
+        errors: Vec::new(),
+    };
+
+    let kind = checker.term(term);
+
+    if checker.errors.is_empty() {
+        return Ok(kind);
+    }
+
+    checker.errors.sort_by(|a, b| (&a.filename, a.start, a.end).cmp(&(&b.filename, b.start, b.end)));
+
+    Err(checker.errors)
+}
+
+/// Variables read where no binding of that name is in scope, in program
+/// order, with `known` telling the names defined outside of the program. Only
+/// resolves scopes, so it is cheap enough to run before every evaluation.
+///
+/// Inside a function a name the function cannot see may still be bound where
+/// it is called, calls falling back to the scope of their caller, so there
+/// only names bound nowhere in the program are reported.
+pub fn unbound(term: &Term, known: impl Fn(&str) -> bool) -> Vec<Error> {
+    let mut scopes = Scopes {
+        bound: HashMap::new(),
+        anywhere: HashSet::new(),
+        functions: 0,
+        known,
+        errors: Vec::new(),
+    };
+
+    scopes.declare(term);
+
+    // what an import without an alias binds is only known once it runs
+    if !contains_open_import(term) {
+        scopes.resolve(term);
+    }
+
+    scopes.errors
+}
+
+struct Scopes<F> {
+    /// How many bindings of each name are in scope.
+    bound: HashMap<Symbol, usize>,
+    /// Every name a `let` or a parameter binds somewhere in the program.
+    anywhere: HashSet<Symbol>,
+    /// How many functions the term being resolved is inside of.
+    functions: usize,
+    known: F,
+    errors: Vec<Error>,
+}
+
+impl<F: Fn(&str) -> bool> Scopes<F> {
+    fn bind(&mut self, name: Symbol) {
+        *self.bound.entry(name).or_default() += 1;
+    }
+
+    fn unbind(&mut self, name: Symbol) {
+        if let Some(count) = self.bound.get_mut(&name) {
+            *count -= 1;
+        }
+    }
+
+    fn declare(&mut self, term: &Term) {
+        match term {
+            Term::Let(x) => match &x.pattern {
+                Some(pattern) => self.anywhere.extend(pattern.names().into_iter().map(|name| name.text)),
+                None => {
+                    self.anywhere.insert(x.name.text);
+                }
+            },
+            Term::Function(x) => self.anywhere.extend(x.parameters.iter().map(|param| param.text)),
+            _ => (),
+        }
+
+        term.children().into_iter().for_each(|child| self.declare(child));
+    }
+
+    fn resolve(&mut self, term: &Term) {
+        match term {
+            Term::Var(x) => {
+                let called = self.functions > 0 && self.anywhere.contains(&x.text);
+
+                if self.bound.get(&x.text).copied().unwrap_or_default() == 0 && !called && !(self.known)(&x.text) {
+                    let msg = format!("Variable {} is not declared", x.text);
+
+                    self.errors.push(Error::new(&msg, x.location.clone()));
+                }
+            }
+            Term::Let(x) => {
+                let names = match &x.pattern {
+                    Some(pattern) => {
+                        self.resolve(&x.value);
+
+                        pattern.names().into_iter().map(|name| name.text).collect()
+                    }
+                    // visible in its own value for recursion
+                    None => {
+                        self.bind(x.name.text);
+                        self.resolve(&x.value);
+
+                        vec![]
+                    }
+                };
+
+                names.iter().for_each(|name| self.bind(*name));
+                self.resolve(&x.next);
+                names.iter().for_each(|name| self.unbind(*name));
+
+                if x.pattern.is_none() {
+                    self.unbind(x.name.text);
+                }
+            }
+            Term::Function(x) => {
+                self.functions += 1;
+
+                for param in x.parameters.iter() {
+                    if let Some(default) = &param.default {
+                        self.resolve(default);
+                    }
+
+                    self.bind(param.text);
+                }
+
+                self.resolve(&x.value);
+                x.parameters.iter().for_each(|param| self.unbind(param.text));
+                self.functions -= 1;
+            }
+            term => term.children().into_iter().for_each(|child| self.resolve(child)),
+        }
+    }
+}
+
+fn contains_open_import(term: &Term) -> bool {
+    match term {
+        Term::Import(x) => x.alias.is_none(),
+        term => term.children().into_iter().any(contains_open_import),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn errors(source: &str) -> Vec<String> {
+        let prog = parse(source, "test").unwrap();
+
+        check(&prog.expression, &Semantics::default(), |name| name == "len")
+            .into_iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn all_at_once() {
+        let errors = errors("let f = fn (a, b) => { a + c }; let s = \"x\"; let _ = print(f(1) + len(s - 1)); if (1) { d } else { 0 }");
+
+        assert_eq!(
+            errors,
+            [
+                "test:27:28: Variable c is not declared",
+                "test:59:63: Arguments declaration differs parameters declaration",
+                "test:70:75: Cannot perform sub operation",
+                "test:79:102: Condition expression not resolve to a boolean primitive",
+                "test:88:89: Variable d is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn valid() {
+        let source = "let f = fn (n) => { if (n < 2) { n } else { f(n - 1) + f(n - 2) } }; let (a, b) = (f(3), \"x\"); print(a + b)";
+
+        assert_eq!(errors(source), [] as [String; 0]);
+        assert_eq!(errors("let x = 1; import \"math.rinha\"; gcd(x, y)"), [] as [String; 0]);
+    }
+
+    #[test]
+    fn unbound() {
+        let prog = parse(
+            "let f = fn (a, b = a) => { a + c }; let (x, y) = (f(1), x); let g = fn (n) => { g(n) }; h(z, len)",
+            "test",
+        )
+        .unwrap();
+
+        let errors: Vec<String> = super::unbound(&prog.expression, |name| name == "len")
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                "test:31:32: Variable c is not declared",
+                "test:56:57: Variable x is not declared",
+                "test:88:89: Variable h is not declared",
+                "test:90:91: Variable z is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn mutual() {
+        let source = "let even = fn (n) => { if (n == 0) { true } else { odd(n - 1) } }; \
+                      let odd = fn (n) => { if (n == 0) { false } else { even(n - 1) } }; \
+                      let g = fn () => { y + w }; let y = 5; print((even(10), g(), y + v))";
+        let prog = parse(source, "test").unwrap();
+
+        let errors: Vec<String> = super::unbound(&prog.expression, |_| false)
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+
+        // later names are only in reach of calls, `w` and `v` are bound nowhere
+        assert_eq!(
+            errors,
+            ["test:158:159: Variable w is not declared", "test:200:201: Variable v is not declared"]
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let prog = parse("let add = fn (a, b) => { a + b }; let inc = add(1); inc(1, 2)", "test").unwrap();
+        let semantics = Semantics {
+            partial_application: true,
+            ..Semantics::default()
+        };
+
+        let errors: Vec<String> = check(&prog.expression, &semantics, |_| false).iter().map(|err| err.to_string()).collect();
+
+        assert_eq!(errors, ["test:52:61: Arguments declaration differs parameters declaration"]);
+    }
+
+    #[test]
+    fn try_catch() {
+        assert_eq!(errors("try { 1 } catch fn (err) => { err }"), [] as [String; 0]);
+        assert_eq!(errors("try { 1 } catch \"handler\""), ["test:16:25: Calling a not callable"]);
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(errors("let f = fn (x) => { x }; f == f"), ["test:25:31: Cannot compare closures"]);
+        assert_eq!(errors("let x = (1, \"a\"); x == 1"), [] as [String; 0]);
+    }
+}
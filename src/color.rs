@@ -0,0 +1,172 @@
+//! ANSI colors for what the command line writes to a terminal. Errors,
+//! warnings and reports are rendered as plain text first, a `Palette` only
+//! paints the result, so tests and files written by the interpreter compare
+//! and keep plain strings.
+
+use std::str::FromStr;
+
+/// When to color output, `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// On terminals, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice {:?}, expected auto, always or never", name)),
+        }
+    }
+}
+
+const BOLD: &str = "1";
+const RED: &str = "31";
+const GREEN: &str = "1;32";
+const YELLOW: &str = "1;33";
+const CYAN: &str = "36";
+
+/// Colors of one output stream, leaving text as it is when disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub const PLAIN: Palette = Palette { enabled: false };
+
+    /// Palette of a stream, `terminal` telling whether it is one and
+    /// `no_color` whether `NO_COLOR` is set to something. Only `Auto` minds
+    /// either.
+    pub fn new(choice: ColorChoice, terminal: bool, no_color: bool) -> Self {
+        Self {
+            enabled: match choice {
+                ColorChoice::Auto => terminal && !no_color,
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+            },
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if !self.enabled || text.is_empty() {
+            return String::from(text);
+        }
+
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    }
+
+    /// Rendered errors, one per line, the location in bold and the message
+    /// in red.
+    pub fn error(&self, text: &str) -> String {
+        self.lines(text, |line| match located(line) {
+            Some((location, message)) => format!("{}: {}", self.paint(BOLD, location), self.paint(RED, message)),
+            None => self.paint(RED, line),
+        })
+    }
+
+    /// Rendered warnings, one per line, `warning:` standing out from the
+    /// location and message around it.
+    pub fn warning(&self, text: &str) -> String {
+        self.lines(text, |line| {
+            let Some((location, rest)) = located(line) else {
+                return String::from(line);
+            };
+
+            match rest.strip_prefix("warning: ") {
+                Some(message) => {
+                    format!("{}: {} {}", self.paint(BOLD, location), self.paint(YELLOW, "warning:"), message)
+                }
+                None => format!("{}: {}", self.paint(BOLD, location), rest),
+            }
+        })
+    }
+
+    pub fn prompt(&self, prompt: &str) -> String {
+        let trimmed = prompt.trim_end();
+
+        format!("{}{}", self.paint(GREEN, trimmed), &prompt[trimmed.len()..])
+    }
+
+    /// `name: value` lines such as those of `--stats` and `--hot`, names in
+    /// cyan and values in bold.
+    pub fn report(&self, text: &str) -> String {
+        self.lines(text, |line| match line.split_once(": ") {
+            Some((name, value)) => format!("{}: {}", self.paint(CYAN, name), self.paint(BOLD, value)),
+            None => String::from(line),
+        })
+    }
+
+    fn lines(&self, text: &str, paint: impl Fn(&str) -> String) -> String {
+        if !self.enabled {
+            return String::from(text);
+        }
+
+        text.split('\n').map(paint).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// `file:line:column` and the rest of a rendered diagnostic, None when the
+/// line does not start with a location.
+fn located(line: &str) -> Option<(&str, &str)> {
+    line.match_indices(": ").find_map(|(i, _)| {
+        let location = &line[..i];
+        let mut parts = location.rsplitn(3, ':');
+        let numeric = |part: Option<&str>| {
+            part.is_some_and(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        };
+
+        (numeric(parts.next()) && numeric(parts.next()) && parts.next().is_some()).then(|| (location, &line[i + 2..]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choice() {
+        assert!(Palette::new(ColorChoice::Auto, true, false).enabled());
+        assert!(!Palette::new(ColorChoice::Auto, true, true).enabled());
+        assert!(!Palette::new(ColorChoice::Auto, false, false).enabled());
+        assert!(Palette::new(ColorChoice::Always, false, true).enabled());
+        assert!(!Palette::new(ColorChoice::Never, true, false).enabled());
+    }
+
+    #[test]
+    fn paint() {
+        let palette = Palette::new(ColorChoice::Always, false, false);
+
+        assert_eq!(
+            palette.error("a.rinha:2:5: Variable x is not declared"),
+            "\x1b[1ma.rinha:2:5\x1b[0m: \x1b[31mVariable x is not declared\x1b[0m"
+        );
+        assert_eq!(
+            palette.warning("a.rinha:1:5: warning: let x is never read"),
+            "\x1b[1ma.rinha:1:5\x1b[0m: \x1b[1;33mwarning:\x1b[0m let x is never read"
+        );
+        assert_eq!(palette.error("Cannot read a.rinha: gone"), "\x1b[31mCannot read a.rinha: gone\x1b[0m");
+        assert_eq!(palette.report("calls: 2\n"), "\x1b[36mcalls\x1b[0m: \x1b[1m2\x1b[0m\n");
+        assert_eq!(palette.prompt("rinha> "), "\x1b[1;32mrinha>\x1b[0m ");
+    }
+
+    #[test]
+    fn plain() {
+        let text = "a.rinha:2:5: Variable x is not declared";
+
+        assert_eq!(Palette::PLAIN.error(text), text);
+        assert_eq!(Palette::PLAIN.prompt("rinha> "), "rinha> ");
+    }
+}
@@ -0,0 +1,75 @@
+//! Project defaults read from a `rinha.toml`, flags given on the command line
+//! take precedence over them.
+
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Profile;
+
+pub const FILENAME: &str = "rinha.toml";
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Preludes evaluated before the program, relative to the config file.
+    pub prelude: Vec<PathBuf>,
+    pub no_prelude: bool,
+    pub allow_env: bool,
+    /// Native fast paths for well known functions such as `fib`.
+    pub intrinsics: Option<bool>,
+    /// `strict` to follow the rinha spec to the letter.
+    pub profile: Option<Profile>,
+}
+
+impl Config {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        toml::from_str(source).map_err(|err| err.to_string())
+    }
+
+    /// Reads the closest `rinha.toml` found in `dir` or one of its parents,
+    /// returning its path along with the settings.
+    pub fn discover(dir: &Path) -> Result<Option<(PathBuf, Self)>, String> {
+        let Some(path) = dir.ancestors().map(|dir| dir.join(FILENAME)).find(|path| path.is_file()) else {
+            return Ok(None);
+        };
+
+        let source = fs::read_to_string(&path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+        let mut config = Self::parse(&source).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        if let Some(base) = path.parent() {
+            config.prelude = config.prelude.iter().map(|prelude| base.join(prelude)).collect();
+        }
+
+        Ok(Some((path, config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discover() {
+        let (path, config) = Config::discover(Path::new("./json/config/nested/dir")).unwrap().unwrap();
+
+        assert_eq!(path, Path::new("./json/config/rinha.toml"));
+        assert_eq!(
+            config,
+            Config {
+                prelude: vec![PathBuf::from("./json/config/helpers.rinha")],
+                no_prelude: false,
+                allow_env: true,
+                intrinsics: Some(false),
+                profile: Some(Profile::Strict),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert!(Config::parse("allow_env = true").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Which top-level bindings can be computed once, when the program is
+//! loaded, see `Interpreter::preevaluate`.
+
+use std::collections::HashSet;
+
+use crate::ast::*;
+use crate::symbol::Symbol;
+use crate::Output;
+
+/// Whether evaluating `term` reads nothing but literals and the names in
+/// `known`, and can neither print nor call anything.
+pub(crate) fn constant(term: &Term, known: &HashSet<Symbol>) -> bool {
+    match term {
+        Term::Int(_) | Term::Str(_) | Term::Bool(_) => true,
+        Term::Var(x) => known.contains(&x.text),
+        Term::Binary(x) => {
+            !matches!(x.op, BinaryOp::Pipe | BinaryOp::Compose) && constant(&x.lhs, known) && constant(&x.rhs, known)
+        }
+        Term::If(x) => constant(&x.condition, known) && constant(&x.then, known) && constant(&x.otherwise, known),
+        Term::Tuple(x) => x.elements.iter().all(|element| constant(element, known)),
+        Term::First(x) => constant(&x.value, known),
+        Term::Second(x) => constant(&x.value, known),
+        _ => false,
+    }
+}
+
+/// Whether running `term` may import a module, which without an alias
+/// brings names into scope no analysis of the program can see.
+pub(crate) fn imports(term: &Term) -> bool {
+    matches!(term, Term::Import(_)) || term.children().into_iter().any(imports)
+}
+
+/// The literal `value` is written as, None for closures and anything else no
+/// literal gives.
+pub(crate) fn literal(value: &Output, location: &Location) -> Option<Term> {
+    let location = location.clone();
+
+    match value {
+        Output::Int(x) => Some(Term::Int(Int {
+            value: x.clone(),
+            location,
+        })),
+        Output::Str(x) => Some(Term::Str(Str {
+            value: x.to_string(),
+            location,
+        })),
+        Output::Bool(x) => Some(Term::Bool(Bool { value: *x, location })),
+        Output::Tuple(x) => {
+            let elements = x.iter().map(|element| literal(element, &location)).collect::<Option<_>>()?;
+
+            Some(Term::Tuple(Tuple { elements, location }))
+        }
+        _ => None,
+    }
+}
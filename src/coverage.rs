@@ -0,0 +1,182 @@
+//! Evaluation counts per AST node, recorded by the interpreter once enabled
+//! with `Interpreter::enable_coverage`.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::ast::{Location, Term};
+
+/// Builtins and qualified calls resolve a named callee without evaluating it,
+/// it counts as run whenever the call did.
+fn named_callee(term: &Term) -> Option<&Term> {
+    match term {
+        Term::Call(x) if matches!(*x.callee, Term::Var(_) | Term::Access(_)) => Some(&x.callee),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    hits: HashMap<Location, usize>,
+}
+
+impl Coverage {
+    pub fn record(&mut self, location: &Location) {
+        match self.hits.get_mut(location) {
+            Some(hits) => *hits += 1,
+            None => {
+                self.hits.insert(location.clone(), 1);
+            }
+        }
+    }
+
+    /// How many times the node at `location` was evaluated.
+    pub fn hits(&self, location: &Location) -> usize {
+        self.hits.get(location).copied().unwrap_or_default()
+    }
+
+    /// The `n` most evaluated nodes, across every file that ran.
+    pub fn hottest(&self, n: usize) -> Vec<(&Location, usize)> {
+        let mut hits: Vec<_> = self.hits.iter().map(|(location, hits)| (location, *hits)).collect();
+
+        hits.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| a.0.filename.cmp(&b.0.filename))
+                .then_with(|| (a.0.start, a.0.end).cmp(&(b.0.start, b.0.end)))
+        });
+        hits.truncate(n);
+
+        hits
+    }
+
+    fn visit<'a>(&self, term: &'a Term, nodes: &mut Vec<(&'a Location, usize)>) {
+        nodes.push((term.location(), self.hits(term.location())));
+
+        for child in term.children() {
+            if named_callee(term).is_some_and(|callee| std::ptr::eq(callee, child)) {
+                nodes.push((child.location(), self.hits(term.location())));
+            } else {
+                self.visit(child, nodes);
+            }
+        }
+    }
+
+    /// Every node of `term` along with its count, in source order.
+    pub fn nodes<'a>(&self, term: &'a Term) -> Vec<(&'a Location, usize)> {
+        let mut nodes = Vec::new();
+
+        self.visit(term, &mut nodes);
+
+        nodes
+    }
+
+    /// Outermost nodes of `term` never evaluated, e.g. branches not taken.
+    pub fn unexecuted<'a>(&self, term: &'a Term) -> Vec<&'a Location> {
+        if self.hits(term.location()) == 0 {
+            return vec![term.location()];
+        }
+
+        term.children()
+            .into_iter()
+            .filter(|child| !named_callee(term).is_some_and(|callee| std::ptr::eq(callee, *child)))
+            .flat_map(|child| self.unexecuted(child))
+            .collect()
+    }
+
+    /// Human readable summary listing where the unexecuted code is.
+    pub fn report(&self, term: &Term) -> String {
+        let nodes = self.nodes(term);
+        let executed = nodes.iter().filter(|(_, hits)| *hits > 0).count();
+
+        let mut report = format!(
+            "coverage: {}/{} nodes executed ({:.1}%)\n",
+            executed,
+            nodes.len(),
+            100.0 * executed as f64 / nodes.len() as f64
+        );
+
+        for location in self.unexecuted(term) {
+            report.push_str(&format!(
+                "{}:{}:{}: not executed\n",
+                location.filename, location.start, location.end
+            ));
+        }
+
+        report
+    }
+
+    /// Per-node counts grouped by file, in the spirit of lcov.
+    pub fn json(&self, term: &Term) -> Value {
+        let mut files: Vec<(&str, Vec<Value>)> = Vec::new();
+
+        for (location, hits) in self.nodes(term) {
+            let node = json!({ "start": location.start, "end": location.end, "hits": hits });
+
+            match files.iter_mut().find(|(name, _)| *name == location.filename) {
+                Some((_, nodes)) => nodes.push(node),
+                None => files.push((&location.filename, vec![node])),
+            }
+        }
+
+        let files: Vec<Value> = files
+            .into_iter()
+            .map(|(filename, nodes)| {
+                let executed = nodes.iter().filter(|node| node["hits"] != 0).count();
+
+                json!({
+                    "filename": filename,
+                    "nodes": nodes.len(),
+                    "executed": executed,
+                    "hits": nodes,
+                })
+            })
+            .collect();
+
+        json!({ "files": files })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse, Context, Interpreter};
+
+    #[test]
+    fn branches() {
+        let prog = parse("let f = fn (x) => { if (x < 0) { 0 - x } else { x } }; f(2)", "test").unwrap();
+        let mut interpreter = Interpreter::default();
+
+        interpreter.enable_coverage();
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let coverage = interpreter.coverage().unwrap();
+        let unexecuted: Vec<_> = coverage
+            .unexecuted(&prog.expression)
+            .into_iter()
+            .map(|x| (x.start, x.end))
+            .collect();
+
+        assert_eq!(unexecuted, [(33, 38)]);
+        assert!(coverage.report(&prog.expression).contains("test:33:38: not executed"));
+        assert_eq!(coverage.json(&prog.expression)["files"][0]["filename"], "test");
+    }
+
+    #[test]
+    fn hottest() {
+        let prog = parse("let f = fn (n) => { if (n == 0) { 0 } else { f(n - 1) } }; f(3)", "test").unwrap();
+        let mut interpreter = Interpreter::default();
+
+        interpreter.enable_coverage();
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let hottest: Vec<_> = interpreter
+            .coverage()
+            .unwrap()
+            .hottest(2)
+            .into_iter()
+            .map(|(x, hits)| (x.start, x.end, hits))
+            .collect();
+
+        // the if and the condition (operands first) run once per call
+        assert_eq!(hottest, [(20, 55, 4), (24, 25, 4)]);
+    }
+}
@@ -0,0 +1,315 @@
+//! Errors and warnings as records for editors and CI pipelines, one JSON
+//! object each or all of them in a SARIF log.
+
+use serde_json::{json, Value};
+
+use crate::{
+    ast::{Location, Position},
+    lint::Warning,
+    Error, ErrorKind,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// Codes by a part of the message they are given for, the first match wins.
+/// Messages are worded the same wherever they are raised: by the checker,
+/// the linter, the parser or while running.
+const CODES: &[(&str, &str)] = &[
+    ("is not declared", "unbound-variable"),
+    ("Arguments declaration differs", "arity"),
+    ("Missing argument", "arity"),
+    ("is given twice", "arity"),
+    ("Cannot perform", "operand-types"),
+    ("Cannot compare closures", "closure-comparison"),
+    ("not callable", "not-callable"),
+    ("Condition expression", "condition-type"),
+    ("non tuple", "not-a-tuple"),
+    ("follows one with a default", "default-order"),
+    ("declared twice", "duplicate-parameters"),
+    ("shadows a binding", "shadowed-capture"),
+    ("is never read", "unused"),
+    ("always calls itself", "nontermination"),
+    ("Implicit coercion", "coercion"),
+    ("Integer overflow", "overflow"),
+    ("dividing by zero", "division-by-zero"),
+    ("depends on itself", "cycle"),
+    ("too deep", "too-deep"),
+    ("Unexpected", "syntax"),
+    ("Unterminated", "syntax"),
+    ("escape", "syntax"),
+    ("Invalid integer literal", "syntax"),
+];
+
+/// Code of `message`, `runtime` for errors and `lint` for warnings no entry
+/// of `CODES` matches.
+pub fn code(message: &str, severity: Severity) -> &'static str {
+    match CODES.iter().find(|(part, _)| message.contains(part)) {
+        Some((_, code)) => code,
+        None if severity == Severity::Error => "runtime",
+        None => "lint",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub location: Location,
+    /// Other places involved and how, e.g. the calls an interrupted program
+    /// was in.
+    pub related: Vec<(String, Location)>,
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(err: &Error) -> Self {
+        let code = match err.kind {
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Exit(_) => "exit",
+            ErrorKind::Failure => code(&err.message, Severity::Error),
+        };
+
+        Self {
+            code,
+            severity: Severity::Error,
+            message: err.message.clone(),
+            location: Location {
+                start: err.start,
+                end: err.end,
+                filename: err.filename.clone(),
+            },
+            related: err
+                .backtrace
+                .iter()
+                .map(|call| (String::from("called from"), call.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        Self {
+            code: code(&warning.message, Severity::Warning),
+            severity: Severity::Warning,
+            message: warning.message.clone(),
+            location: warning.location.clone(),
+            related: Vec::new(),
+        }
+    }
+}
+
+/// Offsets of `location`, along with 1-based lines and columns when `source`
+/// is the text of its file.
+fn span(location: &Location, source: &str) -> Value {
+    let mut span = json!({
+        "filename": location.filename,
+        "start": location.start,
+        "end": location.end,
+    });
+
+    if let (Some(start), Some(end)) = (Position::of(source, location.start), Position::of(source, location.end)) {
+        span["line"] = Value::from(start.line);
+        span["column"] = Value::from(start.column);
+        span["end_line"] = Value::from(end.line);
+        span["end_column"] = Value::from(end.column);
+    }
+
+    span
+}
+
+impl Diagnostic {
+    /// The record as a JSON object, `source` giving the text of a file by
+    /// name for lines and columns, empty when it is not at hand.
+    pub fn json(&self, source: impl Fn(&str) -> String) -> Value {
+        let related: Vec<Value> = self
+            .related
+            .iter()
+            .map(|(message, location)| {
+                json!({
+                    "message": message,
+                    "span": span(location, &source(&location.filename)),
+                })
+            })
+            .collect();
+
+        json!({
+            "code": self.code,
+            "severity": self.severity.name(),
+            "message": self.message,
+            "span": span(&self.location, &source(&self.location.filename)),
+            "related": related,
+        })
+    }
+}
+
+/// SARIF 2.1.0 location of `location`, `text` telling how it is involved
+/// when it is a related one.
+fn sarif_location(location: &Location, source: &str, text: Option<&str>) -> Value {
+    let mut region = json!({
+        "charOffset": location.start,
+        "charLength": location.end.saturating_sub(location.start),
+    });
+
+    if let (Some(start), Some(end)) = (Position::of(source, location.start), Position::of(source, location.end)) {
+        region["startLine"] = Value::from(start.line);
+        region["startColumn"] = Value::from(start.column);
+        region["endLine"] = Value::from(end.line);
+        region["endColumn"] = Value::from(end.column);
+    }
+
+    let mut sarif = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": location.filename },
+            "region": region,
+        },
+    });
+
+    if let Some(text) = text {
+        sarif["message"] = json!({ "text": text });
+    }
+
+    sarif
+}
+
+/// A SARIF 2.1.0 log of `diagnostics`, `source` as for `Diagnostic::json`.
+pub fn sarif(diagnostics: &[Diagnostic], source: impl Fn(&str) -> String) -> Value {
+    let mut rules: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.code).collect();
+
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let related: Vec<Value> = diagnostic
+                .related
+                .iter()
+                .map(|(text, location)| sarif_location(location, &source(&location.filename), Some(text)))
+                .collect();
+            let location = &diagnostic.location;
+
+            json!({
+                "ruleId": diagnostic.code,
+                "level": diagnostic.severity.name(),
+                "message": { "text": diagnostic.message },
+                "locations": [sarif_location(location, &source(&location.filename), None)],
+                "relatedLocations": related,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "rinha",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|rule| json!({ "id": rule })).collect::<Vec<_>>(),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check, lint, parser::parse, Semantics};
+
+    const SOURCE: &str = "let x = 1;\nprint(y)";
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        let prog = parse(SOURCE, "a.rinha").unwrap();
+        let errors = check::check(&prog.expression, &Semantics::default(), |name| name == "print");
+        let warnings = lint::warnings(&prog.expression);
+
+        errors.iter().map(Diagnostic::from).chain(warnings.iter().map(Diagnostic::from)).collect()
+    }
+
+    #[test]
+    fn json() {
+        let records: Vec<Value> = diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.json(|_| String::from(SOURCE)))
+            .collect();
+
+        assert_eq!(
+            records,
+            [
+                json!({
+                    "code": "unbound-variable",
+                    "severity": "error",
+                    "message": "Variable y is not declared",
+                    "span": {
+                        "filename": "a.rinha", "start": 17, "end": 18,
+                        "line": 2, "column": 7, "end_line": 2, "end_column": 8,
+                    },
+                    "related": [],
+                }),
+                json!({
+                    "code": "unused",
+                    "severity": "warning",
+                    "message": "binding x is never read",
+                    "span": {
+                        "filename": "a.rinha", "start": 4, "end": 5,
+                        "line": 1, "column": 5, "end_line": 1, "end_column": 6,
+                    },
+                    "related": [],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sarif() {
+        let log = super::sarif(&diagnostics(), |_| String::new());
+        let run = &log["runs"][0];
+
+        assert_eq!(run["tool"]["driver"]["rules"], json!([{"id": "unbound-variable"}, {"id": "unused"}]));
+        assert_eq!(run["results"][0]["level"], "error");
+        assert_eq!(
+            run["results"][0]["locations"][0]["physicalLocation"],
+            json!({"artifactLocation": {"uri": "a.rinha"}, "region": {"charOffset": 17, "charLength": 1}})
+        );
+    }
+
+    #[test]
+    fn related() {
+        let mut err = Error::cancelled(Location {
+            start: 3,
+            end: 4,
+            filename: String::from("a.rinha"),
+        });
+
+        err.backtrace.push(Location {
+            start: 10,
+            end: 12,
+            filename: String::from("a.rinha"),
+        });
+
+        let record = Diagnostic::from(&err).json(|_| String::new());
+
+        assert_eq!(record["code"], "cancelled");
+        assert_eq!(
+            record["related"],
+            json!([{"message": "called from", "span": {"filename": "a.rinha", "start": 10, "end": 12}}])
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Structural comparison of two programs, reported by `interpreter diff`.
+
+use std::fmt;
+
+use crate::{ast::*, format};
+
+/// Subtree that differs between both programs, the outermost one only.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    /// Fields leading to the node from the root, e.g. `next.value.then`.
+    pub path: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", if self.path.is_empty() { "." } else { &self.path })?;
+
+        for line in self.left.lines() {
+            writeln!(f, "- {}", line)?;
+        }
+
+        for line in self.right.lines() {
+            writeln!(f, "+ {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn pattern(p: &Pattern) -> String {
+    match p {
+        Pattern::Var(x) => x.text.to_string(),
+        Pattern::Tuple(x) => format!("({})", x.elements.iter().map(pattern).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// What a node holds besides its children, locations aside.
+fn label(term: &Term) -> String {
+    match term {
+        Term::Access(x) => format!("Access {}", x.name),
+        Term::Binary(x) => format!("Binary {:?}", x.op),
+        Term::Bool(x) => format!("Bool {}", x.value),
+        Term::Call(x) => {
+            let names: Vec<&str> = x.named.iter().map(|arg| arg.name.as_str()).collect();
+
+            format!("Call {} ({})", x.arguments.len(), names.join(", "))
+        }
+        Term::Function(x) => {
+            let params: Vec<String> = x
+                .parameters
+                .iter()
+                .map(|param| {
+                    let default = if param.default.is_some() { " =" } else { "" };
+
+                    format!("{}{}{}", if param.rest { "..." } else { "" }, param.text, default)
+                })
+                .collect();
+
+            format!("Function ({})", params.join(", "))
+        }
+        Term::Import(x) => format!("Import {:?} {:?}", x.path, x.alias),
+        Term::Int(x) => format!("Int {}", x.value),
+        Term::Let(x) => match &x.pattern {
+            Some(p) => format!("Let {} {}", pattern(p), x.export),
+            None => format!("Let {} {}", x.name.text, x.export),
+        },
+        Term::Seq(x) => format!("Seq {}", x.expressions.len()),
+        Term::Str(x) => format!("Str {:?}", x.value),
+        Term::Tuple(x) => format!("Tuple {}", x.elements.len()),
+        Term::Var(x) => format!("Var {}", x.text),
+        term => String::from(term.kind()),
+    }
+}
+
+/// Children of a node along with the field holding them.
+fn fields(term: &Term) -> Vec<(String, &Term)> {
+    let named = |names: &[&str]| -> Vec<String> { names.iter().map(|name| String::from(*name)).collect() };
+
+    let names = match term {
+        Term::Access(_) => named(&["module"]),
+        Term::Binary(_) => named(&["lhs", "rhs"]),
+        Term::Call(x) => std::iter::once(String::from("callee"))
+            .chain((0..x.arguments.len()).map(|i| format!("arguments[{}]", i)))
+            .chain((0..x.named.len()).map(|i| format!("named[{}].value", i)))
+            .collect(),
+        Term::Function(x) => x
+            .parameters
+            .iter()
+            .enumerate()
+            .filter(|(_, param)| param.default.is_some())
+            .map(|(i, _)| format!("parameters[{}].default", i))
+            .chain([String::from("value")])
+            .collect(),
+        Term::If(_) => named(&["condition", "then", "otherwise"]),
+        Term::Let(_) => named(&["value", "next"]),
+        Term::Seq(x) => (0..x.expressions.len()).map(|i| format!("expressions[{}]", i)).collect(),
+        Term::Try(_) => named(&["body", "handler"]),
+        Term::Tuple(x) => (0..x.elements.len()).map(|i| format!("elements[{}]", i)).collect(),
+        _ => named(&["value"]),
+    };
+
+    names.into_iter().zip(term.children()).collect()
+}
+
+fn walk(path: &str, a: &Term, b: &Term, locations: bool, changes: &mut Vec<Change>) {
+    if label(a) != label(b) {
+        changes.push(Change {
+            path: String::from(path),
+            left: String::from(format::format(a).trim_end()),
+            right: String::from(format::format(b).trim_end()),
+        });
+
+        return;
+    }
+
+    let (x, y) = (a.location(), b.location());
+
+    if locations && (x.start, x.end) != (y.start, y.end) {
+        changes.push(Change {
+            path: format!("{}{}location", path, if path.is_empty() { "" } else { "." }),
+            left: format!("{}..{}", x.start, x.end),
+            right: format!("{}..{}", y.start, y.end),
+        });
+    }
+
+    for ((name, a), (_, b)) in fields(a).into_iter().zip(fields(b)) {
+        let path = if path.is_empty() { name } else { format!("{}.{}", path, name) };
+
+        walk(&path, a, b, locations, changes);
+    }
+}
+
+/// Outermost subtrees where `b` differs from `a`, in source order. Locations
+/// are only compared when `locations` is set, filenames never are.
+pub fn diff(a: &Term, b: &Term, locations: bool) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    walk("", a, b, locations, &mut changes);
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn changes(a: &str, b: &str, locations: bool) -> Vec<Change> {
+        let (a, b) = (parse(a, "a").unwrap(), parse(b, "b").unwrap());
+
+        diff(&a.expression, &b.expression, locations)
+    }
+
+    #[test]
+    fn structure() {
+        assert_eq!(changes("let x = 1 + 2; x", "let x = (1 +   2); x", false), []);
+
+        let res = changes("let x = 1 + 2; print(x)", "let x = 1 - 2; print(x * 2)", false);
+
+        assert_eq!(
+            res,
+            [
+                Change {
+                    path: String::from("value"),
+                    left: String::from("1 + 2"),
+                    right: String::from("1 - 2"),
+                },
+                Change {
+                    path: String::from("next.value"),
+                    left: String::from("x"),
+                    right: String::from("x * 2"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn locations() {
+        let res = changes("let x = 1; x", "let x =  1; x", true);
+
+        assert_eq!(res[0].path, "location");
+        assert_eq!(res[1].path, "value.location");
+        assert_eq!((res[1].left.as_str(), res[1].right.as_str()), ("8..9", "9..10"));
+    }
+}
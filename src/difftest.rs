@@ -0,0 +1,386 @@
+//! Differential testing against another rinha interpreter. Both run the same
+//! JSON AST as separate processes and must agree on stdout and exit status.
+//! A program on which they do not is shrunk, one node at a time, to a smaller
+//! one on which they still disagree the same way.
+
+use std::{
+    env, fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+use crate::{
+    ast::{File, Int, Str, Term},
+    encoding::Encoding,
+};
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Code(i32),
+    /// Ended by a signal, e.g. a crash.
+    Killed,
+    TimedOut,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Code(code) => write!(f, "exit {}", code),
+            Status::Killed => write!(f, "killed"),
+            Status::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub stdout: String,
+    pub status: Status,
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, stdout {:?}", self.status, self.stdout)
+    }
+}
+
+/// Runs of one program by both interpreters that do not agree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub ours: Run,
+    pub reference: Run,
+}
+
+impl Divergence {
+    /// Whether `other` disagrees as this one does: on the output while
+    /// ending the same way, or by ending in different ways.
+    fn alike(&self, other: &Divergence) -> bool {
+        (self.ours.status, self.reference.status) == (other.ours.status, other.reference.status)
+    }
+}
+
+pub struct Harness {
+    /// Command running this interpreter, the program's path appended.
+    pub ours: Vec<String>,
+    /// Command running the reference interpreter, likewise.
+    pub reference: Vec<String>,
+    /// Runs taking longer are killed and count as `Status::TimedOut`.
+    pub timeout: Duration,
+    /// Most pairs of runs spent shrinking one program.
+    pub budget: usize,
+}
+
+/// Programs among `paths`, directories standing for the rinha sources and
+/// ASTs right in them, in a stable order.
+pub fn programs(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut programs = Vec::new();
+
+    for path in paths {
+        if !path.is_dir() {
+            programs.push(path.clone());
+
+            continue;
+        }
+
+        let mut found = Vec::new();
+
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+
+            if path.is_file() && (path.extension().is_some_and(|ext| ext == "rinha") || Encoding::of(&path).is_some()) {
+                found.push(path);
+            }
+        }
+
+        found.sort();
+        programs.extend(found);
+    }
+
+    Ok(programs)
+}
+
+/// Runs `command` with `path` as its last argument, killing it after
+/// `timeout`.
+fn run(command: &[String], path: &Path, timeout: Duration) -> io::Result<Run> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    };
+
+    let mut child = process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Read while waiting, a program printing more than the pipe holds would
+    // block forever otherwise.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut bytes = Vec::new();
+
+        stdout.read_to_end(&mut bytes).map(|_| bytes)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status.code().map_or(Status::Killed, Status::Code);
+        }
+
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+
+            break Status::TimedOut;
+        }
+
+        thread::sleep(Duration::from_millis(2));
+    };
+
+    let stdout = reader.join().expect("reading stdout does not panic")?;
+
+    Ok(Run {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        status,
+    })
+}
+
+impl Harness {
+    /// Runs the JSON AST at `path` with both interpreters, None when they
+    /// agree.
+    pub fn compare(&self, path: &Path) -> io::Result<Option<Divergence>> {
+        let ours = run(&self.ours, path, self.timeout)?;
+        let reference = run(&self.reference, path, self.timeout)?;
+
+        Ok((ours != reference).then_some(Divergence { ours, reference }))
+    }
+
+    /// Writes `prog` as a JSON AST to a temporary file and compares the runs
+    /// of it.
+    pub fn compare_file(&self, prog: &File) -> io::Result<Option<Divergence>> {
+        let path = scratch();
+        let json = serde_json::to_string(prog).map_err(io::Error::other)?;
+
+        fs::write(&path, json)?;
+
+        let divergence = self.compare(&path);
+        let _ = fs::remove_file(&path);
+
+        divergence
+    }
+
+    /// The smallest program found that diverges like `divergence`, the one
+    /// of `prog`, along with how it does. Gives up shrinking after `budget`
+    /// candidates.
+    pub fn minimize(&self, prog: &File, divergence: &Divergence) -> io::Result<(File, Divergence)> {
+        let mut found = divergence.clone();
+        let mut failure = None;
+
+        let expression = minimize(&prog.expression, self.budget, |expression| {
+            if failure.is_some() {
+                return false;
+            }
+
+            let candidate = File {
+                name: prog.name.clone(),
+                expression: expression.clone(),
+                location: prog.location.clone(),
+            };
+
+            match self.compare_file(&candidate) {
+                Ok(Some(divergence)) if divergence.alike(&found) => {
+                    found = divergence;
+
+                    true
+                }
+                Ok(_) => false,
+                Err(err) => {
+                    failure = Some(err);
+
+                    false
+                }
+            }
+        });
+
+        if let Some(err) = failure {
+            return Err(err);
+        }
+
+        let prog = File {
+            name: prog.name.clone(),
+            expression,
+            location: prog.location.clone(),
+        };
+
+        Ok((prog, found))
+    }
+}
+
+/// Path of a file no other run of the harness writes to at the same time.
+fn scratch() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    env::temp_dir().join(format!("rinha-difftest-{}-{}.json", process::id(), n))
+}
+
+/// Shrinks `term` for as long as `interesting` holds for one of the smaller
+/// terms `shrink` makes of it, trying at most `budget` of them. Larger cuts
+/// are tried first, near the root.
+pub fn minimize(term: &Term, budget: usize, mut interesting: impl FnMut(&Term) -> bool) -> Term {
+    let mut term = term.clone();
+    let mut index = 0;
+
+    for _ in 0..budget {
+        let Some(candidate) = shrink(&term, index) else {
+            break;
+        };
+
+        if interesting(&candidate) {
+            term = candidate;
+            index = 0;
+        } else {
+            index += 1;
+        }
+    }
+
+    term
+}
+
+/// The `index`th smaller version of `term`, counting every edit of every
+/// node in preorder: replacing the node by one of its subterms, or a literal
+/// by a smaller one. None past the last.
+fn shrink(term: &Term, index: usize) -> Option<Term> {
+    let mut term = term.clone();
+    let mut index = index;
+
+    edit(&mut term, &mut index).then_some(term)
+}
+
+fn edit(term: &mut Term, index: &mut usize) -> bool {
+    let children = term.children().len();
+
+    if *index < children {
+        *term = term.children()[*index].clone();
+
+        return true;
+    }
+
+    *index -= children;
+
+    let smaller = match term {
+        Term::Int(x) if !x.value.is_zero() => Some(Term::Int(Int {
+            value: &x.value / BigInt::from(2),
+            location: x.location.clone(),
+        })),
+        Term::Str(x) if !x.value.is_empty() => {
+            let mut chars = x.value.chars();
+
+            chars.next_back();
+
+            Some(Term::Str(Str {
+                value: String::from(chars.as_str()),
+                location: x.location.clone(),
+            }))
+        }
+        _ => None,
+    };
+
+    if let Some(smaller) = smaller {
+        if *index == 0 {
+            *term = smaller;
+
+            return true;
+        }
+
+        *index -= 1;
+    }
+
+    children_mut(term).into_iter().any(|child| edit(child, index))
+}
+
+/// Direct subterms edits may replace, those of `Term::children` but the
+/// defaults of parameters.
+fn children_mut(term: &mut Term) -> Vec<&mut Term> {
+    match term {
+        Term::Access(x) => vec![&mut x.module],
+        Term::Binary(x) => vec![&mut x.lhs, &mut x.rhs],
+        Term::Call(x) | Term::Recur(x) => std::iter::once(&mut *x.callee)
+            .chain(&mut x.arguments)
+            .chain(x.named.iter_mut().map(|arg| &mut arg.value))
+            .collect(),
+        Term::First(x) => vec![&mut x.value],
+        Term::Function(x) => vec![Arc::make_mut(&mut x.value)],
+        Term::If(x) => vec![&mut x.condition, &mut x.then, &mut x.otherwise],
+        Term::Let(x) => vec![&mut x.value, &mut x.next],
+        Term::Print(x) => vec![&mut x.value],
+        Term::Second(x) => vec![&mut x.value],
+        Term::Seq(x) => x.expressions.iter_mut().collect(),
+        Term::Try(x) => vec![&mut x.body, &mut x.handler],
+        Term::Tuple(x) => x.elements.iter_mut().collect(),
+        Term::Bool(_) | Term::Import(_) | Term::Int(_) | Term::Str(_) | Term::Var(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format::format, rinha};
+
+    #[test]
+    fn minimize() {
+        let prog = rinha! {
+            let f = fn (n) => { n * 2 };
+            let s = "abc" + f(40);
+            print((s, f(3)))
+        };
+
+        // Anything still adding a string to something is interesting.
+        let smallest = super::minimize(&prog, 1000, |term| format(term).contains("\" +"));
+
+        assert_eq!(format(&smallest), "\"\" + f\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn compare() {
+        // Scripts get the path as $1.
+        let sh = |script: &str| vec![String::from("sh"), String::from("-c"), String::from(script), String::from("sh")];
+        let mut harness = Harness {
+            ours: sh("cat \"$1\""),
+            reference: sh("cat \"$1\""),
+            timeout: Duration::from_secs(10),
+            budget: 100,
+        };
+        let path = Path::new("./json/truthy.rinha");
+
+        assert_eq!(harness.compare(path).unwrap(), None);
+
+        harness.reference = sh("cat \"$1\"; exit 3");
+
+        let divergence = harness.compare(path).unwrap().unwrap();
+
+        assert_eq!(divergence.ours.status, Status::Code(0));
+        assert_eq!(divergence.reference.status, Status::Code(3));
+        assert_eq!(divergence.ours.stdout, divergence.reference.stdout);
+
+        harness.reference = sh("exec sleep 5");
+        harness.timeout = Duration::from_millis(50);
+
+        assert_eq!(harness.compare(path).unwrap().unwrap().reference.status, Status::TimedOut);
+    }
+}
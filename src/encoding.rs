@@ -0,0 +1,102 @@
+//! Ways an AST is written down. JSON is the format of the rinha spec, YAML
+//! is easier on hand-written programs and MessagePack is smaller and faster
+//! to read for the huge generated ASTs of stress tests. All of them hold the
+//! same nodes with the same fields. Precompiled `.rast` files are faster
+//! still to load, see `rast`.
+
+use serde_json::Value;
+use std::{path::Path, str::FromStr};
+
+use crate::{ast::File, malformed, rast};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    Yaml,
+    MessagePack,
+    Precompiled,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "json" => Ok(Encoding::Json),
+            "yaml" => Ok(Encoding::Yaml),
+            "msgpack" => Ok(Encoding::MessagePack),
+            "rast" => Ok(Encoding::Precompiled),
+            _ => Err(format!("Unknown format {:?}, expected json, yaml, msgpack or rast", name)),
+        }
+    }
+}
+
+impl Encoding {
+    /// Encoding of an AST by the extension of its file, None for anything
+    /// else, such as rinha source code.
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Encoding::Json),
+            "yaml" | "yml" => Some(Encoding::Yaml),
+            "msgpack" | "mpk" => Some(Encoding::MessagePack),
+            "rast" => Some(Encoding::Precompiled),
+            _ => None,
+        }
+    }
+
+    /// Reads the AST in `bytes`, what is wrong with it told by the path of
+    /// the node at fault as for JSON, see `malformed`.
+    pub fn decode(&self, bytes: &[u8]) -> Result<File, String> {
+        match self {
+            Encoding::Json => {
+                let source = std::str::from_utf8(bytes).map_err(|err| err.to_string())?;
+
+                crate::parse_json(source).map_err(|err| malformed::explain(source, &err))
+            }
+            Encoding::Yaml => serde_yaml::from_slice(bytes).map_err(|err| {
+                let value = serde_yaml::from_slice::<Value>(bytes).ok();
+
+                value.and_then(|value| malformed::fault(&value)).unwrap_or_else(|| err.to_string())
+            }),
+            Encoding::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| {
+                let value = rmp_serde::from_slice::<Value>(bytes).ok();
+
+                value.and_then(|value| malformed::fault(&value)).unwrap_or_else(|| err.to_string())
+            }),
+            Encoding::Precompiled => rast::decode(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn of() {
+        assert_eq!(Encoding::of(Path::new("a.json")), Some(Encoding::Json));
+        assert_eq!(Encoding::of(Path::new("a.yml")), Some(Encoding::Yaml));
+        assert_eq!(Encoding::of(Path::new("a.msgpack")), Some(Encoding::MessagePack));
+        assert_eq!(Encoding::of(Path::new("a.rinha")), None);
+        assert_eq!("yaml".parse(), Ok(Encoding::Yaml));
+    }
+
+    #[test]
+    fn decode() {
+        let json = std::fs::read("./json/fib.json").unwrap();
+        let value: Value = serde_json::from_slice(&json).unwrap();
+        let expected = Encoding::Json.decode(&json).unwrap().expression;
+
+        let yaml = serde_yaml::to_string(&value).unwrap();
+        let msgpack = rmp_serde::to_vec_named(&value).unwrap();
+
+        assert_eq!(Encoding::Yaml.decode(yaml.as_bytes()).unwrap().expression, expected);
+        assert_eq!(Encoding::MessagePack.decode(&msgpack).unwrap().expression, expected);
+        assert_eq!(
+            Encoding::Yaml.decode(yaml.replacen("kind: Let", "kind: Lett", 1).as_bytes()).unwrap_err(),
+            "at $.expression: unknown kind \"Lett\", did you mean \"Let\"? expected one of Access, Binary, Bool, \
+             Call, First, Function, If, Import, Int, Let, Print, Second, Seq, Str, Try, Tuple, Var"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! C ABI used to embed the evaluator, see `include/rinha.h`.
+
+use num_bigint::BigInt;
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    ptr,
+};
+
+use crate::{host::CaptureHost, malformed, parse_json, Context, Interpreter, Output};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RinhaKind {
+    Void,
+    Bool,
+    Int,
+    Str,
+    Tuple,
+    Closure,
+    Error,
+    Module,
+    Task,
+    Channel,
+}
+
+/// Value crossing the boundary in its printed form: `true`/`false` for bools,
+/// decimal digits for ints and the raw contents for strings.
+#[repr(C)]
+pub struct RinhaValue {
+    pub kind: RinhaKind,
+    pub text: *const c_char,
+}
+
+pub type RinhaBuiltin =
+    extern "C" fn(userdata: *mut c_void, args: *const RinhaValue, argc: usize) -> RinhaValue;
+
+#[repr(C)]
+pub struct RinhaError {
+    pub message: *mut c_char,
+    pub filename: *mut c_char,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[repr(C)]
+pub struct RinhaResult {
+    pub ok: bool,
+    pub kind: RinhaKind,
+    pub value: *mut c_char,
+    pub output: *mut c_char,
+    pub error: RinhaError,
+}
+
+pub struct RinhaInterpreter(Interpreter<CaptureHost>);
+
+fn kind(output: &Output) -> RinhaKind {
+    match output {
+        // thunks never escape the evaluator
+        Output::Thunk(_) | Output::Void => RinhaKind::Void,
+        Output::Bool(_) => RinhaKind::Bool,
+        Output::Int(_) => RinhaKind::Int,
+        Output::Str(_) => RinhaKind::Str,
+        Output::Tuple(_) => RinhaKind::Tuple,
+        Output::Closure(_) => RinhaKind::Closure,
+        Output::Task(_) => RinhaKind::Task,
+        Output::Channel(_) => RinhaKind::Channel,
+        Output::Module(_) => RinhaKind::Module,
+    }
+}
+
+fn c_string(text: &str) -> *mut c_char {
+    // interior nul bytes cannot be represented, truncate at the first one
+    let text = text.split('\0').next().unwrap_or_default();
+
+    CString::new(text).unwrap_or_default().into_raw()
+}
+
+unsafe fn from_value(value: &RinhaValue) -> Result<Output, String> {
+    let text = if value.text.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(value.text).to_string_lossy().into_owned()
+    };
+
+    match value.kind {
+        RinhaKind::Void => Ok(Output::Void),
+        RinhaKind::Bool => Ok(Output::Bool(text == "true")),
+        RinhaKind::Int => text
+            .parse::<BigInt>()
+            .map(Output::Int)
+            .map_err(|_| format!("Builtin returned an invalid int: {}", text)),
+        RinhaKind::Str => Ok(Output::Str(text.into())),
+        RinhaKind::Error => Err(text),
+        RinhaKind::Tuple | RinhaKind::Closure | RinhaKind::Module | RinhaKind::Task | RinhaKind::Channel => {
+            Err(String::from("Builtins can only return primitive values"))
+        }
+    }
+}
+
+/// Builtin calling the C function `builtin` with the arguments in their
+/// printed form.
+pub(crate) fn adapt<H>(
+    builtin: RinhaBuiltin,
+    userdata: *mut c_void,
+) -> impl Fn(&mut H, Vec<Output>) -> Result<Output, String> {
+    move |_, args| {
+        let texts: Vec<CString> = args
+            .iter()
+            .map(|arg| CString::new(arg.to_string().replace('\0', "")).unwrap_or_default())
+            .collect();
+        let values: Vec<RinhaValue> = args
+            .iter()
+            .zip(&texts)
+            .map(|(arg, text)| RinhaValue {
+                kind: kind(arg),
+                text: text.as_ptr(),
+            })
+            .collect();
+
+        let res = builtin(userdata, values.as_ptr(), values.len());
+
+        unsafe { from_value(&res) }
+    }
+}
+
+fn success(output: &Output, stdout: String) -> RinhaResult {
+    RinhaResult {
+        ok: true,
+        kind: kind(output),
+        value: c_string(&output.to_string()),
+        output: c_string(&stdout),
+        error: RinhaError {
+            message: ptr::null_mut(),
+            filename: ptr::null_mut(),
+            start: 0,
+            end: 0,
+        },
+    }
+}
+
+fn failure(message: &str, filename: Option<&str>, start: usize, end: usize, stdout: String) -> RinhaResult {
+    RinhaResult {
+        ok: false,
+        kind: RinhaKind::Error,
+        value: ptr::null_mut(),
+        output: c_string(&stdout),
+        error: RinhaError {
+            message: c_string(message),
+            filename: filename.map_or(ptr::null_mut(), c_string),
+            start,
+            end,
+        },
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rinha_interpreter_new() -> *mut RinhaInterpreter {
+    Box::into_raw(Box::new(RinhaInterpreter(Interpreter::new(CaptureHost::default()))))
+}
+
+/// # Safety
+///
+/// `interpreter` must come from `rinha_interpreter_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rinha_interpreter_free(interpreter: *mut RinhaInterpreter) {
+    if !interpreter.is_null() {
+        drop(Box::from_raw(interpreter));
+    }
+}
+
+/// Registers `builtin` under `name`, `userdata` is handed back on every call.
+/// Strings returned by the builtin are copied and stay owned by the caller.
+///
+/// # Safety
+///
+/// `interpreter` must be live and `name` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rinha_interpreter_register(
+    interpreter: *mut RinhaInterpreter,
+    name: *const c_char,
+    builtin: RinhaBuiltin,
+    userdata: *mut c_void,
+) -> bool {
+    if interpreter.is_null() || name.is_null() {
+        return false;
+    }
+
+    let Ok(name) = CStr::from_ptr(name).to_str() else {
+        return false;
+    };
+
+    (*interpreter).0.register(name, adapt(builtin, userdata));
+
+    true
+}
+
+/// # Safety
+///
+/// `interpreter` must be live and `json` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rinha_interpreter_eval_json(
+    interpreter: *mut RinhaInterpreter,
+    json: *const c_char,
+) -> RinhaResult {
+    if interpreter.is_null() || json.is_null() {
+        return failure("Invalid null argument", None, 0, 0, String::new());
+    }
+
+    let interpreter = &mut (*interpreter).0;
+
+    let Ok(source) = CStr::from_ptr(json).to_str() else {
+        return failure("Program is not valid UTF-8", None, 0, 0, String::new());
+    };
+
+    let prog = match parse_json(source) {
+        Ok(prog) => prog,
+        Err(err) => return failure(&malformed::explain(source, &err), None, 0, 0, String::new()),
+    };
+
+    let res = interpreter.eval(&prog.expression, &mut Context::default());
+    let stdout = interpreter.host_mut().take_output();
+
+    match res {
+        Ok(output) => success(&output, stdout),
+        Err(err) => failure(&err.message, Some(&err.filename), err.start, err.end, stdout),
+    }
+}
+
+/// Evaluates a program on a fresh interpreter without extra builtins.
+///
+/// # Safety
+///
+/// `json` must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rinha_eval_json(json: *const c_char) -> RinhaResult {
+    let interpreter = rinha_interpreter_new();
+    let res = rinha_interpreter_eval_json(interpreter, json);
+
+    rinha_interpreter_free(interpreter);
+
+    res
+}
+
+/// # Safety
+///
+/// `result` must come from one of the eval functions and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn rinha_result_free(result: RinhaResult) {
+    for text in [result.value, result.output, result.error.message, result.error.filename] {
+        if !text.is_null() {
+            drop(CString::from_raw(text));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    extern "C" fn double(_: *mut c_void, args: *const RinhaValue, argc: usize) -> RinhaValue {
+        static RESULT: &[u8] = b"42\0";
+
+        assert_eq!(argc, 1);
+        assert_eq!(unsafe { (*args).kind }, RinhaKind::Int);
+
+        RinhaValue {
+            kind: RinhaKind::Int,
+            text: RESULT.as_ptr() as *const c_char,
+        }
+    }
+
+    #[test]
+    fn eval_json() {
+        let json = CString::new(fs::read_to_string("./json/fib.json").unwrap()).unwrap();
+
+        unsafe {
+            let res = rinha_eval_json(json.as_ptr());
+
+            assert!(res.ok);
+            assert_eq!(res.kind, RinhaKind::Int);
+            assert_eq!(CStr::from_ptr(res.value).to_str(), Ok("55"));
+
+            rinha_result_free(res);
+        }
+    }
+
+    #[test]
+    fn eval_json_error() {
+        let json = CString::new(fs::read_to_string("./json/div2.json").unwrap()).unwrap();
+
+        unsafe {
+            let res = rinha_eval_json(json.as_ptr());
+
+            assert!(!res.ok);
+            assert_eq!(
+                CStr::from_ptr(res.error.message).to_str(),
+                Ok("Arithmetic error, dividing by zero")
+            );
+
+            rinha_result_free(res);
+        }
+    }
+
+    #[test]
+    fn register() {
+        let json = CString::new(fs::read_to_string("./json/builtin1.json").unwrap()).unwrap();
+        let name = CString::new("double").unwrap();
+
+        unsafe {
+            let interpreter = rinha_interpreter_new();
+
+            assert!(rinha_interpreter_register(interpreter, name.as_ptr(), double, ptr::null_mut()));
+
+            let res = rinha_interpreter_eval_json(interpreter, json.as_ptr());
+
+            assert!(res.ok);
+            assert_eq!(CStr::from_ptr(res.value).to_str(), Ok("42"));
+
+            rinha_result_free(res);
+            rinha_interpreter_free(interpreter);
+        }
+    }
+}
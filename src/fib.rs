@@ -1,4 +1,6 @@
 use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Zero};
 
 type Matrix2x2 = [[BigInt; 2]; 2];
 
@@ -13,34 +15,48 @@ fn __matrix__x(a: &Matrix2x2, b: &Matrix2x2) -> Matrix2x2 {
 }
 
 fn __pow(matrix: &Matrix2x2, nth: BigInt) -> Matrix2x2 {
-    if &nth == &BigInt::from(1) {
+    if nth.is_one() {
         matrix.clone()
-    } else if &nth % 2 == BigInt::from(0) {
-        let half_pow = __pow(matrix, nth / 2);
+    } else if nth.is_even() {
+        let half_pow = __pow(matrix, nth / 2u32);
 
         __matrix__x(&half_pow, &half_pow)
     } else {
-        __matrix__x(&matrix, &__pow(&matrix, nth - 1))
+        __matrix__x(matrix, &__pow(matrix, nth - 1u32))
     }
 }
 
 pub fn __fib_matrix(nth: BigInt) -> BigInt {
-    let init = [[BigInt::from(1), BigInt::from(1)], [BigInt::from(1), BigInt::from(0)]];
+    let init = [[BigInt::one(), BigInt::one()], [BigInt::one(), BigInt::zero()]];
     let res = __pow(&init, nth);
 
     res[1][0].clone()
 }
 
-pub fn __fib_iter(nth: BigInt) -> BigInt {
-    let mut a: BigInt = BigInt::from(0);
-    let mut b: BigInt = BigInt::from(1);
+pub fn __fib_iter(nth: u64) -> BigInt {
+    let mut a = BigInt::zero();
+    let mut b = BigInt::one();
 
-    let mut i: BigInt = BigInt::from(0);
-    while i < nth {
-        [b, a] = [&a + &b, b];
-
-        i += 1;
+    for _ in 0..nth {
+        // reuse the buffer of the older term instead of allocating a new sum
+        a += &b;
+        std::mem::swap(&mut a, &mut b);
     }
 
     a
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies() {
+        for n in 1..100u64 {
+            assert_eq!(__fib_iter(n), __fib_matrix(BigInt::from(n)));
+        }
+
+        assert_eq!(__fib_iter(0), BigInt::zero());
+        assert_eq!(__fib_iter(10), BigInt::from(55));
+    }
+}
@@ -0,0 +1,452 @@
+use crate::ast::*;
+
+const INDENT: &str = "  ";
+
+fn precedence(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Pipe => 0,
+        BinaryOp::Compose => 1,
+        BinaryOp::Or => 2,
+        BinaryOp::And => 3,
+        BinaryOp::Eq | BinaryOp::Neq => 4,
+        BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Lte | BinaryOp::Gte => 5,
+        BinaryOp::BitOr => 6,
+        BinaryOp::BitXor => 7,
+        BinaryOp::BitAnd => 8,
+        BinaryOp::Shl | BinaryOp::Shr => 9,
+        BinaryOp::Add | BinaryOp::Sub => 10,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 11,
+    }
+}
+
+pub(crate) fn symbol(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitXor => "^",
+        BinaryOp::Shl => "<<<",
+        BinaryOp::Shr => ">>>",
+        BinaryOp::Eq => "==",
+        BinaryOp::Neq => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gt => ">",
+        BinaryOp::Lte => "<=",
+        BinaryOp::Gte => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Pipe => "|>",
+        BinaryOp::Compose => ">>",
+    }
+}
+
+/// Quotes `value` as a string literal the parser reads back unchanged.
+pub(crate) fn quote(value: &str) -> String {
+    let mut out = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+
+    out
+}
+
+/// Whether a `let` is the desugared form of an import statement.
+fn is_import(name: &str, value: &Term) -> bool {
+    match value {
+        Term::Import(x) => x.alias.as_deref().unwrap_or("_") == name,
+        _ => false,
+    }
+}
+
+struct Formatter {
+    out: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn newline(&mut self) {
+        self.out.push('\n');
+
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn block(&mut self, term: &Term) {
+        self.out.push('{');
+        self.depth += 1;
+        self.newline();
+        self.statements(term);
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    /// `term` where a sequence needs no braces: in a block, after a `let` and
+    /// at the top of the file. A `let` before the end of a sequence is put in
+    /// a block of its own, or it would bind its names for the rest of it.
+    fn statements(&mut self, term: &Term) {
+        let Term::Seq(x) = term else {
+            return self.term(term);
+        };
+
+        for (i, expression) in x.expressions.iter().enumerate() {
+            if i > 0 {
+                self.out.push(';');
+                self.newline();
+            }
+
+            if i + 1 < x.expressions.len() && matches!(expression, Term::Let(_)) {
+                self.block(expression);
+            } else {
+                self.term(expression);
+            }
+        }
+    }
+
+    fn list<'a>(&mut self, terms: impl IntoIterator<Item = &'a Term>) {
+        for (i, term) in terms.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+
+            self.term(term);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Var(x) => self.out.push_str(&x.text),
+            Pattern::Tuple(x) => {
+                self.out.push('(');
+
+                for (i, element) in x.elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+
+                    self.pattern(element);
+                }
+
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn operand(&mut self, term: &Term, parent: u8, right: bool) {
+        let wrap = match term {
+            Term::Binary(x) => {
+                let prec = precedence(&x.op);
+
+                prec < parent || (right && prec == parent)
+            }
+            Term::Let(_) | Term::If(_) | Term::Function(_) => true,
+            _ => false,
+        };
+
+        if wrap {
+            self.out.push('(');
+            self.term(term);
+            self.out.push(')');
+        } else {
+            self.term(term);
+        }
+    }
+
+    fn term(&mut self, term: &Term) {
+        match term {
+            Term::Int(x) => self.out.push_str(&x.value.to_string()),
+            Term::Str(x) => self.out.push_str(&quote(&x.value)),
+            Term::Bool(x) => self.out.push_str(if x.value { "true" } else { "false" }),
+            Term::Var(x) => self.out.push_str(&x.text),
+            Term::Binary(x) => {
+                let prec = precedence(&x.op);
+
+                self.operand(&x.lhs, prec, false);
+                self.out.push(' ');
+                self.out.push_str(symbol(&x.op));
+                self.out.push(' ');
+                self.operand(&x.rhs, prec, true);
+            }
+            Term::Let(x) if is_import(&x.name.text, &x.value) => {
+                self.term(&x.value);
+                self.out.push(';');
+                self.newline();
+                self.statements(&x.next);
+            }
+            Term::Import(x) => {
+                self.out.push_str("import ");
+                self.out.push_str(&quote(&x.path));
+
+                if let Some(alias) = &x.alias {
+                    self.out.push_str(" as ");
+                    self.out.push_str(alias);
+                }
+            }
+            Term::Access(x) => {
+                match *x.module {
+                    Term::Var(_) | Term::Access(_) | Term::Call(_) => self.term(&x.module),
+                    _ => {
+                        self.out.push('(');
+                        self.term(&x.module);
+                        self.out.push(')');
+                    }
+                }
+
+                self.out.push('.');
+                self.out.push_str(&x.name);
+            }
+            Term::Let(x) => {
+                if x.export {
+                    self.out.push_str("export ");
+                }
+
+                self.out.push_str("let ");
+
+                match &x.pattern {
+                    Some(pattern) => self.pattern(pattern),
+                    None => self.out.push_str(&x.name.text),
+                }
+
+                self.out.push_str(" = ");
+                self.term(&x.value);
+                self.out.push(';');
+                self.newline();
+                self.statements(&x.next);
+            }
+            Term::Seq(_) => self.block(term),
+            Term::Function(x) => {
+                self.out.push_str("fn (");
+
+                for (i, param) in x.parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+
+                    if param.rest {
+                        self.out.push_str("...");
+                    }
+
+                    self.out.push_str(&param.text);
+
+                    if let Some(default) = &param.default {
+                        self.out.push_str(" = ");
+                        self.term(default);
+                    }
+                }
+
+                self.out.push_str(") => ");
+                self.block(&x.value);
+            }
+            Term::Try(x) => {
+                self.out.push_str("try ");
+                self.block(&x.body);
+                self.out.push_str(" catch ");
+
+                match *x.handler {
+                    Term::Var(_) | Term::Access(_) | Term::Call(_) | Term::Function(_) => self.term(&x.handler),
+                    _ => {
+                        self.out.push('(');
+                        self.term(&x.handler);
+                        self.out.push(')');
+                    }
+                }
+            }
+            Term::If(x) => {
+                self.out.push_str("if (");
+                self.term(&x.condition);
+                self.out.push_str(") ");
+                self.block(&x.then);
+                self.out.push_str(" else ");
+                self.block(&x.otherwise);
+            }
+            Term::Call(x) | Term::Recur(x) => {
+                match *x.callee {
+                    Term::Var(_) | Term::Call(_) | Term::Access(_) => self.term(&x.callee),
+                    _ => {
+                        self.out.push('(');
+                        self.term(&x.callee);
+                        self.out.push(')');
+                    }
+                }
+
+                self.out.push('(');
+                self.list(&x.arguments);
+
+                for (i, arg) in x.named.iter().enumerate() {
+                    if i > 0 || !x.arguments.is_empty() {
+                        self.out.push_str(", ");
+                    }
+
+                    self.out.push_str(&arg.name);
+                    self.out.push_str(" = ");
+                    self.term(&arg.value);
+                }
+
+                self.out.push(')');
+            }
+            Term::Tuple(x) => {
+                self.out.push('(');
+                self.list(&x.elements);
+                self.out.push(')');
+            }
+            Term::Print(x) => {
+                self.out.push_str("print(");
+                self.term(&x.value);
+                self.out.push(')');
+            }
+            Term::First(x) => {
+                self.out.push_str("first(");
+                self.term(&x.value);
+                self.out.push(')');
+            }
+            Term::Second(x) => {
+                self.out.push_str("second(");
+                self.term(&x.value);
+                self.out.push(')');
+            }
+        }
+    }
+}
+
+/// Renders `term` back as rinha source code in the canonical layout.
+pub fn format(term: &Term) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        depth: 0,
+    };
+
+    formatter.statements(term);
+    formatter.out.push('\n');
+
+    formatter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn layout() {
+        let prog = parse(
+            "let f = fn (a, b) => if (a < b) { (a - (b - 1)) * 2 } else { f(b, a) }; print(f(1, 2))",
+            "test",
+        )
+        .unwrap();
+
+        assert_eq!(
+            format(&prog.expression),
+            "let f = fn (a, b) => {
+  if (a < b) {
+    (a - (b - 1)) * 2
+  } else {
+    f(b, a)
+  }
+};
+print(f(1, 2))
+"
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let source = "let x = (1 + 2) * -3 == 4 || first((true, \"a\")); (fn (y, w = y + 1, ...z) => { y })(x, w = 2) |> len >> len";
+        let prog = parse(source, "test").unwrap();
+        let formatted = format(&prog.expression);
+
+        assert_eq!(format(&parse(&formatted, "test").unwrap().expression), formatted);
+
+        let prog = parse("(1 | 2) & 3 ^ 4 <<< (5 >>> -1) + 6", "test").unwrap();
+
+        assert_eq!(format(&prog.expression), "(1 | 2) & 3 ^ 4 <<< (5 >>> -1) + 6\n");
+    }
+
+    #[test]
+    fn import() {
+        let prog = parse("import \"math.rinha\" double(2)", "test").unwrap();
+
+        assert_eq!(format(&prog.expression), "import \"math.rinha\";\ndouble(2)\n");
+    }
+
+    #[test]
+    fn module() {
+        let source = "import \"math.rinha\" as math;\nexport let x = math.gcd(2, 4);\nx\n";
+        let prog = parse(source, "test").unwrap();
+
+        assert_eq!(format(&prog.expression), source);
+    }
+
+    #[test]
+    fn escapes() {
+        let source = "print(\"tab\\there\\n\\\"quoted\\\" \\\\ \\u{1b}\")\n";
+        let prog = parse(source, "test").unwrap();
+
+        assert_eq!(format(&prog.expression), source);
+    }
+
+    #[test]
+    fn sequence() {
+        let source = "let f = fn (x) => {
+  print(x);
+  x + 1
+};
+let y = {
+  f(1);
+  f(2)
+};
+{
+  let z = y;
+  z
+};
+y
+";
+        let prog = parse(source, "test").unwrap();
+
+        assert_eq!(format(&prog.expression), source);
+    }
+
+    #[test]
+    fn try_catch() {
+        let source = "let x = try {
+  1 / 0
+} catch fn (err) => {
+  0
+};
+try {
+  x
+} catch (if (x) {
+  f
+} else {
+  g
+})
+";
+        let prog = parse(source, "test").unwrap();
+
+        assert_eq!(format(&prog.expression), source);
+    }
+
+    #[test]
+    fn pattern() {
+        let source = "let (a, (b, c)) = (1, (2, 3));\na + b + c\n";
+        let prog = parse(source, "test").unwrap();
+
+        assert_eq!(format(&prog.expression), source);
+    }
+}
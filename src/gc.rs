@@ -0,0 +1,135 @@
+//! Cycle collection for the scopes and thunks the evaluator shares through
+//! `Rc`. Reference counting frees everything else as soon as it is dropped,
+//! what it cannot free are groups of objects only referenced by each other,
+//! e.g. a lazy binding whose suspended scope is the one it is bound in.
+//!
+//! Objects referenced from anywhere but other tracked objects (the Rust
+//! stack, an embedder, a scope made with `Context::default`) are roots. A
+//! collection counts the references tracked objects hold to each other,
+//! keeps whatever is reachable from a root and empties the rest, which breaks
+//! the cycles and lets the counts drop to zero.
+
+use std::{
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+/// An object the collector can look into.
+pub(crate) trait Trace {
+    /// Pushes the address of every tracked object this one holds a strong
+    /// reference to, once per reference. False when the object cannot be
+    /// looked into right now, it is then kept along with what it holds.
+    fn trace(&self, edges: &mut Vec<*const ()>) -> bool;
+
+    /// Drops everything the object holds.
+    fn clear(&self);
+}
+
+/// What the collector did so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub collections: usize,
+    /// Objects found unreachable and emptied.
+    pub freed: usize,
+    /// Objects alive after the last collection.
+    pub live: usize,
+}
+
+/// Objects shorter lived than this many new ones are left to reference
+/// counting between collections.
+const MIN_THRESHOLD: usize = 4096;
+
+pub(crate) struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    threshold: usize,
+    stats: GcStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            threshold: MIN_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+}
+
+impl Heap {
+    pub fn track(&mut self, object: Weak<dyn Trace>) {
+        self.objects.push(object);
+    }
+
+    /// Whether enough objects were tracked since the last collection to run
+    /// another one.
+    pub fn due(&self) -> bool {
+        self.objects.len() >= self.threshold
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    pub fn collect(&mut self) {
+        self.objects.retain(|object| object.strong_count() > 0);
+
+        let index: HashMap<*const (), usize> = self
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.as_ptr() as *const (), i))
+            .collect();
+        let mut refs: Vec<usize> = self.objects.iter().map(Weak::strong_count).collect();
+        let mut edges: Vec<Vec<usize>> = vec![Vec::new(); self.objects.len()];
+        let mut out = Vec::new();
+
+        for (i, object) in self.objects.iter().enumerate() {
+            let Some(object) = object.upgrade() else {
+                continue;
+            };
+
+            out.clear();
+
+            if !object.trace(&mut out) {
+                continue;
+            }
+
+            for address in &out {
+                if let Some(&j) = index.get(address) {
+                    refs[j] = refs[j].saturating_sub(1);
+                    edges[i].push(j);
+                }
+            }
+        }
+
+        let mut live = vec![false; self.objects.len()];
+        let mut pending: Vec<usize> = (0..self.objects.len()).filter(|&i| refs[i] > 0).collect();
+
+        while let Some(i) = pending.pop() {
+            if !std::mem::replace(&mut live[i], true) {
+                pending.extend(&edges[i]);
+            }
+        }
+
+        let garbage: Vec<Rc<dyn Trace>> = self
+            .objects
+            .iter()
+            .zip(&live)
+            .filter(|(_, live)| !**live)
+            .filter_map(|(object, _)| object.upgrade())
+            .collect();
+
+        for object in &garbage {
+            object.clear();
+        }
+
+        self.stats.collections += 1;
+        self.stats.freed += garbage.len();
+
+        drop(garbage);
+
+        self.objects.retain(|object| object.strong_count() > 0);
+        self.stats.live = self.objects.len();
+        self.threshold = (self.objects.len() * 2).max(MIN_THRESHOLD);
+    }
+}
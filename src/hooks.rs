@@ -0,0 +1,35 @@
+//! Callbacks observing evaluation, installed with `Interpreter::set_hooks`.
+
+use crate::{ast::Term, Closure, Error, Output};
+
+/// Every method does nothing by default, implement the ones of interest.
+/// Terms are cloned for `on_exit` while hooks are installed, so evaluation
+/// gets slower.
+pub trait EvalHooks {
+    /// Before `term` gets evaluated.
+    fn on_enter(&mut self, term: &Term) {
+        let _ = term;
+    }
+
+    /// After `term` got evaluated, successfully or not.
+    fn on_exit(&mut self, term: &Term, value: &Result<Output, Error>) {
+        let _ = (term, value);
+    }
+
+    /// Before the body of `closure` runs with `args`, builtins excluded.
+    fn on_call(&mut self, closure: &Closure, args: &[Output]) {
+        let _ = (closure, args);
+    }
+
+    /// After the body of `closure` ran, successfully or not. A body ending in
+    /// a tail call returns before that call runs in its place.
+    fn on_return(&mut self, closure: &Closure, value: &Result<Output, Error>) {
+        let _ = (closure, value);
+    }
+
+    /// When `let`, a destructuring pattern or a parameter binds `name`. Under
+    /// lazy evaluation `value` may still be an `Output::Thunk`.
+    fn on_bind(&mut self, name: &str, value: &Output) {
+        let _ = (name, value);
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Everything the evaluator needs from the outside world. Native builds talk
+/// to the process stdout and filesystem, embedders (e.g. the browser build)
+/// plug their own implementation.
+pub trait Host {
+    fn print(&mut self, text: &str);
+
+    /// `text` as `print` writes it, without ending the line. Hosts only
+    /// dealing in lines print it as one.
+    fn write(&mut self, text: &str) {
+        self.print(text);
+    }
+
+    /// Where `eprint` writes, apart from the output of the program so logs
+    /// do not mix with results. The process stderr unless overridden.
+    fn eprint(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+
+    fn read_to_string(&mut self, path: &Path) -> io::Result<String>;
+
+    /// Contents of a file that may not be text, such as a MessagePack AST.
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        self.read_to_string(path).map(String::into_bytes)
+    }
+
+    /// `Ok(None)` for unset variables, `Err` when the program is not allowed
+    /// to read the environment at all.
+    fn getenv(&mut self, name: &str) -> Result<Option<String>, String> {
+        let _ = name;
+
+        Err(String::from("Environment access is not allowed"))
+    }
+
+    /// Arguments given to the program, not to the interpreter.
+    fn args(&self) -> &[String] {
+        &[]
+    }
+
+    /// Milliseconds on a clock only meaningful relative to other readings.
+    fn now(&mut self) -> Result<u128, String> {
+        Err(String::from("Clock is not available"))
+    }
+
+    /// Host for a task `spawn` runs on another thread, None when this host
+    /// cannot run tasks.
+    fn spawn(&mut self) -> Option<Box<dyn Host + Send>> {
+        None
+    }
+}
+
+impl Host for Box<dyn Host + Send> {
+    fn print(&mut self, text: &str) {
+        (**self).print(text)
+    }
+
+    fn write(&mut self, text: &str) {
+        (**self).write(text)
+    }
+
+    fn eprint(&mut self, text: &str) {
+        (**self).eprint(text)
+    }
+
+    fn read_to_string(&mut self, path: &Path) -> io::Result<String> {
+        (**self).read_to_string(path)
+    }
+
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn getenv(&mut self, name: &str) -> Result<Option<String>, String> {
+        (**self).getenv(name)
+    }
+
+    fn args(&self) -> &[String] {
+        (**self).args()
+    }
+
+    fn now(&mut self) -> Result<u128, String> {
+        (**self).now()
+    }
+
+    fn spawn(&mut self) -> Option<Box<dyn Host + Send>> {
+        (**self).spawn()
+    }
+}
+
+/// When what the program prints reaches stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flush {
+    /// After every print.
+    Line,
+    /// Once a few kilobytes are waiting.
+    Block,
+    /// When the program ends.
+    Exit,
+}
+
+/// Bytes buffered before `Flush::Block` writes them out.
+const BLOCK: usize = 8 * 1024;
+
+/// Program output on its way to stdout, whatever is left is written out when
+/// it is dropped. Stdout is locked once per write rather than once per print.
+#[derive(Debug)]
+pub struct Buffered {
+    flush: Flush,
+    buffer: String,
+}
+
+impl Buffered {
+    pub fn new(flush: Flush) -> Self {
+        Self {
+            flush,
+            buffer: String::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.buffer.push_str(text);
+        self.write("\n");
+    }
+
+    /// Appends `text` as it is, a line left open still reaching stdout as
+    /// `flush` says.
+    fn write(&mut self, text: &str) {
+        self.buffer.push_str(text);
+
+        match self.flush {
+            Flush::Line => self.flush(),
+            Flush::Block if self.buffer.len() >= BLOCK => self.flush(),
+            Flush::Block | Flush::Exit => (),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.flush_to(&mut io::stdout().lock());
+    }
+
+    fn flush_to(&mut self, out: &mut impl Write) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        // a closed stdout, e.g. piped into `head`, is dropped from rather
+        // than panicked over, the program runs on regardless
+        let _ = out.write_all(self.buffer.as_bytes()).and_then(|()| out.flush());
+
+        self.buffer.clear();
+    }
+}
+
+/// Every print when stdout is a terminal, blocks otherwise.
+impl Default for Buffered {
+    fn default() -> Self {
+        Self::new(if io::stdout().is_terminal() { Flush::Line } else { Flush::Block })
+    }
+}
+
+impl Drop for Buffered {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StdHost {
+    pub allow_env: bool,
+    pub args: Vec<String>,
+    pub stdout: Buffered,
+    /// When set, everything printed is also appended to it, by the tasks the
+    /// program spawns as well.
+    pub transcript: Option<Arc<Mutex<String>>>,
+}
+
+impl Host for StdHost {
+    fn print(&mut self, text: &str) {
+        self.stdout.push(text);
+
+        if let Some(transcript) = &self.transcript {
+            let mut transcript = transcript.lock().unwrap_or_else(|err| err.into_inner());
+
+            transcript.push_str(text);
+            transcript.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.stdout.write(text);
+
+        if let Some(transcript) = &self.transcript {
+            transcript.lock().unwrap_or_else(|err| err.into_inner()).push_str(text);
+        }
+    }
+
+    fn read_to_string(&mut self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn getenv(&mut self, name: &str) -> Result<Option<String>, String> {
+        if !self.allow_env {
+            return Err(String::from("Environment access is disabled, run with --allow-env"));
+        }
+
+        Ok(env::var(name).ok())
+    }
+
+    fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    fn now(&mut self) -> Result<u128, String> {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .map_err(|err| err.to_string())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn(&mut self) -> Option<Box<dyn Host + Send>> {
+        Some(Box::new(StdHost {
+            allow_env: self.allow_env,
+            args: self.args.clone(),
+            stdout: Buffered::new(self.stdout.flush),
+            transcript: self.transcript.clone(),
+        }))
+    }
+}
+
+/// Keeps everything printed by the program in memory and has no filesystem.
+#[derive(Debug, Default)]
+pub struct CaptureHost {
+    output: String,
+    errors: String,
+}
+
+impl CaptureHost {
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// What the program wrote with `eprint`.
+    pub fn errors(&self) -> &str {
+        &self.errors
+    }
+
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl Host for CaptureHost {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn eprint(&mut self, text: &str) {
+        self.errors.push_str(text);
+        self.errors.push('\n');
+    }
+
+    fn read_to_string(&mut self, path: &Path) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Cannot read {}, no filesystem available", path.display()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffered() {
+        let mut exit = Buffered::new(Flush::Exit);
+        let mut block = Buffered::new(Flush::Block);
+        let mut line = Buffered::new(Flush::Line);
+
+        for stdout in [&mut exit, &mut block, &mut line] {
+            stdout.push("a");
+            stdout.push("b");
+        }
+
+        assert_eq!(exit.buffer, "a\nb\n");
+        assert_eq!(block.buffer, "a\nb\n");
+        assert_eq!(line.buffer, "");
+
+        block.push(&"x".repeat(BLOCK));
+
+        assert_eq!(block.buffer, "");
+
+        line.write("50%");
+
+        assert_eq!(line.buffer, "");
+
+        exit.write("c");
+
+        assert_eq!(exit.buffer, "a\nb\nc");
+    }
+
+    #[test]
+    fn closed() {
+        struct Closed;
+
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let mut stdout = Buffered::new(Flush::Exit);
+
+        stdout.push("lost");
+        stdout.flush_to(&mut Closed);
+
+        assert_eq!(stdout.buffer, "");
+    }
+}
@@ -0,0 +1,3716 @@
+use core::fmt;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::{
+    cell::{Ref, RefCell},
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    rc::{Rc, Weak},
+    sync::Arc,
+};
+
+use crate::ast::*;
+use crate::builtins;
+use crate::cancel::CancellationToken;
+use crate::check::{self, Kind};
+use crate::constants;
+use crate::coverage::Coverage;
+use crate::encoding::Encoding;
+use crate::hooks::EvalHooks;
+use crate::fib::*;
+use crate::format::quote;
+use crate::gc::{GcStats, Heap, Trace};
+use crate::memory::Allocations;
+use crate::host::{Host, StdHost};
+use crate::parser;
+use crate::lint::Warning;
+use crate::specialize::{self, Operands, Sites};
+use crate::stack;
+use crate::semantics::{Lint, Semantics};
+use crate::summary::Counters;
+use crate::symbol::Symbol;
+use crate::tail;
+use crate::task::{self, Channel, Task};
+use crate::text::Text;
+
+/// Why evaluation stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The program went wrong, or could not be loaded.
+    #[default]
+    Failure,
+    /// The token given to `Interpreter::eval_cancellable` was cancelled.
+    Cancelled,
+    /// The program called `exit(code)`, the process exits with `code`.
+    Exit(i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub start: usize,
+    pub end: usize,
+    pub filename: String,
+    pub message: String,
+    pub kind: ErrorKind,
+    /// Where the calls running when a cancelled evaluation stopped were made,
+    /// innermost first. Tail calls replace the call that made them.
+    pub backtrace: Vec<Location>,
+}
+
+impl Error {
+    pub fn new(message: &str, location: Location) -> Self {
+        Self {
+            start: location.start,
+            end: location.end,
+            filename: location.filename,
+            message: String::from(message),
+            kind: ErrorKind::Failure,
+            backtrace: Vec::new(),
+        }
+    }
+
+    /// Evaluation stopped at `location` by a cancelled token.
+    pub fn cancelled(location: Location) -> Self {
+        Self {
+            kind: ErrorKind::Cancelled,
+            ..Self::new("Evaluation was cancelled", location)
+        }
+    }
+
+    /// Evaluation stopped at `location` by `exit(code)`.
+    pub fn exit(code: i32, location: Location) -> Self {
+        Self {
+            kind: ErrorKind::Exit(code),
+            ..Self::new(&format!("Exited with status {}", code), location)
+        }
+    }
+
+    /// `file:line:column: message` when `source` is the text of the file the
+    /// error is in, the offsets as they are otherwise.
+    pub fn render(&self, source: &str) -> String {
+        match Position::of(source, self.start) {
+            Some(at) => format!("{}:{}:{}: {}", self.filename, at.line, at.column, self.message),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // FONT: lineno == start and column == end, see: '[0]. Without the
+        // source these are byte offsets, `render` gives the real line:column
+        //
+        // '[0]: <https://www.gnu.org/prep/standards/standards.html#Errors>
+        write!(f, "{}:{}:{}: {}", self.filename, self.start, self.end, self.message)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Scope {
+    outter: Option<Context>,
+    inner: HashMap<Symbol, Output>,
+    // scope the running closure was created in, it takes precedence over the
+    // caller's scope in `outter`
+    captured: Option<Context>,
+}
+
+/// A scope of bindings. Copies of a context share the scope, a call links
+/// to the scope of its caller instead of copying it.
+#[derive(Clone, Default)]
+pub struct Context(Rc<RefCell<Scope>>);
+
+impl Context {
+    fn find<T>(&self, name: Symbol, f: &impl Fn(&Output) -> T) -> Option<T> {
+        let mut current = self.clone();
+
+        loop {
+            let outter = {
+                let scope = current.0.borrow();
+
+                if let Some(var) = scope.inner.get(&name) {
+                    return Some(f(var));
+                }
+
+                if let Some(var) = scope.captured.as_ref().and_then(|x| x.find(name, f)) {
+                    return Some(var);
+                }
+
+                scope.outter.clone()
+            };
+
+            current = outter?;
+        }
+    }
+
+    pub fn lookup(&self, name: impl Into<Symbol>) -> Option<Output> {
+        self.find(name.into(), &Output::clone)
+    }
+
+    pub fn contains(&self, name: impl Into<Symbol>) -> bool {
+        self.find(name.into(), &|_| ()).is_some()
+    }
+
+    /// Names bound directly in this scope, in no particular order.
+    pub fn bindings(&self) -> Vec<(String, Output)> {
+        self.entries().into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    fn entries(&self) -> Vec<(Symbol, Output)> {
+        self.0.borrow().inner.iter().map(|(name, value)| (*name, value.clone())).collect()
+    }
+
+    /// Every name in reach, bound to what a lookup finds for it.
+    pub(crate) fn visible(&self) -> Vec<(Symbol, Output)> {
+        let mut seen = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut visible = Vec::new();
+        let mut pending = vec![self.clone()];
+
+        // depth first, in lookup order: own bindings, captured, then outter
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current.address()) {
+                continue;
+            }
+
+            let scope = current.0.borrow();
+
+            for (name, value) in &scope.inner {
+                if seen.insert(*name) {
+                    visible.push((*name, value.clone()));
+                }
+            }
+
+            pending.extend(scope.outter.clone());
+            pending.extend(scope.captured.clone());
+        }
+
+        visible
+    }
+
+    pub(crate) fn insert(&self, name: Symbol, value: Output) {
+        self.0.borrow_mut().inner.insert(name, value);
+    }
+
+    /// Binding made in this very scope, not in the ones it links to.
+    fn own(&self, name: Symbol) -> Option<Output> {
+        self.0.borrow().inner.get(&name).cloned()
+    }
+
+    /// Copy of the bindings made in this scope, linked to the same scopes.
+    fn snapshot(&self) -> Scope {
+        let scope = self.0.borrow();
+
+        Scope {
+            outter: scope.outter.clone(),
+            inner: scope.inner.clone(),
+            captured: scope.captured.clone(),
+        }
+    }
+
+    pub(crate) fn address(&self) -> *const () {
+        Rc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl PartialEq for Context {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// scopes can reach themselves through the values bound in them
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ok(scope) = self.0.try_borrow() else {
+            return write!(f, "Context(..)");
+        };
+        let mut names: Vec<&Symbol> = scope.inner.keys().collect();
+
+        names.sort();
+
+        f.debug_tuple("Context").field(&names).finish()
+    }
+}
+
+impl Trace for RefCell<Scope> {
+    fn trace(&self, edges: &mut Vec<*const ()>) -> bool {
+        let Ok(scope) = self.try_borrow() else {
+            return false;
+        };
+
+        edges.extend(scope.outter.iter().chain(&scope.captured).map(Context::address));
+        scope.inner.values().for_each(|value| value.trace(edges));
+
+        true
+    }
+
+    fn clear(&self) {
+        let scope = self.try_borrow_mut().map(|mut scope| std::mem::take(&mut *scope));
+
+        drop(scope);
+    }
+}
+
+/// Values are cloned on every variable read, so the function behind a
+/// closure is shared with the AST it comes from rather than copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub body: Arc<Term>,
+    pub args: Arc<[Parameter]>,
+    pub context: Context,
+}
+
+impl Closure {
+    fn variadic(&self) -> bool {
+        self.args.last().is_some_and(|param| param.rest)
+    }
+
+    /// Parameters every call fills, all of them but a rest parameter.
+    fn fixed(&self) -> usize {
+        self.args.len() - self.variadic() as usize
+    }
+
+    /// Parameters every call has to give, the ones before defaults start.
+    fn required(&self) -> usize {
+        self.args.iter().take_while(|param| !param.rest && param.default.is_none()).count()
+    }
+
+    /// Whether a call with `given` arguments fills every parameter, with
+    /// defaults for the ones left out.
+    pub(crate) fn accepts(&self, given: usize) -> bool {
+        given >= self.required() && (given <= self.fixed() || self.variadic())
+    }
+}
+
+/// Module bound by `import "path" as name`, its closures run in the module's
+/// own scope when called through qualified access.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: String,
+    pub context: Context,
+    pub exports: Vec<Symbol>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Suspension {
+    Pending(Box<Term>, Context),
+    Forcing,
+    Done(Output),
+}
+
+/// Binding deferred by lazy evaluation, computed the first time it is read
+/// and shared by every copy afterwards. Thunks only live in scopes, reading a
+/// variable always gives back a forced value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thunk(Rc<RefCell<Suspension>>);
+
+impl Thunk {
+    pub(crate) fn suspension(&self) -> Ref<'_, Suspension> {
+        self.0.borrow()
+    }
+}
+
+impl Trace for RefCell<Suspension> {
+    fn trace(&self, edges: &mut Vec<*const ()>) -> bool {
+        let Ok(suspension) = self.try_borrow() else {
+            return false;
+        };
+
+        match &*suspension {
+            Suspension::Pending(_, context) => edges.push(context.address()),
+            Suspension::Done(value) => value.trace(edges),
+            Suspension::Forcing => (),
+        }
+
+        true
+    }
+
+    fn clear(&self) {
+        let suspension = self
+            .try_borrow_mut()
+            .map(|mut suspension| std::mem::replace(&mut *suspension, Suspension::Done(Output::Void)));
+
+        drop(suspension);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Bool(bool),
+    Int(BigInt),
+    Str(Text),
+    Tuple(Vec<Output>),
+    Closure(Closure),
+    Module(Module),
+    Thunk(Thunk),
+    Task(Task),
+    Channel(Channel),
+    Void,
+}
+
+impl Output {
+    /// Scopes and thunks the value holds, see `Trace`.
+    fn trace(&self, edges: &mut Vec<*const ()>) {
+        match self {
+            Output::Closure(x) => edges.push(x.context.address()),
+            Output::Module(x) => edges.push(x.context.address()),
+            Output::Thunk(x) => edges.push(Rc::as_ptr(&x.0) as *const ()),
+            Output::Tuple(x) => x.iter().for_each(|element| element.trace(edges)),
+            Output::Bool(_) | Output::Int(_) | Output::Str(_) | Output::Task(_) | Output::Channel(_) | Output::Void => (),
+        }
+    }
+
+    /// What `==` and `!=` compare: ints, strings and bools by value, tuples
+    /// element by element. Values of different kinds, tuples of different
+    /// sizes included, are unequal. Closures, modules, tasks and channels have
+    /// no equality, so comparing one is an error, also inside tuples of the same size.
+    pub(crate) fn equals(&self, other: &Output) -> Result<bool, String> {
+        match (self, other) {
+            (Output::Thunk(x), other) | (other, Output::Thunk(x)) => match &*x.0.borrow() {
+                Suspension::Done(value) => value.equals(other),
+                _ => Err(String::from("Cannot compare a value still being computed")),
+            },
+            (Output::Bool(a), Output::Bool(b)) => Ok(a == b),
+            (Output::Int(a), Output::Int(b)) => Ok(a == b),
+            (Output::Str(a), Output::Str(b)) => Ok(a == b),
+            (Output::Void, Output::Void) => Ok(true),
+            (Output::Tuple(a), Output::Tuple(b)) => {
+                if a.len() != b.len() {
+                    return Ok(false);
+                }
+
+                let mut equal = true;
+
+                // no early exit, a closure further on is still an error
+                for (a, b) in a.iter().zip(b) {
+                    equal &= a.equals(b)?;
+                }
+
+                Ok(equal)
+            }
+            (Output::Closure(_), _) | (_, Output::Closure(_)) => Err(String::from("Cannot compare closures")),
+            (Output::Module(_), _) | (_, Output::Module(_)) => Err(String::from("Cannot compare modules")),
+            (Output::Task(_), _) | (_, Output::Task(_)) => Err(String::from("Cannot compare tasks")),
+            (Output::Channel(_), _) | (_, Output::Channel(_)) => Err(String::from("Cannot compare channels")),
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the value counts as true for a condition, when conditions are
+    /// not restricted to bools.
+    pub(crate) fn truthy(&self) -> bool {
+        match self {
+            Output::Bool(x) => *x,
+            Output::Int(x) => !x.is_zero(),
+            Output::Str(x) => !x.is_empty(),
+            Output::Void => false,
+            _ => true,
+        }
+    }
+
+    /// Text `print` writes for the value, None for the ones it skips.
+    pub(crate) fn printed(&self) -> Option<String> {
+        match self {
+            Output::Thunk(_) | Output::Void => None,
+            output => Some(output.to_string()),
+        }
+    }
+
+    /// Name of the kind of value, as `typeof` tells it. Thunks are named by
+    /// what they evaluated to, `thunk` while pending.
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Output::Bool(_) => "bool",
+            Output::Int(_) => "int",
+            Output::Str(_) => "str",
+            Output::Tuple(_) => "tuple",
+            Output::Closure(_) => "closure",
+            Output::Module(_) => "module",
+            Output::Thunk(x) => match &*x.0.borrow() {
+                Suspension::Done(output) => output.type_name(),
+                _ => "thunk",
+            },
+            Output::Task(_) => "task",
+            Output::Channel(_) => "channel",
+            Output::Void => "void",
+        }
+    }
+
+    /// What `inspect` shows of the value: its type, strings quoted, every
+    /// element of a tuple and the parameters and captured names of a
+    /// closure, e.g. `tuple (int 1, closure/1 (n) capturing [step])`.
+    pub(crate) fn inspected(&self) -> String {
+        match self {
+            Output::Bool(_) | Output::Int(_) => format!("{} {}", self.type_name(), self),
+            Output::Str(x) => format!("str {}", quote(x)),
+            Output::Tuple(x) => {
+                let elements: Vec<String> = x.iter().map(Output::inspected).collect();
+
+                format!("tuple ({})", elements.join(", "))
+            }
+            Output::Closure(x) => {
+                let params: Vec<String> = x
+                    .args
+                    .iter()
+                    .map(|param| match (param.rest, &param.default) {
+                        (true, _) => format!("...{}", param.text),
+                        (false, Some(_)) => format!("{} = ..", param.text),
+                        (false, None) => param.text.to_string(),
+                    })
+                    .collect();
+                let mut captured: Vec<String> = x.context.visible().iter().map(|(name, _)| name.to_string()).collect();
+
+                captured.sort();
+
+                format!("closure/{} ({}) capturing [{}]", x.args.len(), params.join(", "), captured.join(", "))
+            }
+            Output::Module(x) => format!("module {}", x.name),
+            Output::Thunk(x) => match &*x.0.borrow() {
+                Suspension::Done(output) => output.inspected(),
+                _ => String::from("thunk"),
+            },
+            Output::Task(_) | Output::Channel(_) | Output::Void => String::from(self.type_name()),
+        }
+    }
+}
+
+/// Strings are written raw, also inside tuples, the way `print` shows them.
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Bool(x) => write!(f, "{}", x),
+            Output::Int(x) => write!(f, "{}", x),
+            Output::Str(x) => write!(f, "{}", x),
+            Output::Tuple(x) => {
+                write!(f, "(")?;
+
+                for (i, element) in x.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", element)?;
+                }
+
+                write!(f, ")")
+            }
+            Output::Closure(x) => write!(f, "<#closure/{}>", x.args.len()),
+            Output::Module(x) => write!(f, "<#module {}>", x.name),
+            Output::Task(_) => write!(f, "<#task>"),
+            Output::Channel(_) => write!(f, "<#channel>"),
+            Output::Thunk(x) => match &*x.0.borrow() {
+                Suspension::Done(output) => write!(f, "{}", output),
+                _ => write!(f, "<#thunk>"),
+            },
+            Output::Void => Ok(()),
+        }
+    }
+}
+
+/// Native function callable from rinha programs by name, user bindings with the
+/// same name take precedence. The error message is reported at the call site.
+pub type Builtin<H> = Rc<dyn Fn(&mut H, Vec<Output>) -> Result<Output, String>>;
+
+/// Builtin working on the interpreter rather than just the host, `spawn` and
+/// `join` are. Registered builtins with the same name take precedence.
+pub(crate) type Native<H> = fn(&mut Interpreter<H>, Vec<Output>, &Location) -> Result<Output, Error>;
+
+pub struct Interpreter<H: Host = StdHost> {
+    host: H,
+    builtins: HashMap<Symbol, Builtin<H>>,
+    natives: HashMap<Symbol, Native<H>>,
+    importing: Vec<PathBuf>,
+    coverage: Option<Coverage>,
+    hooks: Option<Box<dyn EvalHooks>>,
+    // checked before every term, see `eval_cancellable`
+    cancellation: Option<CancellationToken>,
+    // channels this interpreter can send to, see `task::Claims`
+    claims: task::Claims,
+    intrinsics: bool,
+    specialization: bool,
+    sites: Sites,
+    lazy: bool,
+    semantics: Semantics,
+    warnings: Vec<Warning>,
+    // tail call the running body ended with, see `enter`
+    recur: Option<Tail>,
+    // emptied scopes of calls that returned, reused by the next calls
+    frames: Vec<HashMap<Symbol, Output>>,
+    heap: Heap,
+    allocations: Allocations,
+    counters: Counters,
+    // closure calls running right now
+    depth: usize,
+    // function literals bound by `let`, by body, with their tail calls
+    // rewritten, the original body kept so its address is not reused
+    tails: HashMap<*const Term, (Arc<Term>, Rc<Term>)>,
+}
+
+/// Most scopes `Interpreter::frames` keeps around for reuse, deeper recursion
+/// allocates the rest.
+const FRAMES: usize = 256;
+
+/// A call `enter` runs in place of the one that made it.
+struct Tail {
+    closure: Closure,
+    slots: Vec<Option<Output>>,
+    rest: Vec<Output>,
+    location: Location,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new(StdHost::default())
+    }
+}
+
+impl<H: Host + 'static> Interpreter<H> {
+    pub fn new(host: H) -> Self {
+        let mut interpreter = Self {
+            host,
+            builtins: HashMap::new(),
+            natives: HashMap::new(),
+            importing: Vec::new(),
+            coverage: None,
+            hooks: None,
+            cancellation: None,
+            claims: task::Claims::default(),
+            intrinsics: true,
+            specialization: true,
+            sites: Sites::default(),
+            lazy: false,
+            semantics: Semantics::default(),
+            warnings: Vec::new(),
+            recur: None,
+            frames: Vec::new(),
+            heap: Heap::default(),
+            allocations: Allocations::default(),
+            counters: Counters::default(),
+            depth: 0,
+            tails: HashMap::new(),
+        };
+
+        builtins::register(&mut interpreter);
+        task::register(&mut interpreter);
+
+        interpreter
+    }
+
+    pub fn register<F>(&mut self, name: &str, builtin: F)
+    where
+        F: Fn(&mut H, Vec<Output>) -> Result<Output, String> + 'static,
+    {
+        self.builtins.insert(Symbol::new(name), Rc::new(builtin));
+    }
+
+    pub(crate) fn native(&mut self, name: &'static str, native: Native<H>) {
+        self.natives.insert(Symbol::new(name), native);
+    }
+
+    /// Whether `name` is a builtin, unless a binding shadows it.
+    fn builtin(&self, name: Symbol) -> bool {
+        self.builtins.contains_key(&name) || self.natives.contains_key(&name)
+    }
+
+    /// Native fast paths for well known functions (`fib`), on by default.
+    pub fn set_intrinsics(&mut self, enabled: bool) {
+        self.intrinsics = enabled;
+    }
+
+    pub fn intrinsics(&self) -> bool {
+        self.intrinsics
+    }
+
+    pub(crate) fn claims(&mut self) -> &mut task::Claims {
+        &mut self.claims
+    }
+
+    /// Fast paths for operators and call sites that keep seeing the same kind
+    /// of values, see `specialize`. On by default, results do not change.
+    pub fn set_specialization(&mut self, enabled: bool) {
+        self.specialization = enabled;
+    }
+
+    pub fn specialization(&self) -> bool {
+        self.specialization
+    }
+
+    /// Defers `let` values and closure arguments until they are read, builtins
+    /// and print still get their arguments evaluated.
+    pub fn set_lazy(&mut self, enabled: bool) {
+        self.lazy = enabled;
+    }
+
+    pub fn lazy(&self) -> bool {
+        self.lazy
+    }
+
+    /// How closely evaluation follows the rinha spec, extended by default.
+    pub fn set_semantics(&mut self, semantics: impl Into<Semantics>) {
+        self.semantics = semantics.into();
+    }
+
+    pub fn semantics(&self) -> Semantics {
+        self.semantics
+    }
+
+    /// Warnings raised while evaluating since the last call, once per location.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Applies the level of a lint to what happened at `location`.
+    fn lint(&mut self, level: Lint, message: &str, location: &Location) -> Result<(), Error> {
+        match level {
+            Lint::Allow => Ok(()),
+            Lint::Warn => {
+                if !self.warnings.iter().any(|warning| warning.location == *location) {
+                    self.warnings.push(Warning {
+                        message: String::from(message),
+                        location: location.clone(),
+                    });
+                }
+
+                Ok(())
+            }
+            Lint::Deny => Err(Error::new(message, location.clone())),
+        }
+    }
+
+    /// Lints `a op b = res` at `location` when a 64-bit result would have
+    /// overflowed.
+    fn overflow(&mut self, a: &BigInt, op: &str, b: &BigInt, res: &BigInt, location: &Location) -> Result<(), Error> {
+        if a.to_i64().is_some() && b.to_i64().is_some() && res.to_i64().is_none() {
+            let message = format!("Integer overflow past 64 bits in {} {} {}", a, op, b);
+
+            self.lint(self.semantics.overflow, &message, location)?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts counting how many times each AST node gets evaluated.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Coverage::default);
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Installs callbacks observing evaluation, replacing the previous ones.
+    pub fn set_hooks(&mut self, hooks: impl EvalHooks + 'static) {
+        self.hooks = Some(Box::new(hooks));
+    }
+
+    pub fn take_hooks(&mut self) -> Option<Box<dyn EvalHooks>> {
+        self.hooks.take()
+    }
+
+    fn bind(&mut self, name: Symbol, value: Output, context: &mut Context) {
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_bind(&name, &value);
+        }
+
+        context.insert(name, value);
+    }
+
+    /// A new scope the collector looks after.
+    pub(crate) fn scope(&mut self, scope: Scope) -> Context {
+        let context = Context(Rc::new(RefCell::new(scope)));
+
+        self.heap.track(Rc::downgrade(&context.0) as Weak<dyn Trace>);
+        self.allocations.scopes += 1;
+
+        context
+    }
+
+    /// What the cycle collector did so far.
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// Terms evaluated and calls made so far.
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Values and scopes created so far, by kind.
+    pub fn allocations(&self) -> Allocations {
+        self.allocations
+    }
+
+    /// Counts `value` as newly created, not what it holds.
+    fn allocated(&mut self, value: Output) -> Output {
+        match &value {
+            Output::Str(_) => self.allocations.strings += 1,
+            Output::Tuple(_) => self.allocations.tuples += 1,
+            Output::Closure(_) => self.allocations.closures += 1,
+            Output::Thunk(_) => self.allocations.thunks += 1,
+            Output::Bool(_) | Output::Int(_) | Output::Module(_) | Output::Task(_) | Output::Channel(_) | Output::Void => (),
+        }
+
+        value
+    }
+
+    pub fn host(&self) -> &H {
+        &self.host
+    }
+
+    pub fn host_mut(&mut self) -> &mut H {
+        &mut self.host
+    }
+
+    /// Reads a JSON AST through the host, errors being reported at the start
+    /// of the file. Files ending in `.yaml`, `.yml`, `.msgpack` or `.mpk`
+    /// are read as such, see `read_ast`.
+    pub fn read_json(&mut self, path: impl AsRef<Path>) -> Result<File, Error> {
+        let path = path.as_ref();
+
+        self.read_ast(path, Encoding::of(path).unwrap_or_default())
+    }
+
+    /// Reads an AST written down as `encoding` through the host, errors being
+    /// reported at the start of the file.
+    pub fn read_ast(&mut self, path: impl AsRef<Path>, encoding: Encoding) -> Result<File, Error> {
+        let path = path.as_ref();
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
+        };
+        let bytes = self.read(path, &location)?;
+
+        decode(encoding, &bytes, path, &location)
+    }
+
+    fn read(&mut self, path: &Path, location: &Location) -> Result<Vec<u8>, Error> {
+        self.host.read(path).map_err(|err| {
+            let msg = format!("Cannot read {}: {}", path.display(), err);
+
+            Error::new(msg.as_str(), location.clone())
+        })
+    }
+
+    /// Imports are relative to the file doing the import, falling back to the
+    /// filename recorded in the AST for the entry program.
+    fn resolve(&self, import: &Import) -> PathBuf {
+        let base = match self.importing.last() {
+            Some(path) => path.parent(),
+            None => Path::new(&import.location.filename).parent(),
+        };
+        let path = base.unwrap_or(Path::new("")).join(&import.path);
+
+        let mut normalized = PathBuf::new();
+
+        for component in path.components() {
+            match component {
+                Component::CurDir => (),
+                Component::ParentDir if normalized.file_name().is_some() => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+
+        normalized
+    }
+
+    /// Reads a program through the host, `.json`, `.yaml` and `.msgpack`
+    /// files hold an AST and anything else is rinha source code. Errors
+    /// reading the file are reported at `location`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %path.display())))]
+    pub fn load(&mut self, path: &Path, location: &Location) -> Result<File, Error> {
+        let bytes = self.read(path, location)?;
+
+        if let Some(encoding) = Encoding::of(path) {
+            return decode(encoding, &bytes, path, location);
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(source) => parser::parse(&source, &path.to_string_lossy()),
+            Err(err) => {
+                let msg = format!("Cannot read {}: {}", path.display(), err.utf8_error());
+
+                Err(Error::new(msg.as_str(), location.clone()))
+            }
+        }
+    }
+
+    /// Evaluates the chain of top-level `let`s of `term` into `context`,
+    /// anything after the last binding is not evaluated. Returns the names
+    /// marked with `export`.
+    fn bindings(&mut self, term: &Term, context: &mut Context) -> Result<Vec<Symbol>, Error> {
+        let mut exports = Vec::new();
+        let mut term = term;
+
+        while let Term::Let(x) = term {
+            let expr = match self.tail_calls(x) {
+                Some(value) => self.eval(&value, context)?,
+                None => self.eval(&x.value, context)?,
+            };
+
+            match &x.pattern {
+                Some(pattern) => {
+                    if x.export {
+                        exports.extend(pattern.names().into_iter().map(|name| name.text));
+                    }
+
+                    self.destructure(pattern, expr, context)?;
+                }
+                None => {
+                    if x.export {
+                        exports.push(x.name.text);
+                    }
+
+                    self.bind(x.name.text, expr, context);
+                }
+            }
+
+            term = &x.next;
+        }
+
+        Ok(exports)
+    }
+
+    /// The function literal `x` binds to a name, its tail calls to that name
+    /// made into `Term::Recur`. Each literal is rewritten once, the first
+    /// time its `let` runs.
+    fn tail_calls(&mut self, x: &Let) -> Option<Rc<Term>> {
+        let Term::Function(function) = &*x.value else {
+            return None;
+        };
+
+        if x.pattern.is_some() {
+            return None;
+        }
+
+        let key = Arc::as_ptr(&function.value);
+
+        if let Some((_, optimized)) = self.tails.get(&key) {
+            return Some(optimized.clone());
+        }
+
+        let optimized = Rc::new(tail::optimize(&x.name.text, Term::Function(function.clone())));
+
+        self.tails.insert(key, (function.value.clone(), optimized.clone()));
+
+        Some(optimized)
+    }
+
+    /// Evaluates the top-level bindings of `prog` straight into `context`, so
+    /// they are available to the program evaluated next.
+    pub fn prelude(&mut self, prog: File, context: &mut Context) -> Result<(), Error> {
+        self.bindings(&prog.expression, context)?;
+
+        Ok(())
+    }
+
+    /// Files without any `export let` export all of their bindings.
+    fn import(&mut self, path: &Path, location: &Location) -> Result<(Context, Vec<Symbol>), Error> {
+        let prog = self.load(path, location)?;
+
+        let mut module = self.scope(Scope::default());
+        let mut exports = self.bindings(&prog.expression, &mut module)?;
+
+        if exports.is_empty() {
+            exports = module.entries().into_iter().map(|(name, _)| name).collect();
+            exports.sort();
+        }
+
+        Ok((module, exports))
+    }
+
+    /// Resolves `module.name`, returning the binding along with the scope of
+    /// the module it belongs to.
+    fn access(&mut self, x: &Access, context: &mut Context) -> Result<(Output, Context), Error> {
+        match self.eval(&x.module, context)? {
+            Output::Module(module) => {
+                let value = module
+                    .exports
+                    .iter()
+                    .find(|name| **name == x.name)
+                    .and_then(|name| module.context.own(*name));
+
+                match value {
+                    Some(value) => Ok((value, module.context)),
+                    None => {
+                        let msg = format!("Module {} does not export {}", module.name, x.name);
+
+                        Err(Error::new(msg.as_str(), x.location.clone()))
+                    }
+                }
+            }
+            _ => {
+                let msg = format!("Cannot access {} of a non module value", x.name);
+
+                Err(Error::new(msg.as_str(), x.location.clone()))
+            }
+        }
+    }
+
+    /// Binds the parts of `value` to the names of `pattern`, which must have
+    /// the same shape.
+    fn destructure(&mut self, pattern: &Pattern, value: Output, context: &mut Context) -> Result<(), Error> {
+        match (pattern, value) {
+            (Pattern::Var(x), value) => {
+                self.bind(x.text, value, context);
+
+                Ok(())
+            }
+            (Pattern::Tuple(x), Output::Tuple(values)) if x.elements.len() == values.len() => {
+                for (pattern, value) in x.elements.iter().zip(values) {
+                    self.destructure(pattern, value, context)?;
+                }
+
+                Ok(())
+            }
+            (Pattern::Tuple(x), Output::Tuple(values)) => {
+                let msg = format!(
+                    "Cannot destructure a tuple of {} elements into {} names",
+                    values.len(),
+                    x.elements.len()
+                );
+
+                Err(Error::new(msg.as_str(), x.location.clone()))
+            }
+            (Pattern::Tuple(x), _) => Err(Error::new("Cannot destructure a non tuple value", x.location.clone())),
+        }
+    }
+
+    /// Evaluates `term` unless running lazily, in which case anything more
+    /// expensive than a literal, a function or a variable becomes a thunk.
+    /// Prints and builtin calls stay strict, so `let _ = print(..)` still
+    /// runs in order.
+    fn delay(&mut self, term: &Term, context: &mut Context) -> Result<Output, Error> {
+        if !self.lazy {
+            return self.eval(term, context);
+        }
+
+        match term {
+            Term::Bool(_) | Term::Int(_) | Term::Str(_) | Term::Function(_) | Term::Print(_) => {
+                self.eval(term, context)
+            }
+            Term::Call(x)
+                if matches!(&*x.callee, Term::Var(z) if !context.contains(z.text) && self.builtin(z.text)) =>
+            {
+                self.eval(term, context)
+            }
+            // pass thunks along without forcing them
+            Term::Var(x) => match context.lookup(x.text) {
+                Some(var) => Ok(var),
+                None => self.eval(term, context),
+            },
+            term => {
+                // bindings made after this one stay out of sight of the thunk
+                let snapshot = self.scope(context.snapshot());
+
+                Ok(self.suspend(term.clone(), snapshot))
+            }
+        }
+    }
+
+    /// A new thunk computing `term` in `context` once forced.
+    pub(crate) fn suspend(&mut self, term: Term, context: Context) -> Output {
+        let thunk = Rc::new(RefCell::new(Suspension::Pending(Box::new(term), context)));
+
+        self.heap.track(Rc::downgrade(&thunk) as Weak<dyn Trace>);
+
+        self.allocated(Output::Thunk(Thunk(thunk)))
+    }
+
+    fn force(&mut self, thunk: Thunk, location: &Location) -> Result<Output, Error> {
+        if let Suspension::Done(value) = &*thunk.0.borrow() {
+            return Ok(value.clone());
+        }
+
+        let Suspension::Pending(term, mut context) = std::mem::replace(&mut *thunk.0.borrow_mut(), Suspension::Forcing)
+        else {
+            return Err(Error::new("Value depends on itself", location.clone()));
+        };
+
+        match self.eval(&term, &mut context) {
+            Ok(value) => {
+                *thunk.0.borrow_mut() = Suspension::Done(value.clone());
+
+                Ok(value)
+            }
+            Err(err) => {
+                *thunk.0.borrow_mut() = Suspension::Pending(term, context);
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Calls `closure` with already evaluated arguments, `context` being the
+    /// scope of the caller.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(arity = args.len(), at = location.start))
+    )]
+    pub fn apply(
+        &mut self,
+        closure: Closure,
+        args: Vec<Output>,
+        context: &Context,
+        location: &Location,
+    ) -> Result<Output, Error> {
+        if self.semantics.partial(closure.required(), args.len()) {
+            let partial = Output::Closure(self.partial(closure, args));
+
+            return Ok(self.allocated(partial));
+        }
+
+        if !closure.accepts(args.len()) {
+            return Err(Error::new(
+                "Arguments declaration differs parameters declaration",
+                location.clone(),
+            ));
+        }
+
+        let mut args = args;
+        let rest = args.split_off(closure.fixed().min(args.len()));
+
+        self.enter(closure, args.into_iter().map(Some).collect(), rest, context, location)
+    }
+
+    /// Positional and named arguments of a call to `closure`, evaluated in
+    /// `context`, in parameter order. Parameters no argument fills are None.
+    fn arguments(
+        &mut self,
+        closure: &Closure,
+        arguments: &[Term],
+        named: &[NamedArgument],
+        location: &Location,
+        context: &mut Context,
+    ) -> Result<(Vec<Option<Output>>, Vec<Output>), Error> {
+        let fixed = closure.fixed();
+
+        if !closure.variadic() && arguments.len() > fixed {
+            return Err(Error::new(
+                "Arguments declaration differs parameters declaration",
+                location.clone(),
+            ));
+        }
+
+        let mut slots = vec![None; fixed];
+        let mut rest = Vec::new();
+
+        for (i, arg) in arguments.iter().enumerate() {
+            let value = self.delay(arg, context)?;
+
+            match slots.get_mut(i) {
+                Some(slot) => *slot = Some(value),
+                None => rest.push(value),
+            }
+        }
+
+        for arg in named {
+            let Some(i) = closure.args.iter().position(|param| param.text == arg.name) else {
+                let msg = format!("Unknown parameter {} in named argument", arg.name);
+
+                return Err(Error::new(&msg, arg.location.clone()));
+            };
+
+            if i == fixed {
+                let msg = format!("Parameter {} collects the remaining arguments, it cannot be named", arg.name);
+
+                return Err(Error::new(&msg, arg.location.clone()));
+            }
+
+            if slots[i].is_some() {
+                return Err(Error::new(&format!("Argument {} is given twice", arg.name), arg.location.clone()));
+            }
+
+            slots[i] = Some(self.delay(&arg.value, context)?);
+        }
+
+        let missing = closure.args.iter().zip(&slots).find(|(param, slot)| slot.is_none() && param.default.is_none());
+
+        if let Some((param, _)) = missing {
+            return Err(Error::new(&format!("Missing argument {}", param.text), location.clone()));
+        }
+
+        Ok((slots, rest))
+    }
+
+    /// Runs the body of `closure` with its parameters bound to `slots`, or to
+    /// their defaults where None, and its rest parameter to `rest`.
+    fn enter(
+        &mut self,
+        closure: Closure,
+        slots: Vec<Option<Output>>,
+        rest: Vec<Output>,
+        context: &Context,
+        location: &Location,
+    ) -> Result<Output, Error> {
+        let mut output = self.body(closure, slots, rest, context, location)?;
+
+        // a `Term::Recur` ending the body leaves the next call behind instead
+        // of making it, running it here keeps the stack flat
+        while let Some(next) = self.recur.take() {
+            output = self.body(next.closure, next.slots, next.rest, context, &next.location)?;
+        }
+
+        Ok(output)
+    }
+
+    fn body(
+        &mut self,
+        closure: Closure,
+        slots: Vec<Option<Output>>,
+        rest: Vec<Output>,
+        context: &Context,
+        location: &Location,
+    ) -> Result<Output, Error> {
+        if let Some(hooks) = &mut self.hooks {
+            let args: Vec<Output> = slots.iter().flatten().chain(&rest).cloned().collect();
+
+            hooks.on_call(&closure, &args);
+        }
+
+        if self.heap.due() {
+            self.heap.collect();
+        }
+
+        let inner = self.frames.pop().unwrap_or_default();
+        let mut new_context = self.scope(Scope {
+            outter: Some(context.clone()),
+            inner,
+            captured: Some(closure.context.clone()),
+        });
+
+        self.depth += 1;
+        self.counters.calls += 1;
+        self.counters.max_depth = self.counters.max_depth.max(self.depth);
+
+        let mut res = self.run(&closure, slots, rest, location, &mut new_context);
+
+        self.depth -= 1;
+
+        if let Err(err) = &mut res {
+            if err.kind == ErrorKind::Cancelled {
+                err.backtrace.push(location.clone());
+            }
+        }
+
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_return(&closure, &res);
+        }
+
+        // unless something made during the call still links to its scope,
+        // e.g. a thunk or a call it returned, the bindings go back to `frames`
+        if Rc::strong_count(&new_context.0) == 1 && self.frames.len() < FRAMES {
+            let mut frame = std::mem::take(&mut new_context.0.borrow_mut().inner);
+
+            frame.clear();
+            self.frames.push(frame);
+        }
+
+        res
+    }
+
+    fn run(
+        &mut self,
+        closure: &Closure,
+        slots: Vec<Option<Output>>,
+        rest: Vec<Output>,
+        location: &Location,
+        new_context: &mut Context,
+    ) -> Result<Output, Error> {
+        let fixed = closure.fixed();
+        let mut slots = slots;
+
+        slots.resize(fixed, None);
+
+        // parameters left out all have a default, callers made sure of it
+        for (param, slot) in closure.args.iter().zip(slots) {
+            let value = match (slot, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.delay(default, new_context)?,
+                (None, None) => continue,
+            };
+
+            self.bind(param.text, value, new_context);
+        }
+
+        if let Some(param) = closure.args.get(fixed) {
+            let mut elements = Vec::with_capacity(rest.len());
+
+            // thunks only live in scopes, not inside tuples
+            for arg in rest {
+                elements.push(match arg {
+                    Output::Thunk(thunk) => self.force(thunk, location)?,
+                    arg => arg,
+                });
+            }
+
+            let rest = self.allocated(Output::Tuple(elements));
+
+            self.bind(param.text, rest, new_context);
+        }
+
+        self.eval(&closure.body, new_context)
+    }
+
+    /// `closure` with its first parameters bound to `args`, taking the rest.
+    fn partial(&mut self, closure: Closure, args: Vec<Output>) -> Closure {
+        let given = args.len();
+        let mut supplied = self.scope(Scope {
+            captured: Some(closure.context),
+            ..Scope::default()
+        });
+
+        for (param, arg) in closure.args.iter().zip(args) {
+            self.bind(param.text, arg, &mut supplied);
+        }
+
+        Closure {
+            body: closure.body,
+            args: closure.args[given..].into(),
+            context: supplied,
+        }
+    }
+
+    /// The closure `f >> g` evaluates to, taking `#x` and calling `g(f(#x))`
+    /// with both functions captured under names no program can write.
+    fn compose(&mut self, f: Closure, g: Closure, location: Location) -> Closure {
+        let var = |text: &str| {
+            Box::new(Term::Var(Var {
+                text: Symbol::new(text),
+                location: location.clone(),
+            }))
+        };
+        let call = |callee, argument| {
+            Term::Call(Call {
+                callee,
+                arguments: vec![argument],
+                named: Vec::new(),
+                location: location.clone(),
+            })
+        };
+
+        let mut captured = self.scope(Scope::default());
+
+        self.bind(Symbol::new("#f"), Output::Closure(f), &mut captured);
+        self.bind(Symbol::new("#g"), Output::Closure(g), &mut captured);
+
+        Closure {
+            body: Arc::new(call(var("#g"), call(var("#f"), *var("#x")))),
+            args: vec![Parameter {
+                text: Symbol::new("#x"),
+                location: location.clone(),
+                rest: false,
+                default: None,
+            }]
+            .into(),
+            context: captured,
+        }
+    }
+
+    /// `term` with the values of its top-level `let`s that only read literals
+    /// and earlier such bindings replaced by what they evaluate to, so later
+    /// runs and the closures reading them do not compute them again. Values
+    /// that fail or warn are left for the run to report.
+    pub fn preevaluate(&mut self, term: &Term) -> Term {
+        let mut folded = term.clone();
+        let mut known = HashSet::new();
+        let mut constants = Context::default();
+        let mut cursor = &mut folded;
+
+        while let Term::Let(x) = cursor {
+            match &x.pattern {
+                Some(pattern) => {
+                    for name in pattern.names() {
+                        known.remove(&name.text);
+                    }
+                }
+                None if constants::constant(&x.value, &known) => {
+                    let warnings = self.warnings.len();
+                    let value = self.eval(&x.value, &mut constants);
+                    let literal = match &value {
+                        Ok(value) if self.warnings.len() == warnings => constants::literal(value, x.value.location()),
+                        _ => None,
+                    };
+
+                    self.warnings.truncate(warnings);
+
+                    match (literal, value) {
+                        (Some(literal), Ok(value)) => {
+                            *x.value = literal;
+                            constants.insert(x.name.text, value);
+                            known.insert(x.name.text);
+                        }
+                        _ => {
+                            known.remove(&x.name.text);
+                        }
+                    }
+                }
+                None => {
+                    known.remove(&x.name.text);
+                }
+            }
+
+            if constants::imports(&x.value) {
+                known.clear();
+            }
+
+            cursor = &mut x.next;
+        }
+
+        folded
+    }
+
+    /// Static errors of `term`, for a program run in `context`, see
+    /// `check::check`.
+    pub fn check(&self, term: &Term, context: &Context) -> Vec<Error> {
+        check::check(term, &self.semantics, |name| {
+            let name = Symbol::new(name);
+
+            context.contains(name) || self.builtin(name)
+        })
+    }
+
+    /// What `term` would evaluate to in `context` as far as the checker can
+    /// tell without running it, see `check::infer`.
+    pub fn infer(&self, term: &Term, context: &Context) -> Result<Option<Kind>, Vec<Error>> {
+        let scope: Vec<(String, Option<Kind>)> = context
+            .visible()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Kind::of(&value)))
+            .collect();
+
+        check::infer(term, &self.semantics, &scope, |name| self.builtin(Symbol::new(name)))
+    }
+
+    /// Variables of `term` no binding, builtin or intrinsic declares, for a
+    /// program run in `context`, see `check::unbound`.
+    pub fn unbound(&self, term: &Term, context: &Context) -> Vec<Error> {
+        check::unbound(term, |name| {
+            let name = Symbol::new(name);
+
+            context.contains(name) || self.builtin(name) || (self.intrinsics && name == "fib")
+        })
+    }
+
+    /// Evaluates `term` in a fresh scope and hands the scope back along with
+    /// the result, holding every top-level binding the program made. Under
+    /// lazy evaluation bindings never read are still `Output::Thunk`.
+    pub fn eval_with_env(&mut self, term: &Term) -> Result<(Output, Context), Error> {
+        let mut context = Context::default();
+        let output = self.eval(term, &mut context)?;
+
+        Ok((output, context))
+    }
+
+    /// Evaluates `term` in a fresh scope, stopping with an
+    /// `ErrorKind::Cancelled` error at the next term once `token` is
+    /// cancelled. Tasks the program spawns stop along with it, builtins
+    /// waiting on a task or a channel are not interrupted.
+    pub fn eval_cancellable(&mut self, term: &Term, token: CancellationToken) -> Result<Output, Error> {
+        let previous = self.cancellation.replace(token);
+        let res = self.eval(term, &mut Context::default());
+
+        self.cancellation = previous;
+
+        res
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<CancellationToken> {
+        self.cancellation.clone()
+    }
+
+    /// Token checked before every term of the evaluations that follow, like
+    /// `eval_cancellable` does for a single one.
+    pub fn set_cancellation(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(start = term.location().start, end = term.location().end))
+    )]
+    pub fn eval(&mut self, term: &Term, context: &mut Context) -> Result<Output, Error> {
+        if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(Error::cancelled(term.location().clone()));
+        }
+
+        if stack::exhausted() {
+            return Err(Error::new("Out of stack, the program recurses too deep", term.location().clone()));
+        }
+
+        self.counters.evaluated += 1;
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(term.location());
+        }
+
+        let Some(hooks) = &mut self.hooks else {
+            return self.reduce(term, context);
+        };
+
+        hooks.on_enter(term);
+
+        let res = self.reduce(term, context);
+
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_exit(term, &res);
+        }
+
+        res
+    }
+
+    fn reduce(&mut self, term: &Term, context: &mut Context) -> Result<Output, Error> {
+        match term {
+            Term::Bool(x) => Ok(Output::Bool(x.value)),
+            Term::Int(x) => Ok(Output::Int(x.value.clone())),
+            Term::Str(x) => Ok(self.allocated(Output::Str(x.value.as_str().into()))),
+            Term::Print(x) => {
+                let expr = self.eval(&x.value, context)?;
+
+                if let Some(text) = expr.printed() {
+                    self.host.print(&text);
+                }
+
+                if self.semantics.print_returns_value {
+                    Ok(expr)
+                } else {
+                    Ok(Output::Void)
+                }
+            }
+            Term::Binary(x) if x.op == BinaryOp::Pipe => {
+                // `x |> f` is the call `f(x)`, so builtins and module members
+                // resolve as usual
+                self.call(&x.rhs, std::slice::from_ref(&x.lhs), &[], &x.location, context)
+            }
+            Term::Binary(x) => {
+                let specializing = self.specialization && specialize::arithmetic(&x.op);
+                let operands = if specializing {
+                    self.sites.operands(term)
+                } else {
+                    Operands::Generic
+                };
+
+                let lhs = self.eval(&x.lhs, context)?;
+                let rhs = self.eval(&x.rhs, context)?;
+
+                match (operands, &lhs, &rhs) {
+                    (Operands::Int, Output::Int(a), Output::Int(b)) => {
+                        if let Some(res) = specialize::int(&x.op, a, b) {
+                            self.counters.specialized += 1;
+
+                            return Ok(res);
+                        }
+                    }
+                    (Operands::Int, _, _) => {
+                        self.sites.deoptimize(term);
+                        self.counters.deoptimized += 1;
+                    }
+                    (Operands::Warming(_), lhs, rhs) => {
+                        let ints = matches!((lhs, rhs), (Output::Int(_), Output::Int(_)));
+
+                        self.sites.observe_operands(term, ints);
+                    }
+                    (Operands::Generic, _, _) => {}
+                }
+
+                self.binary(x, lhs, rhs)
+            }
+            Term::If(x) => {
+                let cond = self.eval(&x.condition, context)?;
+
+                match cond {
+                    Output::Bool(true) => self.eval(&x.then, context),
+                    Output::Bool(false) => self.eval(&x.otherwise, context),
+                    cond if self.semantics.truthy_conditions => {
+                        if cond.truthy() {
+                            self.eval(&x.then, context)
+                        } else {
+                            self.eval(&x.otherwise, context)
+                        }
+                    }
+                    _ => Err(Error::new(
+                        "Condition expression not resolve to a boolean primitive",
+                        x.location.clone(),
+                    )),
+                }
+            }
+            Term::Tuple(x) => {
+                if !self.semantics.tuples_of_any_size && x.elements.len() != 2 {
+                    return Err(Error::new("Tuples have exactly two elements", x.location.clone()));
+                }
+
+                let mut elements = Vec::with_capacity(x.elements.len());
+
+                for element in &x.elements {
+                    elements.push(self.eval(element, context)?);
+                }
+
+                Ok(self.allocated(Output::Tuple(elements)))
+            }
+            Term::First(x) => {
+                let val = self.eval(&x.value, context)?;
+
+                if let Output::Tuple(mut elements) = val {
+                    // a rest parameter collects fewer than two arguments into
+                    // a tuple as well
+                    if elements.is_empty() {
+                        return Err(Error::new("Cannot access first of an empty tuple", x.location.clone()));
+                    }
+
+                    Ok(elements.swap_remove(0))
+                } else {
+                    Err(Error::new(
+                        "Cannot access first of a non tuple argument",
+                        x.location.clone(),
+                    ))
+                }
+            }
+            Term::Second(x) => {
+                let val = self.eval(&x.value, context)?;
+
+                if let Output::Tuple(mut elements) = val {
+                    if elements.len() < 2 {
+                        let msg = format!("Cannot access second of a tuple of {} elements", elements.len());
+
+                        return Err(Error::new(&msg, x.location.clone()));
+                    }
+
+                    Ok(elements.swap_remove(1))
+                } else {
+                    Err(Error::new(
+                        "Cannot access second of a non tuple argument",
+                        x.location.clone(),
+                    ))
+                }
+            }
+            Term::Var(x) => {
+                match context.lookup(x.text) {
+                    Some(Output::Thunk(thunk)) => return self.force(thunk, &x.location),
+                    Some(var) => return Ok(var),
+                    None => (),
+                }
+
+                let msg = format!("Variable {} is not declared", &x.text);
+
+                Err(Error::new(msg.as_str(), x.location.clone()))
+            }
+            Term::Let(x) => {
+                match &x.pattern {
+                    Some(pattern) => {
+                        let expr = self.eval(&x.value, context)?;
+
+                        self.destructure(pattern, expr, context)?;
+                    }
+                    None => {
+                        let expr = match self.tail_calls(x) {
+                            Some(value) => self.delay(&value, context)?,
+                            None => self.delay(&x.value, context)?,
+                        };
+
+                        self.bind(x.name.text, expr, context);
+                    }
+                }
+
+                self.eval(&x.next, context)
+            }
+            Term::Seq(x) => {
+                let mut value = Output::Void;
+
+                for expression in &x.expressions {
+                    value = self.eval(expression, context)?;
+                }
+
+                Ok(value)
+            }
+            // a cancelled or exiting program stops whatever it is in
+            Term::Try(x) => match self.eval(&x.body, context) {
+                Err(err) if err.kind == ErrorKind::Failure => {
+                    let message = Term::Str(Str {
+                        value: err.message,
+                        location: Location {
+                            start: err.start,
+                            end: err.end,
+                            filename: err.filename,
+                        },
+                    });
+
+                    self.call(&x.handler, std::slice::from_ref(&message), &[], &x.location, context)
+                }
+                res => res,
+            },
+            Term::Import(x) => {
+                let path = self.resolve(x);
+
+                if self.importing.contains(&path) {
+                    let msg = format!("Cyclic import of {}", path.display());
+
+                    return Err(Error::new(msg.as_str(), x.location.clone()));
+                }
+
+                self.importing.push(path.clone());
+                let module = self.import(&path, &x.location);
+                self.importing.pop();
+
+                let (module, exports) = module?;
+
+                match &x.alias {
+                    Some(name) => Ok(Output::Module(Module {
+                        name: name.clone(),
+                        context: module,
+                        exports,
+                    })),
+                    None => {
+                        for name in exports {
+                            if let Some(value) = module.own(name) {
+                                context.insert(name, value);
+                            }
+                        }
+
+                        Ok(Output::Void)
+                    }
+                }
+            }
+            Term::Access(x) => Ok(self.access(x, context)?.0),
+            Term::Call(x) => self.call(&x.callee, &x.arguments, &x.named, &x.location, context),
+            Term::Recur(x) => {
+                // builtins and the fib intrinsic take the name before any
+                // closure, as for any call
+                let native = match &*x.callee {
+                    Term::Var(z) => (self.intrinsics && z.text == "fib") || !context.contains(z.text),
+                    _ => true,
+                };
+                let func = if native { None } else { Some(self.eval(&x.callee, context)?) };
+
+                match func {
+                    Some(Output::Closure(y))
+                        if !x.named.is_empty()
+                            || (y.accepts(x.arguments.len())
+                                && !self.semantics.partial(y.required(), x.arguments.len())) =>
+                    {
+                        let (slots, rest) = self.arguments(&y, &x.arguments, &x.named, &x.location, context)?;
+
+                        self.recur = Some(Tail {
+                            closure: y,
+                            slots,
+                            rest,
+                            location: x.location.clone(),
+                        });
+
+                        // stands for the result until `enter` makes the call
+                        Ok(Output::Void)
+                    }
+                    _ => self.call(&x.callee, &x.arguments, &x.named, &x.location, context),
+                }
+            }
+            Term::Function(x) => {
+                if self.semantics.duplicate_parameters != Lint::Allow {
+                    for (i, param) in x.parameters.iter().enumerate() {
+                        if x.parameters[..i].iter().any(|other| other.text == param.text) {
+                            let message = format!("Parameter {} is declared twice", param.text);
+
+                            self.lint(self.semantics.duplicate_parameters, &message, &param.location)?;
+                        }
+                    }
+                }
+
+                // only what the body and defaults read is kept alive by the
+                // closure
+                let mut inner = HashMap::new();
+
+                for name in x.free_variables() {
+                    if let Some(value) = context.lookup(name) {
+                        inner.insert(name, value);
+                    }
+                }
+
+                let context = self.scope(Scope {
+                    inner,
+                    ..Scope::default()
+                });
+
+                Ok(self.allocated(Output::Closure(Closure {
+                    body: x.value.clone(),
+                    args: x.parameters.clone(),
+                    context,
+                })))
+            }
+        }
+    }
+
+    /// `lhs op rhs` for every operator but pipes, on values of any kind.
+    fn binary(&mut self, x: &Binary, lhs: Output, rhs: Output) -> Result<Output, Error> {
+        match x.op {
+            BinaryOp::Add => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) if self.semantics.overflow != Lint::Allow => {
+                    let res = &a + &b;
+
+                    self.overflow(&a, "+", &b, &res, &x.location)?;
+
+                    Ok(Output::Int(res))
+                }
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a + b)),
+                (Output::Str(a), Output::Str(b)) => Ok(self.allocated(Output::Str(Text::concat(&a, &b)))),
+                (Output::Str(a), Output::Int(b)) => {
+                    self.lint(self.semantics.coercion, "Implicit coercion of int to str", &x.location)?;
+
+                    Ok(self.allocated(Output::Str(Text::concat(&a, &b.to_string().into()))))
+                }
+                (Output::Int(a), Output::Str(b)) => {
+                    self.lint(self.semantics.coercion, "Implicit coercion of int to str", &x.location)?;
+
+                    Ok(self.allocated(Output::Str(Text::concat(&a.to_string().into(), &b))))
+                }
+                _ => Err(Error::new("Cannot perform add operation", x.location.clone())),
+            },
+            BinaryOp::Sub => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) if self.semantics.overflow != Lint::Allow => {
+                    let res = &a - &b;
+
+                    self.overflow(&a, "-", &b, &res, &x.location)?;
+
+                    Ok(Output::Int(res))
+                }
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a - b)),
+                _ => Err(Error::new("Cannot perform sub operation", x.location.clone())),
+            },
+            BinaryOp::Mul => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) if self.semantics.overflow != Lint::Allow => {
+                    let res = &a * &b;
+
+                    self.overflow(&a, "*", &b, &res, &x.location)?;
+
+                    Ok(Output::Int(res))
+                }
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a * b)),
+                _ => Err(Error::new("Cannot perform mul operation", x.location.clone())),
+            },
+            BinaryOp::Div => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => {
+                    if b.is_positive() {
+                        Ok(Output::Int(a / b))
+                    } else {
+                        Err(Error::new("Arithmetic error, dividing by zero", x.location.clone()))
+                    }
+                }
+                _ => Err(Error::new("Cannot perform div operation", x.location.clone())),
+            },
+            BinaryOp::Eq => match lhs.equals(&rhs) {
+                Ok(equal) => Ok(Output::Bool(equal)),
+                Err(message) => Err(Error::new(&message, x.location.clone())),
+            },
+            BinaryOp::Neq => match lhs.equals(&rhs) {
+                Ok(equal) => Ok(Output::Bool(!equal)),
+                Err(message) => Err(Error::new(&message, x.location.clone())),
+            },
+            BinaryOp::Gt => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a > b)),
+                (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a > b)),
+                _ => Err(Error::new("Cannot perform gt operation", x.location.clone())),
+            },
+            BinaryOp::Lt => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a < b)),
+                (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a < b)),
+                _ => Err(Error::new("Cannot perform lt operation", x.location.clone())),
+            },
+            BinaryOp::Gte => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a >= b)),
+                (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a >= b)),
+                _ => Err(Error::new("Cannot perform gte operation", x.location.clone())),
+            },
+            BinaryOp::Lte => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a <= b)),
+                (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a <= b)),
+                _ => Err(Error::new("Cannot perform lte operation", x.location.clone())),
+            },
+            BinaryOp::Rem => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => {
+                    if b.is_positive() {
+                        Ok(Output::Int(a % b))
+                    } else {
+                        Err(Error::new("Arithmetic error, dividing by zero", x.location.clone()))
+                    }
+                }
+                _ => Err(Error::new("Cannot perform rem operation", x.location.clone())),
+            },
+            BinaryOp::BitAnd => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a & b)),
+                _ => Err(Error::new("Cannot perform bitand operation", x.location.clone())),
+            },
+            BinaryOp::BitOr => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a | b)),
+                _ => Err(Error::new("Cannot perform bitor operation", x.location.clone())),
+            },
+            BinaryOp::BitXor => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a ^ b)),
+                _ => Err(Error::new("Cannot perform bitxor operation", x.location.clone())),
+            },
+            BinaryOp::Shl | BinaryOp::Shr => match (lhs, rhs) {
+                (Output::Int(a), Output::Int(b)) => {
+                    let left = x.op == BinaryOp::Shl;
+                    let Some(res) = shift(&a, &b, left) else {
+                        let msg = format!("Arithmetic error, shifting by {} bits", b);
+
+                        return Err(Error::new(&msg, x.location.clone()));
+                    };
+
+                    if left && self.semantics.overflow != Lint::Allow {
+                        self.overflow(&a, "<<<", &b, &res, &x.location)?;
+                    }
+
+                    Ok(Output::Int(res))
+                }
+                _ if x.op == BinaryOp::Shl => Err(Error::new("Cannot perform shl operation", x.location.clone())),
+                _ => Err(Error::new("Cannot perform shr operation", x.location.clone())),
+            },
+            BinaryOp::And => match (lhs, rhs) {
+                (Output::Bool(a), Output::Bool(b)) => Ok(Output::Bool(a && b)),
+                _ if !self.semantics.logic_on_any => {
+                    Err(Error::new("Cannot perform and operation", x.location.clone()))
+                }
+                (Output::Bool(false), _) => Ok(Output::Bool(false)),
+                (_, b) => Ok(b),
+            },
+            BinaryOp::Or => match (lhs, rhs) {
+                (Output::Bool(a), Output::Bool(b)) => Ok(Output::Bool(a || b)),
+                _ if !self.semantics.logic_on_any => {
+                    Err(Error::new("Cannot perform or operation", x.location.clone()))
+                }
+                (Output::Bool(true), _) => Ok(Output::Bool(true)),
+                (_, b) => Ok(b),
+            },
+            BinaryOp::Compose => match (lhs, rhs) {
+                (Output::Closure(f), Output::Closure(g)) => {
+                    let composed = Output::Closure(self.compose(f, g, x.location.clone()));
+
+                    Ok(self.allocated(composed))
+                }
+                _ => Err(Error::new("Cannot perform compose operation", x.location.clone())),
+            },
+            BinaryOp::Pipe => unreachable!("pipes are desugared into calls"),
+        }
+    }
+
+    /// Calls `callee` with `arguments` and `named` evaluated in `context`,
+    /// builtins and the fib intrinsic taking the name before any binding.
+    fn call(
+        &mut self,
+        callee: &Term,
+        arguments: &[Term],
+        named: &[NamedArgument],
+        location: &Location,
+        context: &mut Context,
+    ) -> Result<Output, Error> {
+        if let Term::Access(z) = callee {
+            let (func, scope) = self.access(z, context)?;
+
+            let Output::Closure(y) = func else {
+                return Err(Error::new("Calling a not callable", location.clone()));
+            };
+
+            if !named.is_empty() {
+                let (slots, rest) = self.arguments(&y, arguments, named, location, context)?;
+
+                return self.enter(y, slots, rest, &scope, location);
+            }
+
+            let mut args = Vec::with_capacity(arguments.len());
+
+            for arg in arguments {
+                args.push(self.delay(arg, context)?);
+            }
+
+            return self.apply(y, args, &scope, location);
+        }
+
+        if let Term::Var(z) = callee {
+            if self.intrinsics && z.text == "fib" && arguments.len() == 1 && named.is_empty() {
+                if let Output::Int(nth) = self.eval(&arguments[0], context)? {
+                    let res = match nth.to_u64() {
+                        Some(n) if n < 1000 => __fib_iter(n),
+                        _ if nth.is_negative() => BigInt::zero(),
+                        _ => __fib_matrix(nth),
+                    };
+
+                    return Ok(Output::Int(res));
+                }
+            }
+        }
+
+        if let Term::Var(z) = callee {
+            // a hot site calling closures of one body looks the name up once,
+            // skipping builtins, so only when nothing observes every term
+            if named.is_empty() && self.specialization && self.coverage.is_none() && self.hooks.is_none() {
+                if let Some(body) = self.sites.callee(callee).map(Arc::as_ptr) {
+                    match context.lookup(z.text) {
+                        Some(Output::Closure(y)) if Arc::as_ptr(&y.body) == body => {
+                            self.counters.evaluated += 1;
+                            self.counters.specialized += 1;
+
+                            let mut args = Vec::with_capacity(arguments.len());
+
+                            for arg in arguments {
+                                args.push(self.delay(arg, context)?);
+                            }
+
+                            return self.apply(y, args, context, location);
+                        }
+                        _ => {
+                            self.sites.deoptimize(callee);
+                            self.counters.deoptimized += 1;
+                        }
+                    }
+                }
+            }
+
+            if !context.contains(z.text) {
+                if let Some(builtin) = self.builtins.get(&z.text).cloned() {
+                    if let Some(arg) = named.first() {
+                        return Err(Error::new("Builtins take no named arguments", arg.location.clone()));
+                    }
+
+                    let mut args = Vec::with_capacity(arguments.len());
+
+                    for arg in arguments {
+                        args.push(self.eval(arg, context)?);
+                    }
+
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(name = %z.text, arity = args.len(), "builtin call");
+
+                    return builtin(&mut self.host, args)
+                        .map(|value| self.allocated(value))
+                        .map_err(|msg| Error::new(&msg, location.clone()));
+                }
+
+                if let Some(native) = self.natives.get(&z.text).copied() {
+                    if let Some(arg) = named.first() {
+                        return Err(Error::new("Builtins take no named arguments", arg.location.clone()));
+                    }
+
+                    let mut args = Vec::with_capacity(arguments.len());
+
+                    for arg in arguments {
+                        args.push(self.eval(arg, context)?);
+                    }
+
+                    return native(self, args, location);
+                }
+            }
+        }
+
+        let func = self.eval(callee, context)?;
+
+        match func {
+            Output::Closure(y) if !named.is_empty() => {
+                let (slots, rest) = self.arguments(&y, arguments, named, location, context)?;
+
+                self.enter(y, slots, rest, context, location)
+            }
+            Output::Closure(y) => {
+                if matches!(callee, Term::Var(_)) && self.specialization {
+                    self.sites.observe_callee(callee, &y.body);
+                }
+
+                let partial = self.semantics.partial(y.required(), arguments.len());
+
+                if !y.accepts(arguments.len()) && !partial {
+                    return Err(Error::new(
+                        "Arguments declaration differs parameters declaration",
+                        location.clone(),
+                    ));
+                }
+
+                let mut args = Vec::with_capacity(arguments.len());
+
+                for arg in arguments {
+                    args.push(self.delay(arg, context)?);
+                }
+
+                self.apply(y, args, context, location)
+            }
+            _ => Err(Error::new("Calling a not callable", location.clone())),
+        }
+    }
+}
+
+/// Most bits `<<<` shifts a nonzero int by, further shifts are errors rather
+/// than exhausting memory.
+const SHIFT: u64 = 1 << 24;
+
+/// `a` shifted left by `n` bits, or right unless `left`, a negative `n`
+/// shifting the other way. Right shifts round towards negative infinity, as
+/// in two's complement. None when a left shift goes past `SHIFT`.
+fn shift(a: &BigInt, n: &BigInt, left: bool) -> Option<BigInt> {
+    if left != n.is_negative() {
+        let n = n.magnitude().to_u64().filter(|n| *n <= SHIFT || a.is_zero())?;
+
+        return Some(a << n.min(SHIFT));
+    }
+
+    // past its width every bit of `a` is its sign
+    Some(a >> n.magnitude().to_u64().map_or(a.bits(), |n| n.min(a.bits())))
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(bytes = prog.len())))]
+pub fn parse_json(prog: &str) -> Result<File, serde_json::Error> {
+    serde_json::from_str::<File>(prog)
+}
+
+/// `bytes`, the AST at `path` written down as `encoding`, reporting what is
+/// wrong with it at `location`.
+fn decode(encoding: Encoding, bytes: &[u8], path: &Path, location: &Location) -> Result<File, Error> {
+    encoding.decode(bytes).map_err(|err| {
+        let msg = format!("Cannot read {}: {}", path.display(), err);
+
+        Error::new(msg.as_str(), location.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_prelude, rinha, Profile};
+    use crate::host::CaptureHost;
+
+    /// Clock advancing 5ms on every reading.
+    #[derive(Default)]
+    struct FakeClock {
+        ticks: u128,
+    }
+
+    impl Host for FakeClock {
+        fn print(&mut self, _: &str) {}
+
+        fn read_to_string(&mut self, path: &Path) -> std::io::Result<String> {
+            std::fs::read_to_string(path)
+        }
+
+        fn now(&mut self) -> Result<u128, String> {
+            self.ticks += 5;
+
+            Ok(self.ticks)
+        }
+    }
+
+    /// Counts of every callback, shared with the test once installed.
+    #[derive(Default)]
+    struct Counter {
+        counts: Rc<RefCell<[usize; 5]>>,
+    }
+
+    impl EvalHooks for Counter {
+        fn on_enter(&mut self, _: &Term) {
+            self.counts.borrow_mut()[0] += 1;
+        }
+
+        fn on_exit(&mut self, _: &Term, _: &Result<Output, Error>) {
+            self.counts.borrow_mut()[1] += 1;
+        }
+
+        fn on_call(&mut self, _: &Closure, _: &[Output]) {
+            self.counts.borrow_mut()[2] += 1;
+        }
+
+        fn on_bind(&mut self, _: &str, _: &Output) {
+            self.counts.borrow_mut()[3] += 1;
+        }
+
+        fn on_return(&mut self, _: &Closure, _: &Result<Output, Error>) {
+            self.counts.borrow_mut()[4] += 1;
+        }
+    }
+
+    #[test]
+    fn fib() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/fib.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(55)));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut interpreter = Interpreter::default();
+
+        // each of these used to take the process down
+        let err = interpreter.read_json("./json/truncated.json").unwrap_err();
+
+        assert!(err.message.starts_with("Cannot read ./json/truncated.json: "));
+        assert!(interpreter.read_json("./json/missing.json").is_err());
+
+        let prog = parser::parse("fib()", "test").unwrap();
+
+        assert!(interpreter.eval(&prog.expression, &mut Context::default()).is_err());
+
+        let prog = parser::parse("let f = fn (n) => { 1 + f(n + 1) }; f(0)", "test").unwrap();
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "Out of stack, the program recurses too deep");
+    }
+
+    #[test]
+    fn encodings() {
+        let mut interpreter = Interpreter::default();
+        let expected = interpreter.read_json("./json/fib.json").unwrap().expression;
+
+        assert_eq!(interpreter.read_json("./json/fib.yaml").unwrap().expression, expected);
+        assert_eq!(interpreter.read_json("./json/fib.msgpack").unwrap().expression, expected);
+        assert!(interpreter.read_ast("./json/fib.msgpack", Encoding::Json).is_err());
+    }
+
+    #[test]
+    fn print() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { print("output") };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Void);
+    }
+
+    #[test]
+    fn type_of() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            let f = fn (x) => { typeof(x) };
+            (f(1), f("a"), f(true), f((1, 2)), f(f), f(channel()), typeof(print(1)))
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "(int, str, bool, tuple, closure, channel, void)");
+    }
+
+    #[test]
+    fn inspect() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            let step = 2;
+            let add = fn (n, m = 1, ...rest) => { n + m + step };
+            let _ = inspect((1, "a\n", (true, add)));
+            inspect(3) + 1
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "4");
+        assert_eq!(
+            interpreter.host().errors(),
+            "rinha!:75:107: tuple (int 1, str \"a\\n\", tuple (bool true, \
+             closure/3 (n, m = .., ...rest) capturing [step]))\n\
+             rinha!:109:119: int 3\n"
+        );
+    }
+
+    #[test]
+    fn write() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            write("[");
+            write((1, "a"));
+            print("]");
+            write(3)
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res, Output::Void);
+        assert_eq!(interpreter.host().output(), "[(1, a)]\n3");
+    }
+
+    #[test]
+    fn eprint() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            eprint("working");
+            print((1, "a"));
+            eprint((2, "b"))
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res, Output::Void);
+        assert_eq!(interpreter.host().output(), "(1, a)\n");
+        assert_eq!(interpreter.host().errors(), "working\n(2, b)\n");
+    }
+
+    #[test]
+    fn add1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 + 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(3)));
+    }
+
+    #[test]
+    fn add2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { true + 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform add operation");
+    }
+
+    #[test]
+    fn concat1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 + "abc" };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("1abc".into()));
+    }
+
+    #[test]
+    fn concat2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "abc" + 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("abc1".into()));
+    }
+
+    #[test]
+    fn concat3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "abc" + "def" };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("abcdef".into()));
+    }
+
+    #[test]
+    fn sub1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 10 - 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(8)));
+    }
+
+    #[test]
+    fn sub2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "abc" - 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform sub operation");
+    }
+
+    #[test]
+    fn mul1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 2 * 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(4)));
+    }
+
+    #[test]
+    fn mul2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "abc" * 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform mul operation");
+    }
+
+    #[test]
+    fn div1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 10 / 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(5)));
+    }
+
+    #[test]
+    fn div2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 10 / 0 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Arithmetic error, dividing by zero");
+    }
+
+    #[test]
+    fn div3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "abc" / 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform div operation");
+    }
+
+    #[test]
+    fn eq1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 == 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(true));
+    }
+
+    #[test]
+    fn eq2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 == "abc" };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(false));
+    }
+
+    #[test]
+    fn neq1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 != true };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(true));
+    }
+
+    #[test]
+    fn neq2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { true != true };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(false));
+    }
+
+    #[test]
+    fn gt1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 2 > 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(true));
+    }
+
+    #[test]
+    fn gt2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { "b" > "a" };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(true));
+    }
+
+    #[test]
+    fn gt3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 1 > "a" };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform gt operation");
+    }
+
+    #[test]
+    fn gt4() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { true > false };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform gt operation");
+    }
+
+    #[test]
+    fn rem1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 10 % 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(0)));
+    }
+
+    #[test]
+    fn rem2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { 10 % true };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot perform rem operation");
+    }
+
+    #[test]
+    fn and1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { true && 2 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(2)));
+    }
+
+    #[test]
+    fn and2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { true && 2 + 3 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(5)));
+    }
+
+    #[test]
+    fn and3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { false && 1 };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Bool(false));
+    }
+
+    #[test]
+    fn if1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            if (true) {
+              "ok"
+            } else {
+              "fail"
+            }
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("ok".into()));
+    }
+
+    #[test]
+    fn if2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            if (false) {
+              "ok"
+            } else {
+              "fail"
+            }
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("fail".into()));
+    }
+
+    #[test]
+    fn if3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            if ("abc") {
+              "ok"
+            } else {
+              "fail"
+            }
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(
+            res.message,
+            "Condition expression not resolve to a boolean primitive"
+        );
+    }
+
+    #[test]
+    fn tuple() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { (1, 2) };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Int(BigInt::from(1)), Output::Int(BigInt::from(2))])
+        );
+    }
+
+    #[test]
+    fn var() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            let a = 1;
+            a + 2
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(3)));
+    }
+
+    #[test]
+    fn first1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { first((1, 7)) };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(1)));
+    }
+
+    #[test]
+    fn first2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { first("abc") };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot access first of a non tuple argument");
+    }
+
+    #[test]
+    fn second1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { second((1, 7)) };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(7)));
+    }
+
+    #[test]
+    fn second2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { second("abc") };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot access second of a non tuple argument");
+    }
+
+    #[test]
+    fn closure1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            fn (a, b) => {
+              a + b
+            }
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert!(matches!(res, Output::Closure(..)));
+    }
+
+    #[test]
+    fn closure2() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            let add = fn (a, b) => {
+              a + b
+            };
+            add(1)
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(
+            res.message,
+            "Arguments declaration differs parameters declaration"
+        );
+    }
+
+    #[test]
+    fn closure3() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            let a = 1;
+            a()
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Calling a not callable");
+    }
+
+    #[test]
+    fn builtin1() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/builtin1.json").unwrap();
+        let mut context = Context::default();
+
+        interpreter.register("double", |_, args| match &args[..] {
+            [Output::Int(x)] => Ok(Output::Int(x * 2)),
+            _ => Err(String::from("double expects an int")),
+        });
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(42)));
+    }
+
+    #[test]
+    fn builtin2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/builtin2.json").unwrap();
+        let mut context = Context::default();
+
+        interpreter.register("double", |_, _| Ok(Output::Void));
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(6)));
+    }
+
+    #[test]
+    fn import1() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/import1.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Int(BigInt::from(3)), Output::Int(BigInt::from(42))])
+        );
+    }
+
+    #[test]
+    fn import2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/import2.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cyclic import of json/modules/cycle1.rinha");
+    }
+
+    #[test]
+    fn import3() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/import3.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Int(BigInt::from(6)), Output::Int(BigInt::from(12))])
+        );
+    }
+
+    #[test]
+    fn import4() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/import4.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Module math does not export helper");
+    }
+
+    #[test]
+    fn import5() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/import5.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        // lcm is exported, the helper it calls is not
+        assert_eq!(res.message, "Variable helper is not declared");
+    }
+
+    #[test]
+    fn closure4() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            let add = fn (a) => {
+              fn (b) => {
+                a + b
+              }
+            };
+            add(1)(2)
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(BigInt::from(3)));
+    }
+
+    #[test]
+    fn prelude() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/prelude.json").unwrap();
+        let mut context = Context::default();
+
+        interpreter.prelude(default_prelude(), &mut context).unwrap();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Int(BigInt::from(9)), Output::Bool(false)])
+        );
+    }
+
+    #[test]
+    fn getenv1() {
+        let mut interpreter = Interpreter::new(StdHost {
+            allow_env: true,
+            ..StdHost::default()
+        });
+        let prog = interpreter.read_json("./json/getenv1.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Str("interpreter".into()), Output::Bool(false)])
+        );
+    }
+
+    #[test]
+    fn getenv2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/getenv2.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Environment access is disabled, run with --allow-env");
+    }
+
+    #[test]
+    fn argv() {
+        let mut interpreter = Interpreter::new(StdHost {
+            args: vec![String::from("10"), String::from("fast")],
+            ..StdHost::default()
+        });
+        let prog = interpreter.read_json("./json/argv.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![
+                Output::Int(2.into()),
+                Output::Tuple(vec![Output::Str("fast".into()), Output::Bool(false)])
+            ])
+        );
+    }
+
+    #[test]
+    fn now() {
+        let mut interpreter = Interpreter::new(FakeClock::default());
+        let prog = interpreter.read_json("./json/now.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(5.into()));
+    }
+
+    #[test]
+    fn assert1() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/assert1.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(2.into()));
+    }
+
+    #[test]
+    fn assert2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/assert2.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Assertion failed: x must be positive");
+        assert_eq!((res.start, res.end), (11, 46));
+    }
+
+    #[test]
+    fn panic() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/panic.json").unwrap()).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "negative: -2");
+        assert_eq!((err.start, err.end), (43, 66));
+        assert_eq!(interpreter.host().output(), "negative: -1\n");
+    }
+
+    #[test]
+    fn exit() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            print("before");
+            try { exit(3) } catch fn (err) => { print(err) };
+            print("after")
+        };
+
+        let err = interpreter.eval(&prog, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::Exit(3));
+        assert_eq!(interpreter.host().output(), "before\n");
+
+        let prog = rinha! { exit(256) };
+        let err = interpreter.eval(&prog, &mut Context::default()).unwrap_err();
+
+        assert_eq!((err.kind, err.message.as_str()), (ErrorKind::Failure, "Exit status 256 is out of range 0..=255"));
+    }
+
+    #[test]
+    fn lazy() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/lazy.json").unwrap()).unwrap();
+        let mut context = Context::default();
+
+        interpreter.set_lazy(true);
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(res, Output::Int(2.into()));
+        assert_eq!(interpreter.host().output(), "start\n(2, 2)\n");
+    }
+
+    #[test]
+    fn unicode1() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/unicode1.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![
+                Output::Int(4.into()),
+                // code point order, no locale collation
+                Output::Tuple(vec![Output::Str("ñ🦀".into()), Output::Bool(false)])
+            ])
+        );
+    }
+
+    #[test]
+    fn unicode2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/unicode2.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Slice 2..4 is out of range for a string of length 3");
+    }
+
+    #[test]
+    fn convert() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/convert.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![
+                Output::Int(43.into()),
+                Output::Tuple(vec![
+                    Output::Str("-7true".into()),
+                    Output::Str("\"4x\" is not an integer".into())
+                ])
+            ])
+        );
+    }
+
+    #[test]
+    fn format() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/format.json").unwrap()).unwrap();
+        let mut context = Context::default();
+
+        let err = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "n = 3 and s = x, {(1, true)} true\n");
+        assert_eq!(err.message, "format has more placeholders than the 1 values given");
+    }
+
+    #[test]
+    fn display() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/display.json").unwrap()).unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(interpreter.host().output(), "((1, 2), x, <#closure/2>)\n");
+        assert_eq!(res.to_string(), "((1, 2), 3)");
+    }
+
+    #[test]
+    fn big_int() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/big_int.json").unwrap()).unwrap();
+
+        let res = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "922337203685477580700000000000000000000000000000");
+        let int = |value: &str| {
+            serde_json::from_str::<Term>(&format!(
+                r#"{{"kind": "Int", "value": {}, "location": {{"start": 0, "end": 0, "filename": ""}}}}"#,
+                value
+            ))
+        };
+
+        assert!(int("\"-12\"").is_ok());
+        assert!(int("\"12a\"").is_err());
+        assert!(int("1e40").is_err());
+    }
+
+    #[test]
+    fn profile() {
+        let prog = parse_json(&std::fs::read_to_string("./json/profile.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let res = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "(1, 2)\nfalse\n");
+        assert_eq!(res.to_string(), "(1, 2, 3)");
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Profile::Strict);
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "(1, 2)\n(1, 2)\n");
+        assert_eq!(err.message, "Cannot perform or operation");
+    }
+
+    #[test]
+    fn coercion() {
+        let prog = parse_json(&std::fs::read_to_string("./json/coercion.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Semantics {
+            coercion: Lint::Warn,
+            ..Semantics::default()
+        });
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let warnings = interpreter.take_warnings();
+
+        assert_eq!(interpreter.host().output(), "n = 1\nn = 2\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].to_string(), "coercion.rinha:20:30: warning: Implicit coercion of int to str");
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Semantics {
+            coercion: Lint::Deny,
+            ..Semantics::default()
+        });
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "");
+        assert_eq!(err.message, "Implicit coercion of int to str");
+    }
+
+    #[test]
+    fn truthy() {
+        let prog = parse_json(&std::fs::read_to_string("./json/truthy.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "Condition expression not resolve to a boolean primitive");
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Semantics {
+            truthy_conditions: true,
+            ..Semantics::default()
+        });
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "no\nno\nyes\nyes\n");
+    }
+
+    #[test]
+    fn duplicate_parameters() {
+        let prog = parse_json(&std::fs::read_to_string("./json/duplicates.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let warnings = interpreter.take_warnings();
+
+        assert_eq!(interpreter.host().output(), "3\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Parameter a is declared twice");
+        assert_eq!(warnings[0].location.start, 21);
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Profile::Strict);
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "");
+        assert_eq!(err.message, "Parameter a is declared twice");
+    }
+
+    #[test]
+    fn overflow() {
+        let prog = parse_json(&std::fs::read_to_string("./json/overflow.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Semantics {
+            overflow: Lint::Warn,
+            ..Semantics::default()
+        });
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let warnings: Vec<String> = interpreter.take_warnings().iter().map(|warning| warning.message.clone()).collect();
+
+        assert_eq!(interpreter.host().output(), "18446744073709551616\n9223372036854775807\n");
+        assert_eq!(
+            warnings,
+            [
+                "Integer overflow past 64 bits in 4611686018427387904 * 2",
+                "Integer overflow past 64 bits in 9223372036854775807 + 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn curry() {
+        let prog = parse_json(&std::fs::read_to_string("./json/curry.json").unwrap()).unwrap();
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!((err.start, err.end), (51, 57));
+
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        interpreter.set_semantics(Semantics {
+            partial_application: true,
+            ..Semantics::default()
+        });
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "<#closure/1>\n31\n7\n");
+        assert_eq!(err.message, "Arguments declaration differs parameters declaration");
+    }
+
+    #[test]
+    fn variadic() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/variadic.json").unwrap()).unwrap();
+        let mut context = Context::default();
+
+        interpreter.prelude(default_prelude(), &mut context).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "9\n5\n0\n");
+        assert_eq!((err.start, err.end), (297, 305));
+    }
+
+    #[test]
+    fn sequence() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            let count = fn (n, total) => {
+                if (n == 0) {
+                    total
+                } else {
+                    n;
+                    count(n - 1, total + 1)
+                }
+            };
+            print("start");
+            count(100000, 0)
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "100000");
+        assert_eq!(interpreter.host().output(), "start\n");
+
+        let location = r#"{"start": 0, "end": 0, "filename": "a.rinha"}"#;
+        let seq = format!(r#"{{"kind": "Seq", "expressions": [], "location": {}}}"#, location);
+        let json = format!(r#"{{"name": "a.rinha", "location": {0}, "expression": {1}}}"#, location, seq);
+
+        assert!(parse_json(&json).unwrap_err().to_string().contains("expected at least one expression"));
+    }
+
+    #[test]
+    fn try_catch() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            let div = fn (a, b) => {
+                try { a / b } catch fn (err) => { print(err); 0 }
+            };
+            let inner = fn (err) => { second(err) };
+            print(div(6, 3));
+            let nested = try { try { first(1) } catch inner } catch fn (err) => { "outer: " + err };
+            (div(1, 0), nested)
+        };
+
+        let res = interpreter.eval(&prog, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "(0, outer: Cannot access second of a non tuple argument)");
+        assert_eq!(interpreter.host().output(), "2\nArithmetic error, dividing by zero\n");
+
+        let prog = parse_json(&std::fs::read_to_string("./json/forever.json").unwrap()).unwrap();
+        let prog = Term::try_(prog.expression, Term::function(["err"], Term::int(0)));
+        let token = CancellationToken::new();
+
+        let canceller = {
+            let token = token.clone();
+
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                token.cancel();
+            })
+        };
+
+        let err = interpreter.eval_cancellable(&prog, token).unwrap_err();
+
+        canceller.join().unwrap();
+
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn short_rest() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! {
+            let f = fn (...rest) => { rest };
+            let _ = print(first(f(1)));
+            second(f(1))
+        };
+
+        let err = interpreter.eval(&prog, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "1\n");
+        assert_eq!(err.message, "Cannot access second of a tuple of 1 elements");
+    }
+
+    #[test]
+    fn defaults() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/defaults.json").unwrap()).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(
+            interpreter.host().output(),
+            "hello ana hello!\nhi bia hi!\nhey cid ?\n11\n6\n"
+        );
+        assert_eq!(err.message, "Arguments declaration differs parameters declaration");
+    }
+
+    #[test]
+    fn named() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/named.json").unwrap()).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "(2, 1, *)\n(4, 3, #)\n10\n3\n");
+        assert_eq!(err.message, "Argument width is given twice");
+    }
+
+    #[test]
+    fn pipe() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/pipe.json").unwrap()).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "7\n5\n11\n10\n");
+        assert_eq!(err.message, "Cannot perform compose operation");
+    }
+
+    #[test]
+    fn bitwise() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = rinha! { ((-1 <<< 64) ^ 1, -12345678901234567890 <<< -60) };
+        let res = interpreter.eval(&prog, &mut Context::default());
+
+        assert_eq!(res.unwrap().to_string(), "(-18446744073709551615, -11)");
+
+        let err = interpreter.eval(&rinha! { 1 <<< 16777217 }, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "Arithmetic error, shifting by 16777217 bits");
+
+        let err = interpreter.eval(&rinha! { 1 & true }, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "Cannot perform bitand operation");
+    }
+
+    #[test]
+    fn tail() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/tail.json").unwrap()).unwrap();
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "50005000\n1\n1\n");
+    }
+
+    #[test]
+    fn frames() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/tail.json").unwrap()).unwrap();
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        // scopes of returned calls come back emptied, bindings and all
+        assert!(!interpreter.frames.is_empty() && interpreter.frames.len() <= FRAMES);
+        assert!(interpreter.frames.iter().all(HashMap::is_empty));
+    }
+
+    #[test]
+    fn mutual() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let context = Context::default();
+        let prog = rinha! {
+            let even = fn (n) => { if (n == 0) { true } else { odd(n - 1) } };
+            let odd = fn (n) => { if (n == 0) { false } else { even(n - 1) } };
+            print(even(10))
+        };
+
+        assert!(interpreter.unbound(&prog, &context).is_empty());
+
+        interpreter.eval(&prog, &mut context.clone()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "true\n");
+    }
+
+    #[test]
+    fn lookup() {
+        let run = |prog: &Term| {
+            let mut interpreter = Interpreter::new(CaptureHost::default());
+
+            interpreter.eval(prog, &mut Context::default()).unwrap();
+            interpreter.host().output().to_string()
+        };
+
+        // the scope the closure was made in comes first
+        assert_eq!(run(&rinha! { let x = 1; let f = fn () => { x }; let x = 2; print(f()) }), "1\n");
+        // names it does not bind come from where it is called
+        assert_eq!(run(&rinha! { let f = fn () => { y }; let y = 3; print(f()) }), "3\n");
+        assert_eq!(run(&rinha! { let f = fn () => { z }; let g = fn (z) => { f() }; print(g(4)) }), "4\n");
+    }
+
+    #[test]
+    fn escape() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        // the closures outlive the calls making them, and in the lazy
+        // evaluation `w` is a thunk linked to the scope of the call to `make`,
+        // reading `k` from it once forced
+        let prog = rinha! {
+            let make = fn (n) => {
+                let g = fn (v) => {
+                    let w = v + k;
+                    fn (x) => { x + w }
+                };
+                let k = n * 2;
+                g(1)
+            };
+            let a = make(1);
+            let b = make(10);
+            (a(0), b(0), a(1))
+        };
+
+        for lazy in [false, true] {
+            interpreter.set_lazy(lazy);
+
+            let res = interpreter.eval(&prog, &mut Context::default());
+
+            assert_eq!(res.unwrap().to_string(), "(3, 21, 4)");
+            assert!(!interpreter.frames.is_empty() && interpreter.frames.iter().all(HashMap::is_empty));
+        }
+    }
+
+    #[test]
+    fn gc() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/cycle.json").unwrap()).unwrap();
+
+        interpreter.set_lazy(true);
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+        interpreter.heap.collect();
+
+        // the scope of `main` keeps a closure whose unread lazy binding links
+        // back to that scope, nothing else holds either once the call returns
+        let stats = interpreter.gc_stats();
+
+        assert_eq!(interpreter.host().output(), "0\n");
+        assert_eq!(stats.collections, 1);
+        assert!(stats.freed > 0);
+    }
+
+    #[test]
+    fn allocations() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/allocations.json").unwrap()).unwrap();
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        // both closures capture a scope and both calls run in one
+        assert_eq!(interpreter.host().output(), "(1, x1)\n");
+        assert_eq!(
+            interpreter.allocations(),
+            Allocations {
+                scopes: 4,
+                thunks: 0,
+                closures: 2,
+                tuples: 1,
+                strings: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn counters() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/tail.json").unwrap()).unwrap();
+
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        // every step of the loops is a call, none of them nested
+        let counters = interpreter.counters();
+
+        assert!(counters.calls > 10000);
+        assert_eq!(counters.max_depth, 1);
+        assert!(counters.evaluated > counters.calls);
+    }
+
+    #[test]
+    fn specialization() {
+        let prog = parse_json(&std::fs::read_to_string("./json/specialize.json").unwrap()).unwrap();
+        let run = |enabled| {
+            let mut interpreter = Interpreter::new(CaptureHost::default());
+
+            interpreter.set_specialization(enabled);
+
+            let value = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+            (value.to_string(), interpreter.counters())
+        };
+
+        let (value, counters) = run(true);
+
+        // `f(i)` and `a + b` are hot when they first see another closure and strings
+        assert_eq!(value, "(420, (1, ab))");
+        assert!(counters.specialized > 0);
+        assert_eq!(counters.deoptimized, 2);
+        assert_eq!(run(false), (value, Counters { specialized: 0, deoptimized: 0, ..counters }));
+    }
+
+    #[test]
+    fn equality() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/equality.json").unwrap()).unwrap();
+
+        let err = interpreter.eval(&prog.expression, &mut Context::default()).unwrap_err();
+
+        assert_eq!(interpreter.host().output(), "true\ntrue\nfalse\n");
+        assert_eq!(err.message, "Cannot compare closures");
+    }
+
+    #[test]
+    fn tuple3() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/tuple3.json").unwrap()).unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap();
+
+        assert_eq!(
+            res,
+            Output::Tuple(vec![Output::Int(1.into()), Output::Str("c".into()), Output::Bool(true)])
+        );
+        assert_eq!(interpreter.host().output(), "(1, b, c)\n");
+    }
+
+    #[test]
+    fn tuple4() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! { nth((1, 2, 3), 3) };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Index 3 is out of range for a tuple of 3 elements");
+    }
+
+    #[test]
+    fn destructure1() {
+        let mut interpreter = Interpreter::default();
+        let prog = rinha! {
+            let pair = (1, ("a", true));
+            let (n, (s, b)) = pair;
+            if (b) {
+              s + n
+            } else {
+              s
+            }
+        };
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog, &mut context).unwrap();
+
+        assert_eq!(res, Output::Str("a1".into()));
+    }
+
+    #[test]
+    fn destructure2() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/destructure2.json").unwrap();
+        let mut context = Context::default();
+
+        let res = interpreter.eval(&prog.expression, &mut context).unwrap_err();
+
+        assert_eq!(res.message, "Cannot destructure a tuple of 3 elements into 2 names");
+        assert_eq!((res.start, res.end), (4, 10));
+    }
+
+    #[test]
+    fn capture() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/capture.json").unwrap();
+        let mut context = Context::default();
+
+        let Output::Closure(closure) = interpreter.eval(&prog.expression, &mut context).unwrap() else {
+            panic!("expected a closure")
+        };
+
+        let mut names: Vec<String> = closure.context.bindings().into_iter().map(|(name, _)| name).collect();
+        names.sort();
+
+        assert_eq!(names, ["k", "y"]);
+    }
+
+    #[test]
+    fn env() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/env.json").unwrap();
+
+        let (res, context) = interpreter.eval_with_env(&prog.expression).unwrap();
+        let mut names: Vec<String> = context.bindings().into_iter().map(|(name, _)| name).collect();
+        names.sort();
+
+        assert_eq!(res, Output::Int(3.into()));
+        assert_eq!(names, ["a", "b", "f", "x"]);
+        assert_eq!(context.lookup("b"), Some(Output::Str("b".into())));
+    }
+
+    #[test]
+    fn hooks() {
+        let mut interpreter = Interpreter::default();
+        let prog = interpreter.read_json("./json/hooks.json").unwrap();
+        let counter = Counter::default();
+        let counts = counter.counts.clone();
+
+        interpreter.set_hooks(counter);
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        // let, fn, call, f, 1 and n are entered, f and n bound
+        assert_eq!(*counts.borrow(), [6, 6, 1, 2, 1]);
+    }
+
+    #[test]
+    fn borrowed() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/tail.json").unwrap()).unwrap();
+
+        // the program is only read, running it again gives the same output
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(interpreter.host().output(), "50005000\n1\n1\n".repeat(2));
+        assert_eq!(interpreter.tails.len(), 3);
+    }
+
+    #[test]
+    fn preevaluate() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/constants.json").unwrap()).unwrap();
+
+        let program = interpreter.preevaluate(&prog.expression);
+        let mut folded = Vec::new();
+        let mut term = &program;
+
+        while let Term::Let(x) = term {
+            if matches!(*x.value, Term::Int(_) | Term::Str(_) | Term::Bool(_) | Term::Tuple(_)) {
+                folded.push(x.name.text.as_str());
+            }
+
+            term = &x.next;
+        }
+
+        // calls, what reads their results and failures are left to the run
+        assert_eq!(folded, ["big", "name", "pair", "flag"]);
+        assert_eq!(interpreter.host().output(), "");
+
+        let err = interpreter.eval(&program, &mut Context::default()).unwrap_err();
+
+        assert_eq!(err.message, "Arithmetic error, dividing by zero");
+        assert!(interpreter.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn share() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/share.json").unwrap()).unwrap();
+
+        let res = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        // both closures come from the same literal, only their scopes differ
+        let Output::Tuple(closures) = res else {
+            panic!("expected a tuple, got {}", res);
+        };
+        let (Output::Closure(a), Output::Closure(b)) = (&closures[0], &closures[1]) else {
+            panic!("expected closures");
+        };
+
+        assert!(Arc::ptr_eq(&a.body, &b.body));
+        assert_ne!(a.context, b.context);
+    }
+
+    #[test]
+    fn cancellable() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/forever.json").unwrap()).unwrap();
+        let token = CancellationToken::new();
+
+        let canceller = {
+            let token = token.clone();
+
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                token.cancel();
+            })
+        };
+
+        let err = interpreter.eval_cancellable(&prog.expression, token.clone()).unwrap_err();
+
+        canceller.join().unwrap();
+
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+        assert_eq!(err.message, "Evaluation was cancelled");
+        // tail calls run in place of the call that made them
+        assert_eq!(err.backtrace.len(), 1);
+
+        // the token stays with the one evaluation
+        let prog = parse_json(&std::fs::read_to_string("./json/fib.json").unwrap()).unwrap();
+
+        assert!(interpreter.eval(&prog.expression, &mut Context::default()).is_ok());
+        assert_eq!(
+            interpreter.eval_cancellable(&prog.expression, token).unwrap_err().kind,
+            ErrorKind::Cancelled
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let mut context = Context::default();
+        let res = interpreter.eval(&rinha! { let f = fn (x) => { x + 1 }; (f, f) }, &mut context).unwrap();
+
+        // reading a closure copies no part of its function
+        let Output::Tuple(copies) = res else {
+            panic!("expected a tuple, got {}", res);
+        };
+        let (Output::Closure(a), Output::Closure(b)) = (&copies[0], &copies[1]) else {
+            panic!("expected closures");
+        };
+
+        assert!(Arc::ptr_eq(&a.body, &b.body) && Arc::ptr_eq(&a.args, &b.args));
+
+        let location = a.body.location().clone();
+        let res = interpreter.apply(b.clone(), vec![Output::Int(41.into())], &context, &location);
+
+        assert_eq!(res.unwrap(), Output::Int(42.into()));
+    }
+}
@@ -0,0 +1,415 @@
+//! A-normal form of a program, between the `Term` tree read from JSON and
+//! whatever runs it. Operands are only literals and names, anything else is
+//! bound to a temporary first, so the order of evaluation is spelled out and
+//! a call in tail position is the tail of its block.
+
+use num_bigint::BigInt;
+use std::fmt;
+
+use crate::ast::{BinaryOp, Location, NamedArgument, Pattern, Term};
+use crate::format::{quote, symbol};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Atom {
+    Int(BigInt),
+    Str(String),
+    Bool(bool),
+    /// A binding, a builtin or one of the temporaries `%0`, `%1`, .. no
+    /// program can name.
+    Var(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    Atom(Atom),
+    /// `module.name(..)`, run in the scope of the module as `Term::Access`
+    /// callees are.
+    Member(Atom, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Atom(Atom),
+    Binary(BinaryOp, Atom, Atom),
+    Call {
+        callee: Callee,
+        arguments: Vec<Atom>,
+        named: Vec<(String, Atom)>,
+    },
+    Access(Atom, String),
+    Tuple(Vec<Atom>),
+    First(Atom),
+    Second(Atom),
+    Print(Atom),
+    Import {
+        path: String,
+        alias: Option<String>,
+    },
+    Function(Function),
+    If(Atom, Block, Block),
+    /// The body, then the handler, a block giving the function called with
+    /// the message of the error when the body fails.
+    Try(Block, Block),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub parameters: Vec<Parameter>,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub rest: bool,
+    pub default: Option<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Name(String),
+    Pattern(Pattern),
+}
+
+/// `let target = expr`, `location` being that of the term `expr` comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub target: Target,
+    pub expr: Expr,
+    pub export: bool,
+    pub location: Location,
+}
+
+/// Steps run in order, then `tail` gives the value of the block. As with
+/// `let`s in the tree, names a block binds stay visible to the code running
+/// after it in the same scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub steps: Vec<Step>,
+    pub tail: Box<Expr>,
+    pub location: Location,
+}
+
+/// `term` in A-normal form, temporaries numbered across the whole program.
+pub fn lower(term: &Term) -> Block {
+    Lowering { temps: 0 }.block(term)
+}
+
+struct Lowering {
+    temps: usize,
+}
+
+impl Lowering {
+    fn block(&mut self, term: &Term) -> Block {
+        let mut steps = Vec::new();
+        let tail = self.expr(term, &mut steps);
+        let mut last = term;
+
+        loop {
+            last = match last {
+                Term::Let(x) => &x.next,
+                Term::Seq(x) => x.expressions.last().expect("sequences are never empty"),
+                _ => break,
+            };
+        }
+
+        Block {
+            steps,
+            tail: Box::new(tail),
+            location: last.location().clone(),
+        }
+    }
+
+    fn atom(&mut self, term: &Term, steps: &mut Vec<Step>) -> Atom {
+        let expr = match self.expr(term, steps) {
+            Expr::Atom(atom) => return atom,
+            expr => expr,
+        };
+        let name = format!("%{}", self.temps);
+
+        self.temps += 1;
+        steps.push(Step {
+            target: Target::Name(name.clone()),
+            expr,
+            export: false,
+            location: term.location().clone(),
+        });
+
+        Atom::Var(name)
+    }
+
+    fn call<'a>(
+        &mut self,
+        callee: &Term,
+        arguments: impl IntoIterator<Item = &'a Term>,
+        named: &[NamedArgument],
+        steps: &mut Vec<Step>,
+    ) -> Expr {
+        let callee = match callee {
+            Term::Access(x) => Callee::Member(self.atom(&x.module, steps), x.name.clone()),
+            callee => Callee::Atom(self.atom(callee, steps)),
+        };
+        let arguments = arguments.into_iter().map(|arg| self.atom(arg, steps)).collect();
+        let named = named
+            .iter()
+            .map(|arg| (arg.name.to_string(), self.atom(&arg.value, steps)))
+            .collect();
+
+        Expr::Call {
+            callee,
+            arguments,
+            named,
+        }
+    }
+
+    fn expr(&mut self, term: &Term, steps: &mut Vec<Step>) -> Expr {
+        match term {
+            Term::Int(x) => Expr::Atom(Atom::Int(x.value.clone())),
+            Term::Str(x) => Expr::Atom(Atom::Str(x.value.clone())),
+            Term::Bool(x) => Expr::Atom(Atom::Bool(x.value)),
+            Term::Var(x) => Expr::Atom(Atom::Var(x.text.to_string())),
+            // the evaluator runs `x |> f` as the call `f(x)`
+            Term::Binary(x) if x.op == BinaryOp::Pipe => self.call(&x.rhs, [&*x.lhs], &[], steps),
+            Term::Binary(x) => {
+                let lhs = self.atom(&x.lhs, steps);
+                let rhs = self.atom(&x.rhs, steps);
+
+                Expr::Binary(x.op.clone(), lhs, rhs)
+            }
+            Term::Call(x) | Term::Recur(x) => self.call(&x.callee, &x.arguments, &x.named, steps),
+            Term::Access(x) => Expr::Access(self.atom(&x.module, steps), x.name.clone()),
+            Term::Tuple(x) => Expr::Tuple(x.elements.iter().map(|element| self.atom(element, steps)).collect()),
+            Term::First(x) => Expr::First(self.atom(&x.value, steps)),
+            Term::Second(x) => Expr::Second(self.atom(&x.value, steps)),
+            Term::Print(x) => Expr::Print(self.atom(&x.value, steps)),
+            Term::Import(x) => Expr::Import {
+                path: x.path.clone(),
+                alias: x.alias.clone(),
+            },
+            Term::Function(x) => Expr::Function(Function {
+                parameters: x
+                    .parameters
+                    .iter()
+                    .map(|param| Parameter {
+                        name: param.text.to_string(),
+                        rest: param.rest,
+                        default: param.default.as_deref().map(|default| self.block(default)),
+                    })
+                    .collect(),
+                body: self.block(&x.value),
+            }),
+            Term::If(x) => {
+                let condition = self.atom(&x.condition, steps);
+
+                Expr::If(condition, self.block(&x.then), self.block(&x.otherwise))
+            }
+            Term::Let(x) => {
+                let expr = self.expr(&x.value, steps);
+
+                steps.push(Step {
+                    target: match &x.pattern {
+                        Some(pattern) => Target::Pattern(pattern.clone()),
+                        None => Target::Name(x.name.text.to_string()),
+                    },
+                    expr,
+                    export: x.export,
+                    location: x.value.location().clone(),
+                });
+
+                self.expr(&x.next, steps)
+            }
+            Term::Seq(x) => {
+                let (last, rest) = x.expressions.split_last().expect("sequences are never empty");
+
+                // only evaluated for what they do, literals and names do nothing
+                for expression in rest {
+                    self.atom(expression, steps);
+                }
+
+                self.expr(last, steps)
+            }
+            Term::Try(x) => Expr::Try(self.block(&x.body), self.block(&x.handler)),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Atom::Int(x) => write!(f, "{}", x),
+            Atom::Str(x) => write!(f, "{}", quote(x)),
+            Atom::Bool(x) => write!(f, "{}", x),
+            Atom::Var(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Var(x) => x.text.to_string(),
+        Pattern::Tuple(x) => {
+            let elements: Vec<String> = x.elements.iter().map(self::pattern).collect();
+
+            format!("({})", elements.join(", "))
+        }
+    }
+}
+
+fn list<T: fmt::Display>(items: &[T]) -> String {
+    items.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
+}
+
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn nested(&mut self, block: &Block) {
+        self.out.push('{');
+        self.depth += 1;
+        self.newline();
+        self.block(block);
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn block(&mut self, block: &Block) {
+        for step in &block.steps {
+            if step.export {
+                self.out.push_str("export ");
+            }
+
+            match &step.target {
+                Target::Name(name) => self.out.push_str(&format!("let {} = ", name)),
+                Target::Pattern(target) => self.out.push_str(&format!("let {} = ", pattern(target))),
+            }
+
+            self.expr(&step.expr);
+            self.out.push(';');
+            self.newline();
+        }
+
+        self.expr(&block.tail);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Atom(x) => self.out.push_str(&x.to_string()),
+            Expr::Binary(op, lhs, rhs) => self.out.push_str(&format!("{} {} {}", lhs, symbol(op), rhs)),
+            Expr::Call {
+                callee,
+                arguments,
+                named,
+            } => {
+                match callee {
+                    Callee::Atom(x) => self.out.push_str(&x.to_string()),
+                    Callee::Member(module, name) => self.out.push_str(&format!("{}.{}", module, name)),
+                }
+
+                let named: Vec<String> = named.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+                let all: Vec<String> = arguments.iter().map(Atom::to_string).chain(named).collect();
+
+                self.out.push_str(&format!("({})", all.join(", ")));
+            }
+            Expr::Access(module, name) => self.out.push_str(&format!("{}.{}", module, name)),
+            Expr::Tuple(x) => self.out.push_str(&format!("({})", list(x))),
+            Expr::First(x) => self.out.push_str(&format!("first({})", x)),
+            Expr::Second(x) => self.out.push_str(&format!("second({})", x)),
+            Expr::Print(x) => self.out.push_str(&format!("print({})", x)),
+            Expr::Import { path, alias } => {
+                self.out.push_str(&format!("import {}", quote(path)));
+
+                if let Some(alias) = alias {
+                    self.out.push_str(&format!(" as {}", alias));
+                }
+            }
+            Expr::Function(x) => {
+                self.out.push_str("fn (");
+
+                for (i, param) in x.parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+
+                    if param.rest {
+                        self.out.push_str("...");
+                    }
+
+                    self.out.push_str(&param.name);
+
+                    if let Some(default) = &param.default {
+                        self.out.push_str(" = ");
+                        self.nested(default);
+                    }
+                }
+
+                self.out.push_str(") => ");
+                self.nested(&x.body);
+            }
+            Expr::If(condition, then, otherwise) => {
+                self.out.push_str(&format!("if ({}) ", condition));
+                self.nested(then);
+                self.out.push_str(" else ");
+                self.nested(otherwise);
+            }
+            Expr::Try(body, handler) => {
+                self.out.push_str("try ");
+                self.nested(body);
+                self.out.push_str(" catch ");
+                self.nested(handler);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer {
+            out: String::new(),
+            depth: 0,
+        };
+
+        printer.block(self);
+
+        writeln!(f, "{}", printer.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn lower() {
+        let prog = parse("let f = fn (n) => { if (n < 2) { n } else { n * f(n - 1) } }; print(f(5) + 1)", "test").unwrap();
+
+        assert_eq!(
+            super::lower(&prog.expression).to_string(),
+            "let f = fn (n) => {\n  let %0 = n < 2;\n  if (%0) {\n    n\n  } else {\n    let %1 = n - 1;\n    let %2 = \
+             f(%1);\n    n * %2\n  }\n};\nlet %3 = f(5);\nlet %4 = %3 + 1;\nprint(%4)\n"
+        );
+    }
+
+    #[test]
+    fn order() {
+        let prog = parse("let (a, b) = (g(1), 2); m.h(a |> k, x = b)", "test").unwrap();
+        let block = super::lower(&prog.expression);
+
+        assert_eq!(
+            block.to_string(),
+            "let %0 = g(1);\nlet (a, b) = (%0, 2);\nlet %1 = k(a);\nm.h(%1, x = b)\n"
+        );
+        assert!(matches!(*block.tail, Expr::Call { callee: Callee::Member(..), .. }));
+    }
+}
@@ -0,0 +1,63 @@
+pub mod ast;
+mod builtins;
+pub mod cache;
+pub mod cancel;
+pub mod canonical;
+pub mod check;
+pub mod color;
+pub mod config;
+mod constants;
+pub mod coverage;
+pub mod diagnostic;
+pub mod diff;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod difftest;
+pub mod encoding;
+mod fib;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+pub mod format;
+mod gc;
+pub mod host;
+pub mod ir;
+mod hooks;
+pub mod lint;
+mod macros;
+pub mod malformed;
+pub mod memory;
+mod interpreter;
+pub mod parser;
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+pub mod plugin;
+mod prelude;
+pub mod rast;
+pub mod repl;
+
+#[cfg(feature = "python")]
+mod python;
+pub mod rpc;
+mod semantics;
+mod specialize;
+pub mod spec;
+mod stack;
+pub mod stats;
+pub mod summary;
+pub mod symbol;
+pub mod tail;
+mod task;
+mod text;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod trace;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use cancel::CancellationToken;
+pub use gc::GcStats;
+pub use hooks::EvalHooks;
+pub use interpreter::{parse_json, Builtin, Closure, Context, Error, ErrorKind, Interpreter, Module, Output, Thunk};
+pub use prelude::default_prelude;
+pub use semantics::{Lint, Profile, Semantics};
+pub use task::{Channel, Task};
+pub use symbol::Symbol;
+pub use text::Text;
@@ -0,0 +1,348 @@
+//! Static checks over a program that do not stop it from running.
+
+use std::fmt;
+
+use crate::{ast::*, Symbol};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub location: Location,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: warning: {}",
+            self.location.filename, self.location.start, self.location.end, self.message
+        )
+    }
+}
+
+impl Warning {
+    /// Same as `Error::render`, with line and column when `source` is the
+    /// text of the file.
+    pub fn render(&self, source: &str) -> String {
+        match Position::of(source, self.location.start) {
+            Some(at) => format!(
+                "{}:{}:{}: warning: {}",
+                self.location.filename, at.line, at.column, self.message
+            ),
+            None => self.to_string(),
+        }
+    }
+}
+
+struct Binding<'a> {
+    name: &'a Parameter,
+    kind: &'static str,
+    used: bool,
+}
+
+/// Lexical scopes of the walk, innermost binding last.
+#[derive(Default)]
+struct Unused<'a> {
+    bindings: Vec<Binding<'a>>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Unused<'a> {
+    fn bind(&mut self, name: &'a Parameter, kind: &'static str) {
+        self.bindings.push(Binding { name, kind, used: false });
+    }
+
+    /// Drops the bindings made since `mark`, reporting the ones never read.
+    /// Names starting with `_` are meant to be ignored.
+    fn unbind(&mut self, mark: usize) {
+        for binding in self.bindings.drain(mark..) {
+            if !binding.used && !binding.name.text.starts_with('_') {
+                self.warnings.push(Warning {
+                    message: format!("{} {} is never read", binding.kind, binding.name.text),
+                    location: binding.name.location.clone(),
+                });
+            }
+        }
+    }
+
+    fn term(&mut self, term: &'a Term) {
+        match term {
+            Term::Var(x) => {
+                // names bound nowhere come from builtins, preludes or imports
+                if let Some(binding) = self.bindings.iter_mut().rev().find(|b| b.name.text == x.text) {
+                    binding.used = true;
+                }
+            }
+            Term::Let(x) => {
+                let mark = self.bindings.len();
+
+                match &x.pattern {
+                    Some(pattern) => {
+                        self.term(&x.value);
+
+                        for name in pattern.names() {
+                            self.bind(name, "binding");
+                        }
+                    }
+                    None => {
+                        // visible in its own value for recursion, reads from
+                        // there do not count as uses
+                        self.bind(&x.name, "binding");
+                        self.term(&x.value);
+                        self.bindings[mark].used = false;
+                    }
+                }
+
+                // exported bindings are read by importers
+                if x.export {
+                    self.bindings[mark..].iter_mut().for_each(|b| b.used = true);
+                }
+
+                self.term(&x.next);
+                self.unbind(mark);
+            }
+            Term::Function(x) => {
+                let mark = self.bindings.len();
+
+                for param in x.parameters.iter() {
+                    if let Some(default) = &param.default {
+                        self.term(default);
+                    }
+
+                    self.bind(param, "parameter");
+                }
+
+                self.term(&x.value);
+                self.unbind(mark);
+            }
+            term => term.children().into_iter().for_each(|child| self.term(child)),
+        }
+    }
+}
+
+/// `let` bindings and function parameters that are never read.
+pub fn unused(term: &Term) -> Vec<Warning> {
+    let mut unused = Unused::default();
+
+    unused.term(term);
+    unused.warnings.sort_by_key(|warning| warning.location.start);
+
+    unused.warnings
+}
+
+/// Names in scope, innermost binding last.
+#[derive(Default)]
+struct Parameters {
+    bindings: Vec<Symbol>,
+    warnings: Vec<Warning>,
+}
+
+impl Parameters {
+    fn term(&mut self, term: &Term) {
+        match term {
+            Term::Let(x) => {
+                let mark = self.bindings.len();
+
+                match &x.pattern {
+                    Some(pattern) => {
+                        self.term(&x.value);
+                        self.bindings.extend(pattern.names().into_iter().map(|name| name.text));
+                    }
+                    None => {
+                        self.bindings.push(x.name.text);
+                        self.term(&x.value);
+                    }
+                }
+
+                self.term(&x.next);
+                self.bindings.truncate(mark);
+            }
+            Term::Function(x) => {
+                let mark = self.bindings.len();
+
+                for param in x.parameters.iter() {
+                    if let Some(default) = &param.default {
+                        self.term(default);
+                    }
+
+                    // the ones past `mark` are parameters of this very function
+                    let problem = match self.bindings.iter().rposition(|name| *name == param.text) {
+                        Some(i) if i >= mark => Some("is declared twice, the last one wins"),
+                        Some(_) => Some("shadows a binding the function could capture"),
+                        None => None,
+                    };
+
+                    if let Some(problem) = problem {
+                        self.warnings.push(Warning {
+                            message: format!("parameter {} {}", param.text, problem),
+                            location: param.location.clone(),
+                        });
+                    }
+
+                    self.bindings.push(param.text);
+                }
+
+                self.term(&x.value);
+                self.bindings.truncate(mark);
+            }
+            term => term.children().into_iter().for_each(|child| self.term(child)),
+        }
+    }
+}
+
+/// Parameters sharing a name with another one of the same function, or with
+/// a binding of an enclosing scope the body can then no longer read.
+pub fn parameters(term: &Term) -> Vec<Warning> {
+    let mut parameters = Parameters::default();
+
+    parameters.term(term);
+    parameters.warnings.sort_by_key(|warning| warning.location.start);
+
+    parameters.warnings
+}
+
+/// Whether evaluating `term` always calls `name` again, with `params` as the
+/// arguments in order and unchanged, before it gives a value.
+fn repeats(term: &Term, name: Symbol, params: &[Symbol]) -> bool {
+    let again = |term: &Term| repeats(term, name, params);
+    let rebinds = |bound: Symbol| bound == name || params.contains(&bound);
+
+    match term {
+        Term::Call(x) | Term::Recur(x) => {
+            let same = matches!(&*x.callee, Term::Var(f) if f.text == name)
+                && x.named.is_empty()
+                && x.arguments.len() == params.len()
+                && x.arguments.iter().zip(params).all(|(arg, param)| matches!(arg, Term::Var(x) if x.text == *param));
+
+            same || term.children().into_iter().any(again)
+        }
+        // only one branch runs
+        Term::If(x) => again(&x.condition) || (again(&x.then) && again(&x.otherwise)),
+        Term::Let(x) => match &x.pattern {
+            Some(pattern) => again(&x.value) || (!pattern.names().iter().any(|n| rebinds(n.text)) && again(&x.next)),
+            // visible in its own value
+            None => !rebinds(x.name.text) && (again(&x.value) || again(&x.next)),
+        },
+        // bodies and defaults only run when called
+        Term::Function(_) => false,
+        term => term.children().into_iter().any(again),
+    }
+}
+
+fn loops(term: &Term, warnings: &mut Vec<Warning>) {
+    if let Term::Let(x) = term {
+        if let (None, Term::Function(f)) = (&x.pattern, &*x.value) {
+            let params: Vec<Symbol> = f.parameters.iter().map(|param| param.text).collect();
+            // a rest parameter is given a new tuple on every call
+            let plain = f.parameters.iter().all(|param| !param.rest) && !params.contains(&x.name.text);
+
+            if plain && repeats(&f.value, x.name.text, &params) {
+                warnings.push(Warning {
+                    message: format!("function {} always calls itself with the same arguments", x.name.text),
+                    location: x.name.location.clone(),
+                });
+            }
+        }
+    }
+
+    term.children().into_iter().for_each(|child| loops(child, warnings));
+}
+
+/// Functions bound by `let` that call themselves with their own parameters,
+/// unchanged, whatever branch they take, so a call never returns. Only the
+/// obvious cases are caught, a function missing here may loop all the same.
+pub fn nontermination(term: &Term) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    loops(term, &mut warnings);
+    warnings.sort_by_key(|warning| warning.location.start);
+
+    warnings
+}
+
+/// Every warning of this module, sorted by location.
+pub fn warnings(term: &Term) -> Vec<Warning> {
+    let mut warnings = unused(term);
+
+    warnings.extend(parameters(term));
+    warnings.sort_by_key(|warning| warning.location.start);
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn lint(source: &str) -> Vec<String> {
+        let prog = parse(source, "test").unwrap();
+
+        unused(&prog.expression).into_iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn bindings() {
+        let warnings = lint("let x = 1; let y = 2; let _ = print(x); let f = fn (a, b) => { a }; 0");
+
+        assert_eq!(
+            warnings,
+            [
+                "test:15:16: warning: binding y is never read",
+                "test:44:45: warning: binding f is never read",
+                "test:55:56: warning: parameter b is never read",
+            ]
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        assert_eq!(lint("let f = fn (n) => { if (n == 0) { 0 } else { f(n - 1) } }; f(3)"), [] as [String; 0]);
+        assert_eq!(
+            lint("let f = fn (n) => { f(n) }; 0"),
+            ["test:4:5: warning: binding f is never read"]
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        let prog = parse("let x = 1; let f = fn (a, x, a) => { fn (b, a) => { a + b + x } }; f(1, 2, 3)(4, 5)", "test")
+            .unwrap();
+        let warnings: Vec<String> = super::parameters(&prog.expression).iter().map(|w| w.to_string()).collect();
+
+        assert_eq!(
+            warnings,
+            [
+                "test:26:27: warning: parameter x shadows a binding the function could capture",
+                "test:29:30: warning: parameter a is declared twice, the last one wins",
+                "test:44:45: warning: parameter a shadows a binding the function could capture",
+            ]
+        );
+    }
+
+    #[test]
+    fn nontermination() {
+        let source = "let f = fn (a, b) => { if (a == 0) { f(a, b) } else { print(f(a, b)) } }; \
+                      let g = fn (n) => { if (n == 0) { 0 } else { g(n) } }; \
+                      let h = fn (a, b) => { let a = a - 1; h(a, b) }; \
+                      let k = fn () => { let _ = fn () => { 0 }; k() }; 0";
+        let prog = parse(source, "test").unwrap();
+        let warnings: Vec<String> = super::nontermination(&prog.expression).iter().map(|w| w.to_string()).collect();
+
+        assert_eq!(
+            warnings,
+            [
+                "test:4:5: warning: function f always calls itself with the same arguments",
+                "test:182:183: warning: function k always calls itself with the same arguments",
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        assert_eq!(
+            lint("let x = 1; let (x, y) = (2, 3); x + y"),
+            ["test:4:5: warning: binding x is never read"]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! `rinha!`, rinha source written inline in Rust.
+
+/// The `Term` of the rinha program written between the braces, parsed when
+/// the expression runs, e.g. `rinha! { let x = 1; print(x + 2) }`. Tokens go
+/// through the Rust tokenizer first, so the program must be made of Rust
+/// tokens: brackets balanced, strings in double quotes, and comments are
+/// dropped. Locations are offsets into the program as Rust spells it back
+/// and the filename is `rinha!`.
+///
+/// Panics with the rendered error when the program does not parse.
+#[macro_export]
+macro_rules! rinha {
+    ($($source:tt)*) => {{
+        let source = stringify!($($source)*);
+
+        match $crate::parser::parse(source, "rinha!") {
+            Ok(prog) => prog.expression,
+            Err(err) => panic!("{}", err.render(source)),
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{canonical::normalize, parser::parse};
+
+    #[test]
+    fn rinha() {
+        let term = rinha! {
+            // dropped by the Rust tokenizer
+            let f = fn (n, ...rest) => { n |> g >> h };
+            print(f(1) <= 2 && "a\"b" != "")
+        };
+
+        let source = r#"let f = fn (n, ...rest) => { n |> g >> h }; print(f(1) <= 2 && "a\"b" != "")"#;
+
+        // locations depend on how Rust spells the tokens back
+        assert_eq!(normalize(&term), normalize(&parse(source, "test").unwrap().expression));
+    }
+
+    #[test]
+    #[should_panic(expected = "rinha!:1:5: Unexpected")]
+    fn malformed() {
+        rinha! { let = 1 };
+    }
+}
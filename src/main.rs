@@ -1,898 +1,1257 @@
-use core::fmt;
-use num_bigint::BigInt;
-use std::{collections::HashMap, fs, path::Path, rc::Rc, cell::RefCell, thread, sync::{mpsc, Mutex, Arc, RwLock}};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use rinha::{
+    ast::{Location, Position, Term},
+    cache::{self, Cache},
+    color::{ColorChoice, Palette},
+    config::Config,
+    default_prelude,
+    diagnostic::{self, Diagnostic},
+    diff, difftest,
+    encoding::Encoding,
+    format,
+    host::{Buffered, Flush, StdHost},
+    ir,
+    lint::{self, Warning},
+    memory, rast,
+    repl::{self, Reply, Session},
+    rpc, spec, stats,
+    summary::Summary,
+    trace::{Trace, Tracer},
+    CancellationToken, Context, Error, ErrorKind, Interpreter, Lint, Profile, Semantics,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+#[global_allocator]
+static ALLOCATOR: memory::Counting = memory::Counting::new();
+
+#[derive(Parser)]
+#[command(version, about = "Interpreter for the rinha language", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Program AST to evaluate, as JSON, YAML, MessagePack or precompiled
+    #[arg(default_value = "/var/rinha/source.rinha.json")]
+    file: PathBuf,
+
+    /// Read FILE as a json, yaml, msgpack or precompiled (rast) AST, as its extension tells by default, JSON otherwise
+    #[arg(long, value_name = "FORMAT")]
+    format: Option<Encoding>,
+
+    /// Serve newline-delimited JSON-RPC requests (eval, check, format) on stdio
+    #[arg(long, conflicts_with = "file")]
+    rpc: bool,
+
+    /// Program whose top-level bindings are evaluated before FILE, can be repeated
+    #[arg(long, value_name = "PATH", global = true)]
+    prelude: Vec<PathBuf>,
+
+    /// Do not load the built-in prelude (not, compose, max, min)
+    #[arg(long, global = true)]
+    no_prelude: bool,
+
+    /// Shared library registering extra builtins, can be repeated
+    #[cfg(feature = "plugins")]
+    #[arg(long, value_name = "PATH", global = true)]
+    plugin: Vec<PathBuf>,
+
+    /// Let the program read environment variables through getenv
+    #[arg(long, global = true)]
+    allow_env: bool,
+
+    /// Print which parts of FILE never ran and write per-node counts as JSON to PATH
+    #[arg(long, value_name = "PATH")]
+    coverage: Option<PathBuf>,
+
+    /// Print the N most evaluated expressions of the run (10 by default)
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    hot: Option<usize>,
+
+    /// Print what the run did, as text or JSON: evaluations, calls, call depth, cache hits, collections, time per phase
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "text", value_parser = ["text", "json"])]
+    stats: Option<String>,
+
+    /// Write the closure calls of the run to PATH as a Chrome trace, or as folded stacks when PATH ends in .folded
+    #[arg(long, value_name = "PATH")]
+    trace_out: Option<PathBuf>,
+
+    /// Print the peak heap usage of the run and how many values of each kind it created
+    #[arg(long)]
+    mem_profile: bool,
+
+    /// Evaluate every call to fib with the generic evaluator instead of natively
+    #[arg(long, global = true)]
+    no_intrinsics: bool,
+
+    /// Always take the generic path instead of the fast paths hot operators and call sites switch to
+    #[arg(long, global = true)]
+    no_specialize: bool,
+
+    /// Follow the rinha spec to the letter (strict) or with this crate's additions (extended, the default)
+    #[arg(long, value_name = "PROFILE", global = true)]
+    profile: Option<Profile>,
+
+    /// Let if conditions be ints, strings or any other value, false, 0 and "" counting as false
+    #[arg(long, global = true)]
+    truthy: bool,
+
+    /// Calling a closure with fewer arguments than parameters gives a closure taking the rest
+    #[arg(long, global = true)]
+    curry: bool,
+
+    /// Warn on stderr when the program relies on LINT, e.g. coercion of ints added to strings
+    #[arg(long = "warn", value_name = "LINT", value_parser = ["coercion", "overflow", "duplicate-parameters"], global = true)]
+    warn: Vec<String>,
+
+    /// Stop the program with an error when it relies on LINT
+    #[arg(short = 'D', value_name = "LINT", value_parser = ["coercion", "overflow", "duplicate-parameters"], global = true)]
+    deny: Vec<String>,
+
+    /// Report where int arithmetic leaves the 64-bit range, same as --warn overflow
+    #[arg(long, global = true)]
+    checked: bool,
+
+    /// Reuse the output of an earlier run of the same pure program, kept in DIR
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Evaluate let bindings and closure arguments only when they are first read
+    #[arg(long)]
+    lazy: bool,
+
+    /// Run the program even when it reads variables nothing declares, stopping at the first one reached
+    #[arg(long)]
+    no_resolve: bool,
+
+    /// Warn before running, or with lint, about functions that obviously never return
+    #[arg(long, global = true)]
+    termination: bool,
+
+    /// Write what the program prints after every line, in blocks or at exit (lines on a terminal, blocks otherwise)
+    #[arg(long, value_name = "WHEN", value_parser = ["line", "block", "exit"])]
+    flush: Option<String>,
+
+    /// Color errors, warnings, prompts and reports on terminals unless NO_COLOR is set (auto), always or never
+    #[arg(long, value_name = "WHEN", default_value = "auto", global = true)]
+    color: ColorChoice,
+
+    /// Write errors and warnings as text, as JSON lines on stderr, or as one SARIF log on stderr at the end
+    #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = ["text", "json", "sarif"], global = true)]
+    diagnostics_format: String,
+
+    /// Log what the interpreter does to stderr, repeat for more detail (-vv)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Do not log anything, not even warnings
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
+    quiet: bool,
+
+    /// Arguments for the program, read with argc() and argv(i)
+    #[arg(last = true)]
+    args: Vec<String>,
+}
 
-mod ast;
-mod fib;
+#[derive(Subcommand)]
+enum Command {
+    /// Run the `test_*` functions of every spec file in a directory
+    Test {
+        /// Directory holding .rinha or .json spec files
+        #[arg(long, value_name = "DIR")]
+        spec: PathBuf,
+    },
+    /// Report errors found without running the program, and let bindings and
+    /// parameters that are never read
+    Lint {
+        /// Program to check, a JSON AST or rinha source
+        file: PathBuf,
+    },
+    /// Show where two programs differ structurally, ignoring layout and locations
+    Diff {
+        /// Program to compare from, a JSON AST or rinha source
+        a: PathBuf,
+        /// Program to compare to
+        b: PathBuf,
+        /// Also report nodes that moved in the source
+        #[arg(long)]
+        locations: bool,
+    },
+    /// Print node counts per kind, nesting depth, variables and closures of a program
+    Stats {
+        /// Program to measure, a JSON AST or rinha source
+        file: PathBuf,
+    },
+    /// Print the A-normal form of a program, each intermediate value bound to a name
+    Ir {
+        /// Program to lower, a JSON AST or rinha source
+        file: PathBuf,
+    },
+    /// Run programs with this interpreter and a reference one, reporting those whose stdout or exit status differ
+    Difftest {
+        /// Programs to run, or directories holding them
+        #[arg(required = true)]
+        programs: Vec<PathBuf>,
+        /// Reference interpreter, run with the path of a JSON AST as its last argument
+        #[arg(long, value_name = "PATH")]
+        reference: PathBuf,
+        /// Argument given to the reference interpreter before the path, can be repeated
+        #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+        reference_arg: Vec<String>,
+        /// Seconds a run may take before it is killed and counted as timed out
+        #[arg(long, value_name = "SECS", default_value = "10")]
+        timeout: u64,
+        /// Most candidates tried while shrinking a diverging program
+        #[arg(long, value_name = "N", default_value = "500")]
+        budget: usize,
+    },
+    /// Print the JSON AST of a program, e.g. to regenerate a fixture from its rinha source
+    ToJson {
+        /// Program to convert, rinha source or an AST in another format
+        file: PathBuf,
+        /// Write the AST to PATH instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Write a program as a precompiled AST, which loads much faster than JSON
+    Precompile {
+        /// Program to precompile, a JSON, YAML or MessagePack AST or rinha source
+        file: PathBuf,
+        /// Where to write it, FILE with the .rast extension by default
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Evaluate rinha source as it is typed, each entry keeping its bindings for the next ones
+    Repl,
+    /// Print the completion script of a shell for the subcommands and flags
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
 
-use ast::*;
-use fib::*;
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Level {
+    Warn = 1,
+    Info,
+    Debug,
+}
 
-#[derive(Debug, Clone)]
-struct Error {
-    pub start: usize,
-    pub end: usize,
-    pub filename: String,
-    pub message: String,
+/// Diagnostics written to stderr as `level=.. msg=".." key=value` lines, kept
+/// apart from the program output on stdout.
+#[derive(Clone, Copy)]
+struct Log {
+    verbosity: u8,
 }
 
-impl Error {
-    pub fn new(message: &str, location: Location) -> Self {
+impl Log {
+    fn new(cli: &Cli) -> Self {
         Self {
-            start: location.start,
-            end: location.end,
-            filename: location.filename,
-            message: String::from(message),
+            verbosity: if cli.quiet { 0 } else { Level::Warn as u8 + cli.verbose },
         }
     }
+
+    fn emit(&self, level: Level, msg: &str, fields: &[(&str, &dyn fmt::Display)]) {
+        if level as u8 > self.verbosity {
+            return;
+        }
+
+        let name = match level {
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+        let mut line = format!("level={} msg={:?}", name, msg);
+
+        for (key, value) in fields {
+            let value = value.to_string();
+
+            if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+                line.push_str(&format!(" {}={:?}", key, value));
+            } else {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+        }
+
+        eprintln!("{}", line);
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Context {
-    outter: Option<Box<Context>>,
-    inner: HashMap<String, Output>,
+fn millis(start: Instant) -> String {
+    format!("{:.3}", start.elapsed().as_secs_f64() * 1000.0)
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Closure {
-    pub body: Term,
-    pub args: Vec<Parameter>,
-    pub context: Rc<RefCell<Context>>,
-    // pub context: Arc<RwLock<Context>>,
+/// Text of the file at `filename`, empty when it cannot be read so offsets
+/// are printed as they are.
+fn source(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_default()
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Output {
-    Bool(bool),
-    Int(BigInt),
-    Str(String),
-    Tuple((Box<Output>, Box<Output>)),
-    Closure(Closure),
-    Void,
+/// Colors of stdout and stderr, each depending on whether it is a terminal.
+struct Colors {
+    out: Palette,
+    err: Palette,
 }
 
-impl fmt::Display for Output {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Output::Bool(x) => write!(f, "{}", x),
-            Output::Int(x) => write!(f, "{}", x),
-            Output::Str(x) => write!(f, "{}", x),
-            _ => Ok(()),
-        }
+static COLORS: OnceLock<Colors> = OnceLock::new();
+
+fn colors() -> &'static Colors {
+    COLORS.get_or_init(|| Colors {
+        out: Palette::PLAIN,
+        err: Palette::PLAIN,
+    })
+}
+
+/// Error with the line and column it starts at, when its file is at hand,
+/// for stdout.
+fn report(err: &Error) -> String {
+    colors().out.error(&err.render(&source(&err.filename)))
+}
+
+fn warning(warning: &Warning, palette: Palette) -> String {
+    palette.warning(&warning.render(&source(&warning.location.filename)))
+}
+
+/// Diagnostics held back for the SARIF log.
+static PENDING: Mutex<Vec<Diagnostic>> = Mutex::new(Vec::new());
+
+/// Writes the SARIF log of every diagnostic reported once dropped, at the
+/// end of `main`, an empty one when there is none.
+struct Sarif;
+
+impl Drop for Sarif {
+    fn drop(&mut self) {
+        let pending = PENDING.lock().unwrap_or_else(|err| err.into_inner());
+
+        eprintln!("{}", diagnostic::sarif(&pending, source));
     }
 }
 
-/*
-type Job = Box<dyn FnOnce() -> Output + Send + 'static>;
+/// Reports `diagnostic` as `--diagnostics-format` says, `text` being how it
+/// is written as text.
+fn diagnose(cli: &Cli, diagnostic: Diagnostic, text: impl FnOnce()) {
+    match cli.diagnostics_format.as_str() {
+        "json" => eprintln!("{}", diagnostic.json(source)),
+        "sarif" => PENDING.lock().unwrap_or_else(|err| err.into_inner()).push(diagnostic),
+        _ => text(),
+    }
+}
 
-struct ThreadPool {
-    workers: Vec<Worker>,
-    tx: mpsc::Sender<Job>,
+fn diagnose_error(cli: &Cli, err: &Error) {
+    diagnose(cli, Diagnostic::from(err), || println!("{}", report(err)));
 }
 
-impl ThreadPool {
-    pub fn new(size: usize) -> Self {
-        assert!(size > 0);
+/// Most calls an interrupted program reports, innermost first.
+const BACKTRACE: usize = 16;
 
-        let (tx, rx) = mpsc::channel();
-        let rx = Arc::new(Mutex::new(rx));
-        let mut workers = Vec::with_capacity(size);
+/// Where Ctrl-C stopped the program, then where the calls still running were
+/// made.
+fn interrupted(err: &Error) -> String {
+    let mut sources = HashMap::new();
+    let mut at = |filename: &str, start: usize| {
+        let source = sources.entry(filename.to_string()).or_insert_with(|| source(filename));
 
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&rx)))
+        match Position::of(source, start) {
+            Some(at) => format!("{}:{}:{}", filename, at.line, at.column),
+            None => format!("{}:{}", filename, start),
         }
+    };
+    let mut out = format!("Interrupted at {}\n", at(&err.filename, err.start));
+
+    for call in err.backtrace.iter().take(BACKTRACE) {
+        out += &format!("  called from {}\n", at(&call.filename, call.start));
+    }
 
-        Self { workers, tx }
+    if err.backtrace.len() > BACKTRACE {
+        out += &format!("  ... {} more calls\n", err.backtrace.len() - BACKTRACE);
     }
 
-    pub fn exec<F>(&self, f: F) 
-    where F: FnOnce() -> Output + Send + 'static {
-        let job = Box::new(f);
+    out
+}
+
+static INTERRUPT: OnceLock<CancellationToken> = OnceLock::new();
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    let Some(token) = INTERRUPT.get() else {
+        return;
+    };
 
-        self.tx.send(job).unwrap()
+    // a second Ctrl-C does not wait for the program to notice the first
+    if token.is_cancelled() {
+        unsafe { libc::_exit(130) };
     }
+
+    token.cancel();
 }
 
-struct Worker {
-    id: usize,
-    output: Output,
+/// Ctrl-C stops `interpreter` before its next term rather than killing the
+/// process, e.g. halfway through printing.
+fn interrupt_on_ctrl_c(interpreter: &mut Interpreter) {
+    let token = INTERRUPT.get_or_init(CancellationToken::new).clone();
+
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+
+    interpreter.set_cancellation(Some(token));
 }
 
-impl Worker {
-    fn new(id: usize, rx: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
-        let output = thread::spawn(move || {
-            while let Ok(job) = rx.lock().unwrap().recv() {
-                job();
-            }
-        });
+/// Fills in what was not given on the command line from the closest
+/// `rinha.toml`, starting at the working directory.
+fn configure(cli: &mut Cli, log: Log) -> Result<(), String> {
+    let dir = env::current_dir().map_err(|err| err.to_string())?;
+
+    let Some((path, config)) = Config::discover(&dir)? else {
+        return Ok(());
+    };
+
+    log.emit(Level::Debug, "loaded config", &[("path", &path.display())]);
 
-        Self { id, output }
+    if cli.prelude.is_empty() {
+        cli.prelude = config.prelude;
     }
+
+    cli.no_prelude |= config.no_prelude;
+    cli.allow_env |= config.allow_env;
+    cli.no_intrinsics |= config.intrinsics == Some(false);
+    cli.profile = cli.profile.or(config.profile);
+
+    Ok(())
 }
 
-const CPU: usize = 2;
-const POOL: ThreadPool = ThreadPool::new(CPU * 2);
-*/
+/// Semantics of the profile with `--truthy`, `--curry` and the levels of
+/// `--warn` and `-D` applied, denying wins.
+fn semantics(cli: &Cli) -> Semantics {
+    let mut semantics = Semantics::from(cli.profile.unwrap_or_default());
 
-fn eval(term: Term, context: &mut Context) -> Result<Output, Error> {
-    match term {
-        Term::Bool(x) => Ok(Output::Bool(x.value)),
-        Term::Int(x) => Ok(Output::Int(BigInt::from(x.value))),
-        Term::Str(x) => Ok(Output::Str(x.value)),
-        Term::Print(x) => {
-            let expr = eval(*x.value, context)?;
+    semantics.truthy_conditions = cli.truthy;
+    semantics.partial_application = cli.curry;
 
-            match expr {
-                Output::Bool(x) => println!("{}", x),
-                Output::Int(x) => println!("{}", x),
-                Output::Str(x) => println!("{}", x),
-                Output::Tuple(x) => println!("({}, {})", x.0, x.1),
-                Output::Closure(_) => println!("<#closure>"),
-                Output::Void => (),
-            };
+    if cli.checked {
+        semantics.overflow = Lint::Warn;
+    }
 
-            Ok(Output::Void)
-        }
-        Term::Binary(x) => {
-            // let lhs = POOL.exec(eval(*x.lhs, context));
-            // let rhs = POOL.exec(eval(*x.rhs, context));
-            let lhs = eval(*x.lhs, context)?;
-            let rhs = eval(*x.rhs, context)?;
-
-            match x.op {
-                BinaryOp::Add => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a + b)),
-                    (Output::Str(a), Output::Str(b)) => Ok(Output::Str(format!("{}{}", a, b))),
-                    (Output::Str(a), Output::Int(b)) => Ok(Output::Str(format!("{}{}", a, b))),
-                    (Output::Int(a), Output::Str(b)) => Ok(Output::Str(format!("{}{}", a, b))),
-                    _ => Err(Error::new("Cannot perform add operation", x.location)),
-                },
-                BinaryOp::Sub => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a - b)),
-                    _ => Err(Error::new("Cannot perform sub operation", x.location)),
-                },
-                BinaryOp::Mul => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Int(a * b)),
-                    _ => Err(Error::new("Cannot perform mul operation", x.location)),
-                },
-                BinaryOp::Div => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => {
-                        if b > BigInt::from(0) {
-                            Ok(Output::Int(a / b))
-                        } else {
-                            Err(Error::new("Arithmetic error, dividing by zero", x.location))
-                        }
-                    }
-                    _ => Err(Error::new("Cannot perform div operation", x.location)),
-                },
-                BinaryOp::Eq => match (lhs, rhs) {
-                    (a, b) => Ok(Output::Bool(a == b)),
-                },
-                BinaryOp::Neq => match (lhs, rhs) {
-                    (a, b) => Ok(Output::Bool(a != b)),
-                },
-                BinaryOp::Gt => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a > b)),
-                    (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a > b)),
-                    _ => Err(Error::new("Cannot perform gt operation", x.location)),
-                },
-                BinaryOp::Lt => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a < b)),
-                    (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a < b)),
-                    _ => Err(Error::new("Cannot perform lt operation", x.location)),
-                },
-                BinaryOp::Gte => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a >= b)),
-                    (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a >= b)),
-                    _ => Err(Error::new("Cannot perform gte operation", x.location)),
-                },
-                BinaryOp::Lte => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => Ok(Output::Bool(a <= b)),
-                    (Output::Str(a), Output::Str(b)) => Ok(Output::Bool(a <= b)),
-                    _ => Err(Error::new("Cannot perform lte operation", x.location)),
-                },
-                BinaryOp::Rem => match (lhs, rhs) {
-                    (Output::Int(a), Output::Int(b)) => {
-                        if b > BigInt::from(0) {
-                            Ok(Output::Int(a % b))
-                        } else {
-                            Err(Error::new("Arithmetic error, dividing by zero", x.location))
-                        }
-                    }
-                    _ => Err(Error::new("Cannot perform rem operation", x.location)),
-                },
-                BinaryOp::And => match (lhs, rhs) {
-                    (Output::Bool(false), _) => Ok(Output::Bool(false)),
-                    (_, b) => Ok(b),
-                },
-                BinaryOp::Or => match (lhs, rhs) {
-                    (Output::Bool(true), _) => Ok(Output::Bool(true)),
-                    (_, b) => Ok(b),
-                },
+    for (lints, level) in [(&cli.warn, Lint::Warn), (&cli.deny, Lint::Deny)] {
+        for lint in lints {
+            match lint.as_str() {
+                "coercion" => semantics.coercion = level,
+                "overflow" => semantics.overflow = level,
+                "duplicate-parameters" => semantics.duplicate_parameters = level,
+                _ => unreachable!("clap only accepts known lints"),
             }
         }
-        Term::If(x) => {
-            let cond = eval(*x.condition, context)?;
+    }
 
-            match cond {
-                Output::Bool(true) => eval(*x.then, context),
-                Output::Bool(false) => eval(*x.otherwise, context),
-                _ => Err(Error::new(
-                    "Condition expression not resolve to a boolean primitive",
-                    x.location,
-                )),
-            }
-        }
-        Term::Tuple(x) => {
-            // let _1st = POOL.exec(eval(*x.first, context));
-            // let _2nd = POOL.exec(eval(*x.second, context));
-            let _1st = eval(*x.first, context)?;
-            let _2nd = eval(*x.second, context)?;
+    semantics
+}
 
-            Ok(Output::Tuple((Box::new(_1st), Box::new(_2nd))))
-        }
-        Term::First(x) => {
-            let val = eval(*x.value, context)?;
+/// Interpreter with the preludes selected on the command line already
+/// evaluated into the returned scope.
+fn setup(cli: &Cli, log: Log) -> Result<(Interpreter, Context), Error> {
+    let stdout = match cli.flush.as_deref() {
+        Some("line") => Buffered::new(Flush::Line),
+        Some("block") => Buffered::new(Flush::Block),
+        Some(_) => Buffered::new(Flush::Exit),
+        None => Buffered::default(),
+    };
+    let mut interpreter = Interpreter::new(StdHost {
+        allow_env: cli.allow_env,
+        args: cli.args.clone(),
+        stdout,
+        ..StdHost::default()
+    });
+    let mut context = Context::default();
+
+    interpreter.set_intrinsics(!cli.no_intrinsics);
+    interpreter.set_specialization(!cli.no_specialize);
+    interpreter.set_semantics(semantics(cli));
+
+    #[cfg(feature = "plugins")]
+    for path in &cli.plugin {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
+        };
 
-            if let Output::Tuple(x) = val {
-                Ok(*x.0)
-            } else {
-                Err(Error::new(
-                    "Cannot access first of a non tuple argument",
-                    x.location,
-                ))
-            }
-        }
-        Term::Second(x) => {
-            let val = eval(*x.value, context)?;
+        // SAFETY: plugins are trusted by whoever passed them on the command line
+        unsafe { rinha::plugin::load(&mut interpreter, path) }
+            .map_err(|msg| Error::new(&format!("Cannot load plugin: {}", msg), location))?;
 
-            if let Output::Tuple(x) = val {
-                Ok(*x.1)
-            } else {
-                Err(Error::new(
-                    "Cannot access second of a non tuple argument",
-                    x.location,
-                ))
-            }
-        }
-        Term::Var(x) => {
-            if let Some(var) = context.inner.get(&x.text) {
-                return Ok(var.clone());
-            } 
+        log.emit(Level::Debug, "loaded plugin", &[("path", &path.display())]);
+    }
 
-            let mut ctx = &context.outter;
+    if !cli.no_prelude {
+        let start = Instant::now();
 
-            while let Some(outter) = ctx {
-                if let Some(var) = outter.inner.get(&x.text) {
-                    return Ok(var.clone());
-                }
+        interpreter.prelude(default_prelude(), &mut context)?;
 
-                ctx = &outter.outter;
-            }
+        log.emit(Level::Debug, "loaded prelude", &[("path", &"prelude.rinha"), ("ms", &millis(start))]);
+    }
 
-            let msg = format!("Variable {} is not declared", &x.text);
+    for path in &cli.prelude {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
+        };
 
-            Err(Error::new(msg.as_str(), x.location))
-        }
-        Term::Let(x) => {
-            let id = x.name.text;
-            let expr = eval(*x.value, context)?;
+        let start = Instant::now();
+        let prog = interpreter.load(path, &location)?;
 
-            match expr {
-                Output::Closure(y) => {
-                    let closure = Output::Closure(Closure {
-                        body: y.body,
-                        args: y.args,
-                        context: Rc::new(RefCell::new(context.clone())),
-                        // context: Arc::new(RwLock::new(context.clone())),
-                    });
+        interpreter.prelude(prog, &mut context)?;
 
-                    context.inner.insert(id, closure);
-                }
-                y => {
-                    context.inner.insert(id, y);
-                }
-            }
+        log.emit(Level::Debug, "loaded prelude", &[("path", &path.display()), ("ms", &millis(start))]);
+    }
 
-            eval(*x.next, context)
-        }
-        Term::Call(x) => {
-            let mut new_context = Context {
-                outter: Some(Box::new(context.clone())),
-                inner: HashMap::new(),
-            };
+    Ok((interpreter, context))
+}
 
-            if let Term::Var(z) = *x.callee.clone() {
-                if z.text == "fib" {
-                    if let Output::Int(nth) = eval(x.arguments[0].clone(), context)? {
-                        let res: BigInt;
+/// Cache key of running `expr` as configured, None when the result could
+/// depend on anything besides the program text.
+fn cache_key(cli: &Cli, expr: &Term) -> Option<String> {
+    // a plugin's builtins are not known to be pure
+    #[cfg(feature = "plugins")]
+    if !cli.plugin.is_empty() {
+        return None;
+    }
 
-                        if nth < BigInt::from(1000) {
-                            res = __fib_iter(nth);
-                        } else {
-                            res = __fib_matrix(nth);
-                        }
+    let mut preludes = Vec::new();
 
-                        return Ok(Output::Int(res));
-                    }
-                }
-            }
+    if !cli.no_prelude {
+        preludes.push(default_prelude().expression);
+    }
 
-            let func = eval(*x.callee, context)?;
+    for path in &cli.prelude {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
+        };
 
-            match func {
-                Output::Closure(y) => {
-                    if y.args.len() != x.arguments.len() {
-                        return Err(Error::new(
-                            "Arguments declaration differs parameters declaration",
-                            x.location,
-                        ));
-                    }
+        preludes.push(Interpreter::default().load(path, &location).ok()?.expression);
+    }
 
-                    for (param, arg) in y.args.into_iter().zip(x.arguments.clone()) {
-                        new_context.inner.insert(param.text, eval(arg, context)?);
-                    }
+    let programs: Vec<&Term> = preludes.iter().chain([expr]).collect();
 
-                    eval(y.body, &mut new_context)
-                }
-                _ => Err(Error::new("Calling a not callable", x.location)),
-            }
-        }
-        Term::Function(x) => Ok(Output::Closure(Closure {
-            body: *x.value,
-            args: x.parameters,
-            // @@@
-            context: Rc::new(RefCell::new(context.clone())),
-            // context: Arc::new(RwLock::new(context.clone())),
-        })),
+    if !programs.iter().all(|term| cache::pure(term)) {
+        return None;
     }
-}
 
-fn read_json(path: &str) -> File {
-    let prog = fs::read_to_string(Path::new(path)).expect("Cannot read the program file");
+    let flags = format!(
+        "version={} lazy={} intrinsics={} semantics={:?}",
+        env!("CARGO_PKG_VERSION"),
+        cli.lazy,
+        !cli.no_intrinsics,
+        semantics(cli)
+    );
 
-    serde_json::from_str::<File>(&prog).unwrap()
+    Some(cache::key(&programs, &flags))
 }
 
-fn main() {
-    let prog = read_json("/var/rinha/source.rinha.json");
-    let expr = prog.expression;
+fn test(cli: &Cli, log: Log, dir: &Path) -> bool {
+    let files = match spec::files(dir) {
+        Ok(files) => files,
+        Err(err) => {
+            println!("Cannot read {}: {}", dir.display(), err);
 
-    let mut context = Context {
-        outter: None,
-        inner: HashMap::new(),
+            return false;
+        }
     };
 
-    eval(expr, &mut context).unwrap_or_else(|err| {
-        // FONT: lineno == start and column == end, see: '[0]
-        //
-        // '[0]: <https://www.gnu.org/prep/standards/standards.html#Errors>
-        println!(
-            "{}:{}:{}: {}",
-            err.filename, err.start, err.end, err.message
+    let (mut passed, mut failed) = (0, 0);
+
+    for path in files {
+        let start = Instant::now();
+        let outcomes = match spec::run(&path, || setup(cli, log)) {
+            Ok(outcomes) => outcomes,
+            Err(err) => {
+                println!("{}", report(&err));
+                failed += 1;
+
+                continue;
+            }
+        };
+
+        log.emit(
+            Level::Info,
+            "ran spec",
+            &[("path", &path.display()), ("cases", &outcomes.len()), ("ms", &millis(start))],
         );
 
-        Output::Void
-    });
+        for outcome in outcomes {
+            match outcome.result {
+                Ok(()) => {
+                    println!("PASS {} {}", path.display(), outcome.name);
+                    passed += 1;
+                }
+                Err(err) => {
+                    println!("FAIL {} {}", path.display(), outcome.name);
+                    println!("  {}", report(&err));
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+
+    failed == 0
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Prints the errors and warnings for the program at `path`, false when there
+/// is any. Names from the preludes and builtins count as declared.
+fn lint(cli: &Cli, log: Log, path: &Path) -> bool {
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
 
-    #[test]
-    fn fib() {
-        let prog = read_json("./json/fib.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let prog = setup(cli, log).and_then(|(mut interpreter, context)| {
+        let prog = interpreter.load(path, &location)?;
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        Ok((interpreter.check(&prog.expression, &context), prog))
+    });
 
-        assert_eq!(res, Output::Int(BigInt::from(55)));
-    }
+    let (errors, prog) = match prog {
+        Ok(prog) => prog,
+        Err(err) => {
+            diagnose_error(cli, &err);
 
-    #[test]
-    fn print() {
-        let prog = read_json("./json/print.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+            return false;
+        }
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    let mut warnings = lint::warnings(&prog.expression);
 
-        assert_eq!(res, Output::Void);
+    if cli.termination {
+        warnings.extend(lint::nontermination(&prog.expression));
+        warnings.sort_by_key(|warning| warning.location.start);
     }
 
-    #[test]
-    fn add1() {
-        let prog = read_json("./json/add1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
-
-        let res = eval(prog.expression, &mut context).unwrap();
+    for err in &errors {
+        diagnose_error(cli, err);
+    }
 
-        assert_eq!(res, Output::Int(BigInt::from(3)));
+    for warning in &warnings {
+        diagnose(cli, Diagnostic::from(warning), || println!("{}", self::warning(warning, colors().out)));
     }
 
-    #[test]
-    fn add2() {
-        let prog = read_json("./json/add2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    errors.is_empty() && warnings.is_empty()
+}
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+/// Where the REPL keeps the entries of past sessions, `RINHA_HISTORY` or
+/// `~/.rinha_history`.
+fn history() -> Option<PathBuf> {
+    env::var_os("RINHA_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".rinha_history")))
+}
 
-        assert_eq!(res.message, "Cannot perform add operation");
-    }
+/// Reads entries until `:quit` or Ctrl-D, an entry going on for as many lines
+/// as it leaves brackets open. Ctrl-C drops the entry being typed.
+fn read_eval_print(cli: &Cli, log: Log) -> bool {
+    let (mut interpreter, context) = match setup(cli, log) {
+        Ok(setup) => setup,
+        Err(err) => {
+            println!("{}", report(&err));
 
-    #[test]
-    fn concat1() {
-        let prog = read_json("./json/concat1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+            return false;
+        }
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    interpreter.set_lazy(cli.lazy);
+    interrupt_on_ctrl_c(&mut interpreter);
 
-        assert_eq!(res, Output::Str(String::from("1abc")));
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Cannot read from the terminal: {}", err);
+
+            return false;
+        }
+    };
+    let history = history();
+
+    // there is none on the first run
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
     }
 
-    #[test]
-    fn concat2() {
-        let prog = read_json("./json/concat2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let mut session = Session::new(interpreter, context);
+    let mut entry = String::new();
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    loop {
+        let prompt = if entry.is_empty() { "rinha> " } else { "  ...> " };
 
-        assert_eq!(res, Output::Str(String::from("abc1")));
-    }
+        match editor.readline(&colors().out.prompt(prompt)) {
+            Ok(line) => {
+                entry.push_str(&line);
+                entry.push('\n');
 
-    #[test]
-    fn concat3() {
-        let prog = read_json("./json/concat3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+                if repl::incomplete(&entry) {
+                    continue;
+                }
 
-        let res = eval(prog.expression, &mut context).unwrap();
+                let _ = editor.add_history_entry(entry.trim_end());
+                let reply = session.entry(&entry);
 
-        assert_eq!(res, Output::Str(String::from("abcdef")));
-    }
+                entry.clear();
+                session.interpreter_mut().host_mut().stdout.flush();
 
-    #[test]
-    fn sub1() {
-        let prog = read_json("./json/sub1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+                // Ctrl-C stopped that entry, not the session
+                if let Some(token) = INTERRUPT.get() {
+                    token.reset();
+                }
 
-        let res = eval(prog.expression, &mut context).unwrap();
+                match reply {
+                    Reply::Value(text) => println!("{}", text),
+                    Reply::Error(text) => println!("{}", colors().out.error(&text)),
+                    Reply::Done => (),
+                    Reply::Quit => break,
+                }
+            }
+            Err(ReadlineError::Interrupted) => entry.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Cannot read from the terminal: {}", err);
 
-        assert_eq!(res, Output::Int(BigInt::from(8)));
+                return false;
+            }
+        }
     }
 
-    #[test]
-    fn sub2() {
-        let prog = read_json("./json/sub2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+    if let Some(path) = &history {
+        if let Err(err) = editor.save_history(path) {
+            log.emit(Level::Warn, "cannot write history", &[("path", &path.display()), ("error", &err)]);
+        }
+    }
+
+    true
+}
+
+/// Prints the changes from `a` to `b`, true when there is none.
+fn diff(a: &Path, b: &Path, locations: bool) -> bool {
+    let load = |path: &Path| {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
         };
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+        Interpreter::default().load(path, &location)
+    };
 
-        assert_eq!(res.message, "Cannot perform sub operation");
-    }
+    let (a, b) = match (load(a), load(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(err), _) | (_, Err(err)) => {
+            println!("{}", report(&err));
 
-    #[test]
-    fn mul1() {
-        let prog = read_json("./json/mul1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+            return false;
+        }
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    let changes = diff::diff(&a.expression, &b.expression, locations);
 
-        assert_eq!(res, Output::Int(BigInt::from(4)));
+    for change in &changes {
+        print!("{}", change);
     }
 
-    #[test]
-    fn mul2() {
-        let prog = read_json("./json/mul2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    changes.is_empty()
+}
+
+fn stats(path: &Path) -> bool {
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    match Interpreter::default().load(path, &location) {
+        Ok(prog) => {
+            print!("{}", stats::stats(&prog.expression));
 
-        assert_eq!(res.message, "Cannot perform mul operation");
+            true
+        }
+        Err(err) => {
+            println!("{}", report(&err));
+
+            false
+        }
     }
+}
 
-    #[test]
-    fn div1() {
-        let prog = read_json("./json/div1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+fn ir(path: &Path) -> bool {
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    match Interpreter::default().load(path, &location) {
+        Ok(prog) => {
+            print!("{}", ir::lower(&prog.expression));
+
+            true
+        }
+        Err(err) => {
+            println!("{}", report(&err));
 
-        assert_eq!(res, Output::Int(BigInt::from(5)));
+            false
+        }
     }
+}
 
-    #[test]
-    fn div2() {
-        let prog = read_json("./json/div2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+/// Command running this interpreter as `difftest` compares it, with the
+/// options telling what the language is.
+fn ours(cli: &Cli) -> Vec<String> {
+    let exe = env::current_exe().map_or_else(|_| String::from("interpreter"), |exe| exe.display().to_string());
+    let mut command = vec![exe, String::from("--color=never")];
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    match cli.profile {
+        Some(Profile::Strict) => command.push(String::from("--profile=strict")),
+        Some(Profile::Extended) => command.push(String::from("--profile=extended")),
+        None => (),
+    }
 
-        assert_eq!(res.message, "Arithmetic error, dividing by zero");
+    for (flag, set) in [("--no-prelude", cli.no_prelude), ("--truthy", cli.truthy), ("--curry", cli.curry)] {
+        if set {
+            command.push(String::from(flag));
+        }
     }
 
-    #[test]
-    fn div3() {
-        let prog = read_json("./json/div3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    command
+}
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+fn run_difftest(harness: &difftest::Harness, paths: &[PathBuf]) -> bool {
+    let files = match difftest::programs(paths) {
+        Ok(files) => files,
+        Err(err) => {
+            println!("Cannot list programs: {}", err);
 
-        assert_eq!(res.message, "Cannot perform div operation");
-    }
+            return false;
+        }
+    };
 
-    #[test]
-    fn eq1() {
-        let prog = read_json("./json/eq1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+    let (mut same, mut diverged) = (0, 0);
+
+    for path in files {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
         };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        let prog = match Interpreter::default().load(&path, &location) {
+            Ok(prog) => prog,
+            Err(err) => {
+                println!("{}", report(&err));
+                diverged += 1;
 
-        assert_eq!(res, Output::Bool(true));
-    }
+                continue;
+            }
+        };
 
-    #[test]
-    fn eq2() {
-        let prog = read_json("./json/eq2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+        // JSON ASTs run where they are, so their imports are found.
+        let compared = match Encoding::of(&path) {
+            Some(Encoding::Json) => harness.compare(&path),
+            _ => harness.compare_file(&prog),
         };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        let divergence = match compared {
+            Ok(None) => {
+                println!("SAME {}", path.display());
+                same += 1;
 
-        assert_eq!(res, Output::Bool(false));
-    }
+                continue;
+            }
+            Ok(Some(divergence)) => divergence,
+            Err(err) => {
+                println!("Cannot run {}: {}", path.display(), err);
+                diverged += 1;
 
-    #[test]
-    fn neq1() {
-        let prog = read_json("./json/neq1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+                continue;
+            }
         };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        diverged += 1;
 
-        assert_eq!(res, Output::Bool(true));
-    }
+        println!("DIFF {}", path.display());
+        println!("  ours: {}", divergence.ours);
+        println!("  reference: {}", divergence.reference);
 
-    #[test]
-    fn neq2() {
-        let prog = read_json("./json/neq2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+        match harness.minimize(&prog, &divergence) {
+            Ok((smallest, divergence)) => {
+                println!("  minimized:");
 
-        let res = eval(prog.expression, &mut context).unwrap();
+                for line in format::format(&smallest.expression).lines() {
+                    println!("    {}", line);
+                }
 
-        assert_eq!(res, Output::Bool(false));
+                println!("  ours: {}", divergence.ours);
+                println!("  reference: {}", divergence.reference);
+            }
+            Err(err) => println!("  Cannot minimize: {}", err),
+        }
     }
 
-    #[test]
-    fn gt1() {
-        let prog = read_json("./json/gt1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    println!("{} same, {} diverged", same, diverged);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    diverged == 0
+}
 
-        assert_eq!(res, Output::Bool(true));
-    }
+fn to_json(path: &Path, output: Option<&Path>) -> bool {
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
 
-    #[test]
-    fn gt2() {
-        let prog = read_json("./json/gt2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let res = Interpreter::default().load(path, &location).and_then(|prog| {
+        let json = serde_json::to_string_pretty(&prog).map_err(|err| {
+            let msg = format!("Cannot convert {}: {}", path.display(), err);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+            Error::new(msg.as_str(), location.clone())
+        })? + "\n";
 
-        assert_eq!(res, Output::Bool(true));
-    }
+        match output {
+            Some(output) => fs::write(output, json).map_err(|err| {
+                let msg = format!("Cannot write {}: {}", output.display(), err);
 
-    #[test]
-    fn gt3() {
-        let prog = read_json("./json/gt3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+                Error::new(msg.as_str(), location.clone())
+            }),
+            None => {
+                print!("{}", json);
+
+                Ok(())
+            }
+        }
+    });
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    match res {
+        Ok(()) => true,
+        Err(err) => {
+            println!("{}", report(&err));
 
-        assert_eq!(res.message, "Cannot perform gt operation");
+            false
+        }
     }
+}
 
-    #[test]
-    fn gt4() {
-        let prog = read_json("./json/gt4.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+fn precompile(path: &Path, output: Option<&Path>) -> bool {
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
+    let output = output.map_or_else(|| path.with_extension("rast"), Path::to_path_buf);
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    let res = Interpreter::default().load(path, &location).and_then(|prog| {
+        fs::write(&output, rast::encode(&prog)).map_err(|err| {
+            let msg = format!("Cannot write {}: {}", output.display(), err);
 
-        assert_eq!(res.message, "Cannot perform gt operation");
-    }
+            Error::new(msg.as_str(), location.clone())
+        })
+    });
 
-    #[test]
-    fn rem1() {
-        let prog = read_json("./json/rem1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    match res {
+        Ok(()) => true,
+        Err(err) => {
+            println!("{}", report(&err));
+
+            false
+        }
+    }
+}
 
-        let res = eval(prog.expression, &mut context).unwrap();
+fn main() {
+    let mut cli = Cli::parse();
 
-        assert_eq!(res, Output::Int(BigInt::from(0)));
+    if cli.mem_profile {
+        ALLOCATOR.enable();
     }
 
-    #[test]
-    fn rem2() {
-        let prog = read_json("./json/rem2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let log = Log::new(&cli);
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    let _ = COLORS.set(Colors {
+        out: Palette::new(cli.color, io::stdout().is_terminal(), no_color),
+        err: Palette::new(cli.color, io::stderr().is_terminal(), no_color),
+    });
 
-        assert_eq!(res.message, "Cannot perform rem operation");
-    }
+    // a broken rinha.toml in the working directory does not matter here
+    if let Some(Command::Completions { shell }) = cli.command {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        let mut script = Vec::new();
 
-    #[test]
-    fn and1() {
-        let prog = read_json("./json/and1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+        clap_complete::generate(shell, &mut command, name, &mut script);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        // e.g. piped into `head`
+        let _ = io::stdout().write_all(&script);
 
-        assert_eq!(res, Output::Int(BigInt::from(2)));
+        return;
     }
 
-    #[test]
-    fn and2() {
-        let prog = read_json("./json/and2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    if let Err(err) = configure(&mut cli, log) {
+        println!("{}", err);
+        process::exit(1);
+    }
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    // dropping a `Sarif` writes the log, only make one when it is wanted
+    let sarif = if cli.diagnostics_format == "sarif" { Some(Sarif) } else { None };
+    let passed = match &cli.command {
+        Some(Command::Test { spec }) => Some(test(&cli, log, spec)),
+        Some(Command::Lint { file }) => Some(lint(&cli, log, file)),
+        Some(Command::Diff { a, b, locations }) => Some(diff(a, b, *locations)),
+        Some(Command::Stats { file }) => Some(stats(file)),
+        Some(Command::Ir { file }) => Some(ir(file)),
+        Some(Command::Difftest {
+            programs,
+            reference,
+            reference_arg,
+            timeout,
+            budget,
+        }) => {
+            let mut command = vec![reference.display().to_string()];
+
+            command.extend(reference_arg.iter().cloned());
+
+            let harness = difftest::Harness {
+                ours: ours(&cli),
+                reference: command,
+                timeout: Duration::from_secs(*timeout),
+                budget: *budget,
+            };
 
-        assert_eq!(res, Output::Int(BigInt::from(5)));
-    }
+            Some(run_difftest(&harness, programs))
+        }
+        Some(Command::ToJson { file, output }) => Some(to_json(file, output.as_deref())),
+        Some(Command::Precompile { file, output }) => Some(precompile(file, output.as_deref())),
+        Some(Command::Repl) => Some(read_eval_print(&cli, log)),
+        Some(Command::Completions { .. }) | None => None,
+    };
 
-    #[test]
-    fn and3() {
-        let prog = read_json("./json/and3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    if let Some(passed) = passed {
+        if !passed {
+            exit(1, sarif);
+        }
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        return;
+    }
+
+    if cli.rpc {
+        if let Err(err) = rpc::serve(io::stdin().lock(), io::stdout()) {
+            eprintln!("Cannot write to stdout: {}", err);
+            exit(1, sarif);
+        }
 
-        assert_eq!(res, Output::Bool(false));
+        return;
     }
 
-    #[test]
-    fn if1() {
-        let prog = read_json("./json/if1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let (mut interpreter, mut context) = match setup(&cli, log) {
+        Ok(setup) => setup,
+        Err(err) => {
+            diagnose_error(&cli, &err);
+            exit(1, sarif);
+        }
+    };
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    let mut summary = Summary::default();
+    let start = Instant::now();
+    let prog = match cli.format {
+        Some(encoding) => interpreter.read_ast(&cli.file, encoding),
+        None => interpreter.read_json(&cli.file),
+    };
+    let expr = match prog {
+        Ok(prog) => prog.expression,
+        Err(err) => {
+            diagnose_error(&cli, &err);
+            exit(1, sarif);
+        }
+    };
 
-        assert_eq!(res, Output::Str(String::from("ok")));
-    }
+    summary.phases.push(("parse", start.elapsed()));
+    log.emit(Level::Info, "loaded program", &[("path", &cli.file.display()), ("ms", &millis(start))]);
 
-    #[test]
-    fn if2() {
-        let prog = read_json("./json/if2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    interpreter.set_lazy(cli.lazy);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+    // typos in branches the run would not take are reported all the same
+    if !cli.no_resolve {
+        let errors = interpreter.unbound(&expr, &context);
 
-        assert_eq!(res, Output::Str(String::from("fail")));
-    }
+        if !errors.is_empty() {
+            for err in &errors {
+                diagnose_error(&cli, err);
+            }
 
-    #[test]
-    fn if3() {
-        let prog = read_json("./json/if3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+            exit(1, sarif);
+        }
+    }
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    if cli.termination && !cli.quiet {
+        for warning in lint::nontermination(&expr) {
+            diagnose(&cli, Diagnostic::from(&warning), || eprintln!("{}", self::warning(&warning, colors().err)));
+        }
+    }
 
-        assert_eq!(
-            res.message,
-            "Condition expression not resolve to a boolean primitive"
-        );
+    if cli.coverage.is_some() || cli.hot.is_some() {
+        log.emit(Level::Debug, "counting evaluations", &[]);
+        interpreter.enable_coverage();
     }
 
-    #[test]
-    fn tuple() {
-        let prog = read_json("./json/tuple.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let trace = cli.trace_out.as_ref().map(|_| {
+        let trace = Trace::default();
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        interpreter.set_hooks(Tracer::new(trace.clone()));
+        trace
+    });
 
-        assert_eq!(
-            res,
-            Output::Tuple((Box::new(Output::Int(BigInt::from(1))), Box::new(Output::Int(BigInt::from(2)))))
-        );
-    }
+    // counting evaluations or allocations and tracing need an actual run
+    let observed = interpreter.coverage().is_some() || trace.is_some();
+    let cache = match &cli.cache_dir {
+        Some(dir) if !observed && !cli.mem_profile => cache_key(&cli, &expr).map(|key| (Cache::new(dir), key)),
+        _ => None,
+    };
 
-    #[test]
-    fn var() {
-        let prog = read_json("./json/var.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    if let Some((cache, key)) = &cache {
+        if let Some(entry) = cache.get(key) {
+            log.emit(Level::Info, "reused cached output", &[("key", key)]);
+            print!("{}", entry.output);
+
+            summary.cache_hits += 1;
+            print_summary(&cli, &summary);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+            return;
+        }
 
-        assert_eq!(res, Output::Int(BigInt::from(3)));
+        interpreter.host_mut().transcript = Some(Default::default());
     }
 
-    #[test]
-    fn first1() {
-        let prog = read_json("./json/first1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    // coverage and traces are of the program as written
+    let program = if observed {
+        expr.clone()
+    } else {
+        let start = Instant::now();
+        let program = interpreter.preevaluate(&expr);
 
-        let res = eval(prog.expression, &mut context).unwrap();
+        summary.phases.push(("preevaluate", start.elapsed()));
+        program
+    };
 
-        assert_eq!(res, Output::Int(BigInt::from(1)));
-    }
+    interrupt_on_ctrl_c(&mut interpreter);
 
-    #[test]
-    fn first2() {
-        let prog = read_json("./json/first2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    let start = Instant::now();
+    let res = interpreter.eval(&program, &mut context);
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    // whatever the program printed comes before what is reported about it
+    interpreter.host_mut().stdout.flush();
+    summary.phases.push(("eval", start.elapsed()));
+    log.emit(Level::Info, "evaluated program", &[("ms", &millis(start))]);
 
-        assert_eq!(res.message, "Cannot access first of a non tuple argument");
+    if !cli.quiet {
+        for warning in interpreter.take_warnings() {
+            diagnose(&cli, Diagnostic::from(&warning), || eprintln!("{}", self::warning(&warning, colors().err)));
+        }
     }
 
-    #[test]
-    fn second1() {
-        let prog = read_json("./json/second1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
-
-        let res = eval(prog.expression, &mut context).unwrap();
+    match (&res, &cache) {
+        (Err(err), _) if err.kind == ErrorKind::Cancelled => {
+            diagnose(&cli, Diagnostic::from(err), || eprint!("{}", interrupted(err)))
+        }
+        // the program chose to stop, there is nothing to report
+        (Err(err), _) if matches!(err.kind, ErrorKind::Exit(_)) => (),
+        (Err(err), _) => diagnose_error(&cli, err),
+        // errors point at locations of this very file, only successes are kept
+        (Ok(value), Some((cache, key))) => {
+            let entry = cache::Entry {
+                value: value.to_string(),
+                output: interpreter
+                    .host_mut()
+                    .transcript
+                    .take()
+                    .map(|transcript| transcript.lock().unwrap_or_else(|err| err.into_inner()).clone())
+                    .unwrap_or_default(),
+            };
 
-        assert_eq!(res, Output::Int(BigInt::from(7)));
+            if let Err(err) = cache.put(key, &entry) {
+                log.emit(Level::Warn, "cannot write cache", &[("path", &cache.dir().display()), ("error", &err)]);
+            }
+        }
+        (Ok(_), None) => (),
     }
 
-    #[test]
-    fn second2() {
-        let prog = read_json("./json/second2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    if let (Some(path), Some(coverage)) = (&cli.coverage, interpreter.coverage()) {
+        eprint!("{}", coverage.report(&expr));
+
+        if let Err(err) = fs::write(path, coverage.json(&expr).to_string()) {
+            log.emit(Level::Warn, "cannot write coverage", &[("path", &path.display()), ("error", &err)]);
+        }
+    }
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    if let (Some(n), Some(coverage)) = (cli.hot, interpreter.coverage()) {
+        for (location, hits) in coverage.hottest(n) {
+            let line = format!("{}:{}:{}: {} evaluations", location.filename, location.start, location.end, hits);
 
-        assert_eq!(res.message, "Cannot access second of a non tuple argument");
+            eprintln!("{}", colors().err.report(&line));
+        }
     }
 
-    #[test]
-    fn closure1() {
-        let prog = read_json("./json/closure1.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+    if cli.mem_profile {
+        let report = memory::Report {
+            usage: ALLOCATOR.usage(),
+            allocations: interpreter.allocations(),
         };
 
-        let res = eval(prog.expression, &mut context).unwrap();
-
-        assert!(matches!(res, Output::Closure(..)));
+        eprint!("{}", report);
     }
 
-    #[test]
-    fn closure2() {
-        let prog = read_json("./json/closure2.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
+    if let (Some(path), Some(trace)) = (&cli.trace_out, &trace) {
+        let out = match path.extension() {
+            Some(ext) if ext == "folded" => trace.folded(),
+            _ => trace.chrome().to_string(),
         };
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
-
-        assert_eq!(
-            res.message,
-            "Arguments declaration differs parameters declaration"
-        );
+        if let Err(err) = fs::write(path, out) {
+            log.emit(Level::Warn, "cannot write trace", &[("path", &path.display()), ("error", &err)]);
+        }
     }
 
-    #[test]
-    fn closure3() {
-        let prog = read_json("./json/closure3.json");
-        let mut context = Context {
-            outter: None,
-            inner: HashMap::new(),
-        };
+    summary.counters = interpreter.counters();
+    summary.collections = interpreter.gc_stats().collections;
+    print_summary(&cli, &summary);
 
-        let res = eval(prog.expression, &mut context).unwrap_err();
+    match res.map_err(|err| err.kind) {
+        Err(ErrorKind::Cancelled) => exit(130, sarif),
+        Err(ErrorKind::Exit(code)) => exit(code, sarif),
+        Err(ErrorKind::Failure) => exit(1, sarif),
+        Ok(_) => (),
+    }
+}
+
+/// Exits with `code` once the SARIF log, when there is one, is written.
+fn exit(code: i32, sarif: Option<Sarif>) -> ! {
+    drop(sarif);
+    let _ = io::stdout().flush();
+    process::exit(code)
+}
 
-        assert_eq!(res.message, "Calling a not callable");
+fn print_summary(cli: &Cli, summary: &Summary) {
+    match cli.stats.as_deref() {
+        Some("json") => eprintln!("{}", summary.json()),
+        Some(_) => eprint!("{}", colors().err.report(&summary.to_string())),
+        None => (),
     }
 }
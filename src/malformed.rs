@@ -0,0 +1,249 @@
+//! What is wrong with a JSON AST serde gave up on. Tagged terms are buffered
+//! before they are deserialized, so serde only says what it expected, not
+//! where. On failure the document is walked again from the outside in to
+//! report the innermost node at fault by its path, e.g. `$.expression.next`.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+
+use crate::ast::{
+    Access, Binary, Bool, Call, File, First, Function, If, Import, Int, Let, Parameter, Print, Second, Seq, Str,
+    Try, Tuple, TuplePattern, Var,
+};
+
+/// Kinds of term a JSON AST may hold.
+const KINDS: &[&str] = &[
+    "Access", "Binary", "Bool", "Call", "First", "Function", "If", "Import", "Int", "Let", "Print", "Second", "Seq",
+    "Str", "Try", "Tuple", "Var",
+];
+
+/// Kinds of pattern a destructuring `let` may hold.
+const PATTERNS: &[&str] = &["Tuple", "Var"];
+
+/// What a field of a node holds, for the fields the walk looks into.
+#[derive(Clone, Copy)]
+enum Role {
+    Term,
+    Pattern,
+    /// Parameters and named arguments, holding terms of their own.
+    Node,
+}
+
+fn role(field: &str, pattern: bool) -> Option<Role> {
+    match field {
+        "elements" if pattern => Some(Role::Pattern),
+        "pattern" => Some(Role::Pattern),
+        "name" | "parameters" | "named" => Some(Role::Node),
+        "expression" | "value" | "next" | "callee" | "arguments" | "elements" | "first" | "second" | "lhs" | "rhs"
+        | "condition" | "then" | "otherwise" | "module" | "default" | "expressions" | "body"
+        | "handler" => Some(Role::Term),
+        _ => None,
+    }
+}
+
+/// Edits turning `a` into `b`, letters compared regardless of case.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.to_lowercase().chars().enumerate() {
+        let mut diagonal = row[0];
+
+        row[0] = i + 1;
+
+        for (j, y) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(x != *y);
+
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The kind `kind` was likely meant to be, if any is close enough.
+fn suggestion<'a>(kind: &str, kinds: &[&'a str]) -> Option<&'a str> {
+    kinds
+        .iter()
+        .map(|candidate| (distance(kind, candidate), *candidate))
+        .filter(|(edits, _)| *edits <= 2 && *edits < kind.chars().count())
+        .min_by_key(|(edits, _)| *edits)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Deserializes `value` as a `T`, the error telling the path in `value` of
+/// the field at fault, if any.
+fn fields<T: DeserializeOwned>(value: &Value, path: &str) -> Result<(), String> {
+    serde_path_to_error::deserialize::<_, T>(value).map(drop).map_err(|err| {
+        let mut path = String::from(path);
+
+        for segment in err.path().iter() {
+            match segment {
+                Segment::Seq { index } => path.push_str(&format!("[{}]", index)),
+                segment => path.push_str(&format!(".{}", segment)),
+            }
+        }
+
+        format!("at {}: {}", path, err.inner())
+    })
+}
+
+/// Looks into the fields of `node` for the terms and patterns they hold.
+fn children(node: &serde_json::Map<String, Value>, path: &str, pattern: bool) -> Result<(), String> {
+    for (field, value) in node {
+        let Some(role) = role(field, pattern) else {
+            continue;
+        };
+        let path = format!("{}.{}", path, field);
+
+        match value {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    walk(item, &format!("{}[{}]", path, i), role)?;
+                }
+            }
+            value => walk(value, &path, role)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds what is wrong with `value`, found at `path`, innermost first. Values
+/// that are not objects are left to the deserializer of the node they are in.
+fn walk(value: &Value, path: &str, role: Role) -> Result<(), String> {
+    let Value::Object(node) = value else {
+        return Ok(());
+    };
+
+    let kinds = match role {
+        Role::Term => KINDS,
+        Role::Pattern => PATTERNS,
+        Role::Node => return children(node, path, false),
+    };
+
+    let kind = match node.get("kind") {
+        Some(Value::String(kind)) => kind,
+        Some(other) => return Err(format!("at {}: kind must be a string, found {}", path, other)),
+        None => return Err(format!("at {}: missing field `kind`, expected one of {}", path, kinds.join(", "))),
+    };
+
+    if !kinds.contains(&kind.as_str()) {
+        let hint = match suggestion(kind, kinds) {
+            Some(candidate) => format!(", did you mean {:?}?", candidate),
+            None => String::from(","),
+        };
+
+        return Err(format!("at {}: unknown kind {:?}{} expected one of {}", path, kind, hint, kinds.join(", ")));
+    }
+
+    let pattern = matches!(role, Role::Pattern);
+
+    children(node, path, pattern)?;
+
+    match (kind.as_str(), pattern) {
+        ("Var", true) => fields::<Parameter>(value, path),
+        ("Tuple", true) => fields::<TuplePattern>(value, path),
+        ("Access", _) => fields::<Access>(value, path),
+        ("Binary", _) => fields::<Binary>(value, path),
+        ("Bool", _) => fields::<Bool>(value, path),
+        ("Call", _) => fields::<Call>(value, path),
+        ("First", _) => fields::<First>(value, path),
+        ("Function", _) => fields::<Function>(value, path),
+        ("If", _) => fields::<If>(value, path),
+        ("Import", _) => fields::<Import>(value, path),
+        ("Int", _) => fields::<Int>(value, path),
+        ("Let", _) => fields::<Let>(value, path),
+        ("Print", _) => fields::<Print>(value, path),
+        ("Second", _) => fields::<Second>(value, path),
+        ("Seq", _) => fields::<Seq>(value, path),
+        ("Str", _) => fields::<Str>(value, path),
+        ("Try", _) => fields::<Try>(value, path),
+        ("Tuple", _) => fields::<Tuple>(value, path),
+        ("Var", _) => fields::<Var>(value, path),
+        _ => Ok(()),
+    }
+}
+
+/// What is wrong with `value` as a JSON AST, None when nothing is.
+pub fn fault(value: &Value) -> Option<String> {
+    let found = match value {
+        Value::Object(node) => children(node, "$", false).and_then(|()| fields::<File>(value, "$")),
+        _ => fields::<File>(value, "$"),
+    };
+
+    found.err()
+}
+
+/// Explains why `source` is not a JSON AST, `err` being what serde said.
+/// Syntax errors, and anything the walk finds no better words for, are
+/// told as serde tells them.
+pub fn explain(source: &str, err: &serde_json::Error) -> String {
+    if !err.is_data() {
+        return err.to_string();
+    }
+
+    serde_json::from_str::<Value>(source)
+        .ok()
+        .and_then(|value| fault(&value))
+        .unwrap_or_else(|| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    fn explain(source: &str) -> String {
+        super::explain(source, &parse_json(source).unwrap_err())
+    }
+
+    #[test]
+    fn kinds() {
+        for kind in KINDS {
+            let err = serde_json::from_value::<crate::ast::Term>(serde_json::json!({ "kind": kind })).unwrap_err();
+
+            assert!(!err.to_string().contains("unknown variant"), "{} is not a kind of term", kind);
+        }
+    }
+
+    #[test]
+    fn paths() {
+        let location = r#"{"start": 0, "end": 1, "filename": "a.rinha"}"#;
+        let prog = |expression: &str| {
+            format!(r#"{{"name": "a.rinha", "location": {0}, "expression": {1}}}"#, location, expression)
+        };
+        let print = |value: &str| format!(r#"{{"kind": "Print", "value": {}, "location": {}}}"#, value, location);
+
+        assert_eq!(
+            explain(&prog(&print(r#"{"kind": "Strr", "value": "a"}"#))),
+            "at $.expression.value: unknown kind \"Strr\", did you mean \"Str\"? expected one of Access, Binary, \
+             Bool, Call, First, Function, If, Import, Int, Let, Print, Second, Seq, Str, Try, Tuple, Var"
+        );
+        assert_eq!(
+            explain(&prog(&print(r#"{"kind": "Str", "value": "a"}"#))),
+            "at $.expression.value: missing field `location`"
+        );
+        assert_eq!(
+            explain(&prog(&print(&format!(
+                r#"{{"kind": "Tuple", "elements": [{{"kind": "Int", "value": 1, "location": {0}}}, {{"value": 2}}],
+                "location": {0}}}"#,
+                location
+            )))),
+            "at $.expression.value.elements[1]: missing field `kind`, expected one of Access, Binary, Bool, Call, \
+             First, Function, If, Import, Int, Let, Print, Second, Seq, Str, Try, Tuple, Var"
+        );
+        assert_eq!(
+            explain(&prog(&print(r#"{"kind": "Str", "value": "a", "location": {"start": "0"}}"#))),
+            "at $.expression.value.location.start: invalid type: string \"0\", expected usize"
+        );
+        assert_eq!(
+            explain(&prog(&print(r#"{"kind": "Nothing"}"#))),
+            "at $.expression.value: unknown kind \"Nothing\", expected one of Access, Binary, Bool, Call, First, \
+             Function, If, Import, Int, Let, Print, Second, Seq, Str, Try, Tuple, Var"
+        );
+        assert_eq!(explain(r#"{"name": "a.rinha""#), "EOF while parsing an object at line 1 column 18");
+    }
+}
@@ -0,0 +1,189 @@
+//! What a run allocates, reported by `--mem-profile`: values and scopes the
+//! evaluator creates, counted by kind, and the bytes the process has on the
+//! heap when `Counting` is its global allocator.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// Values and scopes created by the evaluator, copies of a value not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Allocations {
+    /// Calls, closure captures, modules and suspended lazy bindings.
+    pub scopes: usize,
+    pub thunks: usize,
+    pub closures: usize,
+    pub tuples: usize,
+    /// Literals read and strings built by `+` or builtins.
+    pub strings: usize,
+}
+
+/// Heap usage as seen by `Counting`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub current: usize,
+    pub peak: usize,
+    pub allocations: usize,
+}
+
+/// The system allocator keeping count of the bytes in use and the most ever
+/// used at once, install it with `#[global_allocator]`. It counts nothing
+/// until `enable` is called, so installing it costs a flag read per
+/// allocation on runs that do not look at the counts.
+pub struct Counting {
+    enabled: AtomicBool,
+    current: AtomicUsize,
+    peak: AtomicUsize,
+    allocations: AtomicUsize,
+}
+
+impl Counting {
+    pub const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+        }
+    }
+
+    /// Starts counting, the bytes allocated before then are not seen.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn usage(&self) -> Usage {
+        Usage {
+            current: self.current.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn grow(&self, bytes: usize) {
+        let current = self.current.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn shrink(&self, bytes: usize) {
+        // memory allocated before `enable` may be freed after it
+        let _ = self.current.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            Some(current.saturating_sub(bytes))
+        });
+    }
+}
+
+impl Default for Counting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+
+        if !ptr.is_null() && self.enabled() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            self.grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+
+        if !ptr.is_null() && self.enabled() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            self.grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+
+        if self.enabled() {
+            self.shrink(layout.size());
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = unsafe { System.realloc(ptr, layout, new_size) };
+
+        if !new.is_null() && self.enabled() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            self.shrink(layout.size());
+            self.grow(new_size);
+        }
+
+        new
+    }
+}
+
+/// The summary `--mem-profile` prints once the program finishes.
+pub struct Report {
+    pub usage: Usage,
+    pub allocations: Allocations,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "peak: {} bytes", self.usage.peak)?;
+        writeln!(f, "heap allocations: {}", self.usage.allocations)?;
+        writeln!(f, "  scopes: {}", self.allocations.scopes)?;
+        writeln!(f, "  thunks: {}", self.allocations.thunks)?;
+        writeln!(f, "  closures: {}", self.allocations.closures)?;
+        writeln!(f, "  tuples: {}", self.allocations.tuples)?;
+        writeln!(f, "  strings: {}", self.allocations.strings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting() {
+        let counting = Counting::new();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        unsafe {
+            let early = counting.alloc(layout);
+
+            assert_eq!(counting.usage(), Usage::default());
+
+            counting.enable();
+            counting.dealloc(early, layout);
+
+            let a = counting.alloc(layout);
+            let b = counting.alloc(layout);
+
+            counting.dealloc(a, layout);
+
+            let b = counting.realloc(b, layout, 256);
+
+            assert_eq!(
+                counting.usage(),
+                Usage {
+                    current: 256,
+                    peak: 256,
+                    allocations: 3
+                }
+            );
+
+            counting.dealloc(b, Layout::from_size_align(256, 8).unwrap());
+        }
+
+        assert_eq!(counting.usage().current, 0);
+    }
+}
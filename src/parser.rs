@@ -0,0 +1,1040 @@
+use crate::ast::*;
+use crate::stack;
+use crate::symbol::Symbol;
+use crate::Error;
+use num_bigint::BigInt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(String),
+    Str(String),
+    Ident(String),
+    Let,
+    Import,
+    Fn,
+    If,
+    Else,
+    True,
+    False,
+    Print,
+    First,
+    Second,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    Ellipsis,
+    Semicolon,
+    Assign,
+    Arrow,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Amp,
+    Bar,
+    Caret,
+    Shl,
+    Shr,
+    EqEq,
+    NotEq,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+    AndAnd,
+    OrOr,
+    Pipe,
+    Compose,
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    start: usize,
+    end: usize,
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    filename: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn error(&self, message: &str, start: usize, end: usize) -> Error {
+        Error::new(
+            message,
+            Location {
+                start,
+                end,
+                filename: String::from(self.filename),
+            },
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), Error> {
+        loop {
+            let rest = &self.source[self.pos..];
+
+            if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+                self.pos += c.len_utf8();
+            } else if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                match comment.find("*/") {
+                    Some(end) => self.pos += end + 4,
+                    None => {
+                        return Err(self.error("Unterminated comment", self.pos, self.source.len()))
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads the string literal opening at `start`, decoding `\n`, `\t`, `\r`,
+    /// `\0`, `\\`, `\"` and `\u{..}` escapes.
+    fn string(&mut self, start: usize) -> Result<String, Error> {
+        let mut value = String::new();
+        let mut chars = self.source[start + 1..].char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            let at = start + 1 + i;
+
+            match c {
+                '"' => {
+                    self.pos = at + 1;
+
+                    return Ok(value);
+                }
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some((_, 'n')) => '\n',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'r')) => '\r',
+                        Some((_, '0')) => '\0',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '"')) => '"',
+                        Some((_, 'u')) => {
+                            let rest = &self.source[at + 2..];
+                            let code = rest
+                                .strip_prefix('{')
+                                .and_then(|rest| rest.split_once('}'))
+                                .map(|(digits, _)| digits);
+
+                            let Some(digits) = code else {
+                                return Err(self.error("Expected \\u{..} escape", at, at + 2));
+                            };
+
+                            let end = at + 2 + digits.len() + 2;
+                            let c = u32::from_str_radix(digits, 16)
+                                .ok()
+                                .filter(|_| !digits.is_empty() && digits.len() <= 6)
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("Invalid unicode escape", at, end))?;
+
+                            for _ in 0..digits.len() + 2 {
+                                chars.next();
+                            }
+
+                            c
+                        }
+                        Some((j, c)) => {
+                            let msg = format!("Unknown escape sequence \\{}", c);
+
+                            return Err(self.error(&msg, at, start + 1 + j + c.len_utf8()));
+                        }
+                        None => break,
+                    };
+
+                    value.push(escaped);
+                }
+                c => value.push(c),
+            }
+        }
+
+        Err(self.error("Unterminated string literal", start, self.source.len()))
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Spanned>, Error> {
+        let mut tokens = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                tokens.push(Spanned {
+                    token: Token::Eof,
+                    start,
+                    end: start,
+                });
+
+                return Ok(tokens);
+            };
+
+            let token = if c.is_ascii_digit() {
+                let len = self.source[start..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(self.source.len() - start);
+                self.pos += len;
+
+                Token::Int(String::from(&self.source[start..self.pos]))
+            } else if c.is_alphabetic() || c == '_' {
+                let len = self.source[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(self.source.len() - start);
+                self.pos += len;
+
+                match &self.source[start..self.pos] {
+                    "let" => Token::Let,
+                    "import" => Token::Import,
+                    "fn" => Token::Fn,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    "print" => Token::Print,
+                    "first" => Token::First,
+                    "second" => Token::Second,
+                    ident => Token::Ident(String::from(ident)),
+                }
+            } else if c == '"' {
+                Token::Str(self.string(start)?)
+            } else {
+                let rest = &self.source[start..];
+                let (token, len) = [
+                    ("...", Token::Ellipsis),
+                    ("<<<", Token::Shl),
+                    (">>>", Token::Shr),
+                    ("=>", Token::Arrow),
+                    ("==", Token::EqEq),
+                    ("!=", Token::NotEq),
+                    ("<=", Token::Lte),
+                    (">=", Token::Gte),
+                    ("&&", Token::AndAnd),
+                    ("||", Token::OrOr),
+                    ("|>", Token::Pipe),
+                    (">>", Token::Compose),
+                    ("(", Token::LParen),
+                    (")", Token::RParen),
+                    ("{", Token::LBrace),
+                    ("}", Token::RBrace),
+                    (",", Token::Comma),
+                    (".", Token::Dot),
+                    (";", Token::Semicolon),
+                    ("=", Token::Assign),
+                    ("+", Token::Plus),
+                    ("-", Token::Minus),
+                    ("*", Token::Star),
+                    ("/", Token::Slash),
+                    ("%", Token::Percent),
+                    ("&", Token::Amp),
+                    ("|", Token::Bar),
+                    ("^", Token::Caret),
+                    ("<", Token::Lt),
+                    (">", Token::Gt),
+                ]
+                .into_iter()
+                .find(|(text, _)| rest.starts_with(text))
+                .map(|(text, token)| (token, text.len()))
+                .ok_or_else(|| {
+                    let msg = format!("Unexpected character {:?}", c);
+
+                    self.error(&msg, start, start + c.len_utf8())
+                })?;
+                self.pos += len;
+
+                token
+            };
+
+            tokens.push(Spanned {
+                token,
+                start,
+                end: self.pos,
+            });
+        }
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    filename: &'a str,
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn location(&self, start: usize, end: usize) -> Location {
+        Location {
+            start,
+            end,
+            filename: String::from(self.filename),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].token
+    }
+
+    fn advance(&mut self) -> Spanned {
+        let token = self.tokens[self.pos].clone();
+
+        if token.token != Token::Eof {
+            self.pos += 1;
+        }
+
+        token
+    }
+
+    /// Whether `name =` comes next, starting a named argument.
+    fn named(&self) -> bool {
+        matches!(self.peek(), Token::Ident(_))
+            && self.tokens.get(self.pos + 1).is_some_and(|next| next.token == Token::Assign)
+    }
+
+    fn last_end(&self) -> usize {
+        self.tokens[self.pos.saturating_sub(1)].end
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let current = &self.tokens[self.pos];
+        let msg = match &current.token {
+            Token::Eof => format!("Unexpected end of file, expected {}", expected),
+            _ => format!(
+                "Unexpected '{}', expected {}",
+                &self.source[current.start..current.end],
+                expected
+            ),
+        };
+
+        Error::new(&msg, self.location(current.start, current.end))
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<Spanned, Error> {
+        if *self.peek() == token {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        &self.tokens[(self.pos + n).min(self.tokens.len() - 1)].token
+    }
+
+    /// `export`, `as`, `try` and `catch` are only keywords where they can
+    /// appear, so existing programs using them as names keep working.
+    fn contextual(&self, n: usize, keyword: &str) -> bool {
+        matches!(self.peek_nth(n), Token::Ident(text) if text == keyword)
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        if *self.peek() == token {
+            self.advance();
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<Parameter, Error> {
+        if !matches!(self.peek(), Token::Ident(_)) {
+            return Err(self.unexpected("an identifier"));
+        }
+
+        let current = self.advance();
+        let Token::Ident(text) = current.token else {
+            unreachable!()
+        };
+
+        Ok(Parameter {
+            text: text.into(),
+            location: self.location(current.start, current.end),
+            rest: false,
+            default: None,
+        })
+    }
+
+    /// `name` or a parenthesized tuple of at least two patterns.
+    fn pattern(&mut self) -> Result<Pattern, Error> {
+        if *self.peek() != Token::LParen {
+            return Ok(Pattern::Var(self.ident()?));
+        }
+
+        let start = self.advance().start;
+        let mut elements = vec![self.pattern()?];
+
+        self.expect(Token::Comma, "','")?;
+
+        loop {
+            elements.push(self.pattern()?);
+
+            if !self.eat(Token::Comma) {
+                break;
+            }
+        }
+
+        self.expect(Token::RParen, "')'")?;
+
+        Ok(Pattern::Tuple(TuplePattern {
+            elements,
+            location: self.location(start, self.last_end()),
+        }))
+    }
+
+    fn expr(&mut self) -> Result<Term, Error> {
+        // every nested block, parenthesis, argument or `let` goes through here
+        if stack::exhausted() {
+            let current = &self.tokens[self.pos];
+
+            return Err(Error::new("Expression nests too deep", self.location(current.start, current.end)));
+        }
+
+        if *self.peek() == Token::Import {
+            return self.import();
+        }
+
+        let export = self.contextual(0, "export") && *self.peek_nth(1) == Token::Let;
+
+        if *self.peek() != Token::Let && !export {
+            return self.binary(0);
+        }
+
+        let start = self.advance().start;
+
+        if export {
+            self.advance();
+        }
+
+        let (name, pattern) = if *self.peek() == Token::LParen {
+            let pattern = self.pattern()?;
+            let name = Parameter {
+                text: Symbol::new("_"),
+                location: pattern.location().clone(),
+                rest: false,
+                default: None,
+            };
+
+            (name, Some(pattern))
+        } else {
+            (self.ident()?, None)
+        };
+
+        self.expect(Token::Assign, "'='")?;
+        let value = self.expr()?;
+        self.eat(Token::Semicolon);
+        let next = self.sequence()?;
+
+        Ok(Term::Let(Let {
+            name,
+            pattern,
+            value: Box::new(value),
+            next: Box::new(next),
+            export,
+            location: self.location(start, self.last_end()),
+        }))
+    }
+
+    /// `import "path"; next` binds the imported names for `next`, sugar for
+    /// `let _ = import "path"; next`. With `import "path" as name; next` the
+    /// module itself is bound to `name` instead.
+    fn import(&mut self) -> Result<Term, Error> {
+        let start = self.advance().start;
+
+        if !matches!(self.peek(), Token::Str(_)) {
+            return Err(self.unexpected("a path string"));
+        }
+
+        let current = self.advance();
+        let Token::Str(path) = current.token else {
+            unreachable!()
+        };
+
+        let alias = if self.contextual(0, "as") {
+            self.advance();
+
+            Some(self.ident()?)
+        } else {
+            None
+        };
+
+        let import = Term::Import(Import {
+            path,
+            alias: alias.as_ref().map(|alias| alias.text.to_string()),
+            location: self.location(start, self.last_end()),
+        });
+
+        self.eat(Token::Semicolon);
+
+        if matches!(self.peek(), Token::Eof | Token::RBrace) {
+            return Ok(import);
+        }
+
+        let next = self.sequence()?;
+
+        Ok(Term::Let(Let {
+            name: alias.unwrap_or_else(|| Parameter {
+                text: Symbol::new("_"),
+                location: self.location(start, start),
+                rest: false,
+                default: None,
+            }),
+            pattern: None,
+            value: Box::new(import),
+            next: Box::new(next),
+            export: false,
+            location: self.location(start, self.last_end()),
+        }))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Term, Error> {
+        const LEVELS: [&[(Token, BinaryOp)]; 12] = [
+            &[(Token::Pipe, BinaryOp::Pipe)],
+            &[(Token::Compose, BinaryOp::Compose)],
+            &[(Token::OrOr, BinaryOp::Or)],
+            &[(Token::AndAnd, BinaryOp::And)],
+            &[(Token::EqEq, BinaryOp::Eq), (Token::NotEq, BinaryOp::Neq)],
+            &[
+                (Token::Lt, BinaryOp::Lt),
+                (Token::Gt, BinaryOp::Gt),
+                (Token::Lte, BinaryOp::Lte),
+                (Token::Gte, BinaryOp::Gte),
+            ],
+            &[(Token::Bar, BinaryOp::BitOr)],
+            &[(Token::Caret, BinaryOp::BitXor)],
+            &[(Token::Amp, BinaryOp::BitAnd)],
+            &[(Token::Shl, BinaryOp::Shl), (Token::Shr, BinaryOp::Shr)],
+            &[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Sub)],
+            &[
+                (Token::Star, BinaryOp::Mul),
+                (Token::Slash, BinaryOp::Div),
+                (Token::Percent, BinaryOp::Rem),
+            ],
+        ];
+
+        if level == LEVELS.len() {
+            return self.call();
+        }
+
+        let start = self.tokens[self.pos].start;
+        let mut lhs = self.binary(level + 1)?;
+
+        while let Some((_, op)) = LEVELS[level].iter().find(|(token, _)| token == self.peek()) {
+            self.advance();
+            let rhs = self.binary(level + 1)?;
+
+            lhs = Term::Binary(Binary {
+                lhs: Box::new(lhs),
+                op: op.clone(),
+                rhs: Box::new(rhs),
+                location: self.location(start, self.last_end()),
+            });
+        }
+
+        Ok(lhs)
+    }
+
+    fn call(&mut self) -> Result<Term, Error> {
+        let start = self.tokens[self.pos].start;
+        let mut callee = self.primary()?;
+
+        loop {
+            if self.eat(Token::Dot) {
+                let name = self.ident()?;
+
+                callee = Term::Access(Access {
+                    module: Box::new(callee),
+                    name: name.text.to_string(),
+                    location: self.location(start, self.last_end()),
+                });
+
+                continue;
+            }
+
+            if !self.eat(Token::LParen) {
+                break;
+            }
+
+            let mut arguments = Vec::new();
+            let mut named = Vec::new();
+
+            while *self.peek() != Token::RParen {
+                let at = self.tokens[self.pos].start;
+
+                if self.named() {
+                    let name = self.ident()?.text;
+
+                    self.advance();
+
+                    let value = self.expr()?;
+
+                    named.push(NamedArgument {
+                        name,
+                        value,
+                        location: self.location(at, self.last_end()),
+                    });
+                } else if named.is_empty() {
+                    arguments.push(self.expr()?);
+                } else {
+                    return Err(Error::new(
+                        "Positional arguments go before named ones",
+                        self.location(at, self.tokens[self.pos].end),
+                    ));
+                }
+
+                if !self.eat(Token::Comma) {
+                    break;
+                }
+            }
+
+            self.expect(Token::RParen, "')'")?;
+
+            callee = Term::Call(Call {
+                callee: Box::new(callee),
+                arguments,
+                named,
+                location: self.location(start, self.last_end()),
+            });
+        }
+
+        Ok(callee)
+    }
+
+    /// Expressions separated by `;`, a `Term::Seq` when there are more than
+    /// one. Bodies of blocks, of `let` and of the file are sequences, so the
+    /// names a `let` binds are in scope up to the end of the block.
+    fn sequence(&mut self) -> Result<Term, Error> {
+        let start = self.tokens[self.pos].start;
+        let mut expressions = vec![self.expr()?];
+
+        while self.eat(Token::Semicolon) && !matches!(self.peek(), Token::RBrace | Token::Eof) {
+            expressions.push(self.expr()?);
+        }
+
+        if expressions.len() == 1 {
+            return Ok(expressions.remove(0));
+        }
+
+        Ok(Term::Seq(Seq {
+            expressions,
+            location: self.location(start, self.last_end()),
+        }))
+    }
+
+    fn block(&mut self) -> Result<Term, Error> {
+        self.expect(Token::LBrace, "'{'")?;
+        let term = self.sequence()?;
+        self.expect(Token::RBrace, "'}'")?;
+
+        Ok(term)
+    }
+
+    fn unary(&mut self) -> Result<Box<Term>, Error> {
+        self.expect(Token::LParen, "'('")?;
+        let value = self.expr()?;
+        self.expect(Token::RParen, "')'")?;
+
+        Ok(Box::new(value))
+    }
+
+    fn primary(&mut self) -> Result<Term, Error> {
+        let current = self.advance();
+        let start = current.start;
+
+        match current.token {
+            Token::Int(text) => Ok(Term::Int(Int {
+                value: self.int(&text, start, current.end)?,
+                location: self.location(start, current.end),
+            })),
+            Token::Minus if matches!(self.peek(), Token::Int(_)) => {
+                let literal = self.advance();
+                let Token::Int(text) = literal.token else {
+                    unreachable!()
+                };
+
+                Ok(Term::Int(Int {
+                    value: self.int(&format!("-{}", text), start, literal.end)?,
+                    location: self.location(start, literal.end),
+                }))
+            }
+            Token::Str(value) => Ok(Term::Str(Str {
+                value,
+                location: self.location(start, current.end),
+            })),
+            Token::True | Token::False => Ok(Term::Bool(Bool {
+                value: current.token == Token::True,
+                location: self.location(start, current.end),
+            })),
+            // the handler is a name, a member, a call or a function literal
+            Token::Ident(text) if text == "try" && *self.peek() == Token::LBrace => {
+                let body = self.block()?;
+
+                if !self.contextual(0, "catch") {
+                    return Err(self.unexpected("'catch'"));
+                }
+
+                self.advance();
+                let handler = self.call()?;
+
+                Ok(Term::Try(Try {
+                    body: Box::new(body),
+                    handler: Box::new(handler),
+                    location: self.location(start, self.last_end()),
+                }))
+            }
+            Token::Ident(text) => Ok(Term::Var(Var {
+                text: text.into(),
+                location: self.location(start, current.end),
+            })),
+            Token::Print => Ok(Term::Print(Print {
+                value: self.unary()?,
+                location: self.location(start, self.last_end()),
+            })),
+            Token::First => Ok(Term::First(First {
+                value: self.unary()?,
+                location: self.location(start, self.last_end()),
+            })),
+            Token::Second => Ok(Term::Second(Second {
+                value: self.unary()?,
+                location: self.location(start, self.last_end()),
+            })),
+            Token::LParen => {
+                let first = self.expr()?;
+
+                if self.eat(Token::Comma) {
+                    let mut elements = vec![first, self.expr()?];
+
+                    while self.eat(Token::Comma) {
+                        elements.push(self.expr()?);
+                    }
+
+                    self.expect(Token::RParen, "')'")?;
+
+                    Ok(Term::Tuple(Tuple {
+                        elements,
+                        location: self.location(start, self.last_end()),
+                    }))
+                } else {
+                    self.expect(Token::RParen, "')'")?;
+
+                    Ok(first)
+                }
+            }
+            Token::LBrace => {
+                let term = self.sequence()?;
+                self.expect(Token::RBrace, "'}'")?;
+
+                Ok(term)
+            }
+            Token::Fn => {
+                self.expect(Token::LParen, "'('")?;
+                let mut parameters = Vec::new();
+
+                while *self.peek() != Token::RParen {
+                    let rest = self.eat(Token::Ellipsis);
+                    let mut param = self.ident()?;
+
+                    param.rest = rest;
+
+                    if !rest && self.eat(Token::Assign) {
+                        param.default = Some(Box::new(self.expr()?));
+                    } else if !rest && parameters.iter().any(|param: &Parameter| param.default.is_some()) {
+                        let msg = format!("Parameter {} follows one with a default, it needs a default too", param.text);
+
+                        return Err(Error::new(&msg, param.location));
+                    }
+
+                    parameters.push(param);
+
+                    // nothing may follow a rest parameter
+                    if rest || !self.eat(Token::Comma) {
+                        break;
+                    }
+                }
+
+                self.expect(Token::RParen, "')'")?;
+                self.expect(Token::Arrow, "'=>'")?;
+
+                let value = if *self.peek() == Token::LBrace {
+                    self.block()?
+                } else {
+                    self.expr()?
+                };
+
+                Ok(Term::Function(Function {
+                    parameters: parameters.into(),
+                    value: Arc::new(value),
+                    location: self.location(start, self.last_end()),
+                }))
+            }
+            Token::If => {
+                self.expect(Token::LParen, "'('")?;
+                let condition = self.expr()?;
+                self.expect(Token::RParen, "')'")?;
+                let then = self.block()?;
+                self.expect(Token::Else, "'else'")?;
+
+                let otherwise = if *self.peek() == Token::If {
+                    self.primary()?
+                } else {
+                    self.block()?
+                };
+
+                Ok(Term::If(If {
+                    condition: Box::new(condition),
+                    then: Box::new(then),
+                    otherwise: Box::new(otherwise),
+                    location: self.location(start, self.last_end()),
+                }))
+            }
+            token => {
+                if token != Token::Eof {
+                    self.pos -= 1;
+                }
+
+                Err(self.unexpected("an expression"))
+            }
+        }
+    }
+
+    fn int(&self, text: &str, start: usize, end: usize) -> Result<BigInt, Error> {
+        text.parse::<BigInt>()
+            .map_err(|_| Error::new("Invalid integer literal", self.location(start, end)))
+    }
+}
+
+/// Parses rinha source code into the same AST produced by the reference
+/// JSON front-end, locations are byte offsets into `source`.
+pub fn parse(source: &str, filename: &str) -> Result<File, Error> {
+    let tokens = Lexer {
+        source,
+        filename,
+        pos: 0,
+    }
+    .tokenize()?;
+
+    let mut parser = Parser {
+        source,
+        filename,
+        tokens,
+        pos: 0,
+    };
+
+    let expression = parser.sequence()?;
+
+    if *parser.peek() != Token::Eof {
+        return Err(parser.unexpected("end of file"));
+    }
+
+    Ok(File {
+        name: String::from(filename),
+        expression,
+        location: parser.location(0, source.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Interpreter, Output};
+    use num_bigint::BigInt;
+
+    fn eval(source: &str) -> Output {
+        let prog = parse(source, "test").unwrap();
+
+        Interpreter::default()
+            .eval(&prog.expression, &mut Context::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn fib() {
+        let res = eval(
+            "let fib = fn (n) => {
+                if (n < 2) { n } else { fib(n - 1) + fib(n - 2) }
+            };
+
+            fib(10)",
+        );
+
+        assert_eq!(res, Output::Int(BigInt::from(55)));
+    }
+
+    #[test]
+    fn precedence() {
+        let res = eval("1 + 2 * 3 - -4 % 3 == 8 && (1, \"a\") != (1, \"b\") || false");
+
+        assert_eq!(res, Output::Bool(true));
+    }
+
+    #[test]
+    fn big_int() {
+        assert_eq!(eval("-99999999999999999999 * 10"), Output::Int("-999999999999999999990".parse().unwrap()));
+    }
+
+    #[test]
+    fn rest() {
+        assert_eq!(eval("let f = fn (a, ...b) => { (a, b) }; f(1, 2, 3)").to_string(), "(1, (2, 3))");
+        assert_eq!(parse("fn (...a, b) => { a }", "test").unwrap_err().message, "Unexpected ',', expected ')'");
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(eval("let f = fn (a, b = a * 2) => { (a, b) }; (f(1), f(1, 3))").to_string(), "((1, 2), (1, 3))");
+        assert_eq!(
+            parse("fn (a = 1, b) => { a }", "test").unwrap_err().message,
+            "Parameter b follows one with a default, it needs a default too"
+        );
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(eval("let f = fn (a, b = 2, c = 3) => { (a, b, c) }; f(1, c = 4)").to_string(), "(1, 2, 4)");
+        assert_eq!(parse("f(a = 1, 2)", "test").unwrap_err().message, "Positional arguments go before named ones");
+    }
+
+    #[test]
+    fn pipe() {
+        assert_eq!(eval("let f = fn (n) => { n * 2 }; 1 + 2 |> f >> f").to_string(), "12");
+        assert_eq!(eval("\"ab\" + \"c\" |> len").to_string(), "3");
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(eval("5 & 3 | 8 ^ 1 <<< 4 + 1").to_string(), "41");
+        assert_eq!(eval("(-5 >>> 1, 1 <<< -1, 3 >>> -2, -1 >>> 1000, 12 & -4)").to_string(), "(-3, 0, 12, -1, 12)");
+        assert_eq!(eval("let f = fn (n) => { n ^ 1 }; 4 |> f >> f").to_string(), "4");
+    }
+
+    #[test]
+    fn sequence() {
+        assert_eq!(eval("let f = fn (x) => { print(x); x * 2 }; { f(1); f(2) }").to_string(), "4");
+        assert_eq!(eval("let x = 1; print(x); let y = x + 1; y").to_string(), "2");
+        assert_eq!(eval("{ 1; 2; }").to_string(), "2");
+
+        let prog = parse("print(1); 2", "test").unwrap();
+
+        let Term::Seq(x) = prog.expression else {
+            panic!("expected a sequence")
+        };
+
+        assert_eq!(x.expressions.len(), 2);
+        assert_eq!((x.location.start, x.location.end), (0, 11));
+        assert_eq!(parse("{ 1;; 2 }", "test").unwrap_err().message, "Unexpected ';', expected an expression");
+    }
+
+    #[test]
+    fn try_catch() {
+        assert_eq!(eval("let try = 1; try + (try { 1 / 0 } catch fn (err) => { 2 })").to_string(), "3");
+        assert_eq!(eval("try { 1 } catch len + 1").to_string(), "2");
+        assert_eq!(parse("try { 1 } finally", "test").unwrap_err().message, "Unexpected 'finally', expected 'catch'");
+    }
+
+    #[test]
+    fn location() {
+        let prog = parse("let x = 1; x", "test").unwrap();
+
+        let Term::Let(x) = prog.expression else {
+            panic!("expected a let")
+        };
+
+        assert_eq!((x.location.start, x.location.end), (0, 12));
+        assert_eq!((x.next.location().start, x.next.location().end), (11, 12));
+    }
+
+    #[test]
+    fn error() {
+        let err = parse("let x = ; 1", "test").unwrap_err();
+
+        assert_eq!(err.message, "Unexpected ';', expected an expression");
+        assert_eq!((err.start, err.end), (8, 9));
+
+        let source = "let s = \"ação\";\nlet x = ; 1";
+        let err = parse(source, "test").unwrap_err();
+
+        assert_eq!(err.render(source), "test:2:9: Unexpected ';', expected an expression");
+        assert_eq!(err.render(""), "test:26:27: Unexpected ';', expected an expression");
+    }
+
+    #[test]
+    fn nesting() {
+        let source = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+
+        assert_eq!(parse(&source, "test").unwrap_err().message, "Expression nests too deep");
+    }
+
+    #[test]
+    fn escapes() {
+        let res = eval(r#""a\tb\n\"c\" \\ \u{1F980}""#);
+
+        assert_eq!(res, Output::Str("a\tb\n\"c\" \\ \u{1F980}".into()));
+
+        let err = parse(r#"let x = "\q"; x"#, "test").unwrap_err();
+
+        assert_eq!(err.message, "Unknown escape sequence \\q");
+        assert_eq!((err.start, err.end), (9, 11));
+    }
+    #[test]
+    fn literals() {
+        assert_eq!(eval("(true, false, \"\", 007)").to_string(), "(true, false, , 7)");
+        assert_eq!(eval("let t = ((1, 2), 3); first(first(t)) + second(t)").to_string(), "4");
+        assert_eq!(eval("if (1 < 2) { \"yes\" } else { \"no\" }").to_string(), "yes");
+        assert_eq!(eval("let f = fn () => { 1 }; f()").to_string(), "1");
+    }
+
+    #[test]
+    fn trivia() {
+        assert_eq!(eval("// one\nlet x = /* two */ 1; /* three\n */ x // four").to_string(), "1");
+        assert_eq!(parse("1 /* two", "test").unwrap_err().message, "Unterminated comment");
+
+        let err = parse("let x = \"a; x", "test").unwrap_err();
+
+        assert_eq!(err.message, "Unterminated string literal");
+        assert_eq!((err.start, err.end), (8, 13));
+
+        let err = parse("1 $ 2", "test").unwrap_err();
+
+        assert_eq!(err.message, "Unexpected character '$'");
+        assert_eq!((err.start, err.end), (2, 3));
+        assert_eq!(parse("1 2", "test").unwrap_err().message, "Unexpected '2', expected end of file");
+    }
+
+    #[test]
+    fn samples() {
+        let mut sources: Vec<_> = std::fs::read_dir("./json")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rinha"))
+            .collect();
+        sources.sort();
+
+        assert!(!sources.is_empty());
+
+        // the same trees as the JSON ASTs next to them, locations aside
+        for path in sources {
+            let source = std::fs::read_to_string(&path).unwrap();
+            let json = std::fs::read_to_string(path.with_extension("json")).unwrap();
+            let parsed = parse(&source, "test").unwrap_or_else(|err| panic!("{}: {}", path.display(), err.message));
+
+            assert_eq!(
+                crate::canonical::hash(&parsed.expression),
+                crate::canonical::hash(&crate::parse_json(&json).unwrap().expression),
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
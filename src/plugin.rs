@@ -0,0 +1,140 @@
+//! Builtins loaded from shared libraries at startup, see the plugin section
+//! of `include/rinha.h`.
+
+use libloading::{Library, Symbol};
+use std::{
+    ffi::{c_char, c_void, CStr},
+    path::Path,
+};
+
+use crate::{
+    ffi::{adapt, RinhaBuiltin},
+    host::Host,
+    Interpreter,
+};
+
+/// Bumped whenever the layout of `RinhaPluginApi` or `RinhaValue` changes.
+pub const RINHA_PLUGIN_ABI: u32 = 1;
+
+#[repr(C)]
+pub struct RinhaPluginApi {
+    pub abi: u32,
+    pub registrar: *mut c_void,
+    pub register_builtin: extern "C" fn(
+        registrar: *mut c_void,
+        name: *const c_char,
+        builtin: RinhaBuiltin,
+        userdata: *mut c_void,
+    ) -> bool,
+}
+
+/// `rinha_plugin_init`, registering the builtins of the plugin through `api`
+/// and returning false to refuse loading.
+pub type RinhaPluginInit = unsafe extern "C" fn(api: *const RinhaPluginApi) -> bool;
+
+extern "C" fn register<H: Host + 'static>(
+    registrar: *mut c_void,
+    name: *const c_char,
+    builtin: RinhaBuiltin,
+    userdata: *mut c_void,
+) -> bool {
+    if name.is_null() {
+        return false;
+    }
+
+    let interpreter = unsafe { &mut *(registrar as *mut Interpreter<H>) };
+
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+        return false;
+    };
+
+    interpreter.register(name, adapt(builtin, userdata));
+
+    true
+}
+
+/// Runs `init` with a registrar adding builtins to `interpreter`.
+///
+/// # Safety
+///
+/// `init` must follow the plugin interface.
+pub unsafe fn install<H: Host + 'static>(interpreter: &mut Interpreter<H>, init: RinhaPluginInit) -> Result<(), String> {
+    let api = RinhaPluginApi {
+        abi: RINHA_PLUGIN_ABI,
+        registrar: interpreter as *mut Interpreter<H> as *mut c_void,
+        register_builtin: register::<H>,
+    };
+
+    if init(&api) {
+        Ok(())
+    } else {
+        Err(String::from("Plugin refused to load"))
+    }
+}
+
+/// Loads the shared library at `path` and registers its builtins. The library
+/// stays loaded until the process exits, as its builtins point into it.
+///
+/// # Safety
+///
+/// Loading a library runs its initializers, only load trusted plugins.
+pub unsafe fn load<H: Host + 'static>(interpreter: &mut Interpreter<H>, path: &Path) -> Result<(), String> {
+    let library = Library::new(path).map_err(|err| err.to_string())?;
+
+    let abi: Symbol<unsafe extern "C" fn() -> u32> = library
+        .get(b"rinha_plugin_abi\0")
+        .map_err(|err| err.to_string())?;
+    let abi = abi();
+
+    if abi != RINHA_PLUGIN_ABI {
+        return Err(format!("Plugin targets ABI {}, expected {}", abi, RINHA_PLUGIN_ABI));
+    }
+
+    let init: Symbol<RinhaPluginInit> = library
+        .get(b"rinha_plugin_init\0")
+        .map_err(|err| err.to_string())?;
+
+    install(interpreter, *init)?;
+    std::mem::forget(library);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::RinhaKind, ffi::RinhaValue, host::CaptureHost, parser::parse, Context, Output};
+
+    extern "C" fn answer(_: *mut c_void, _: *const RinhaValue, _: usize) -> RinhaValue {
+        RinhaValue {
+            kind: RinhaKind::Int,
+            text: c"42".as_ptr(),
+        }
+    }
+
+    unsafe extern "C" fn init(api: *const RinhaPluginApi) -> bool {
+        let api = &*api;
+
+        api.abi == RINHA_PLUGIN_ABI && (api.register_builtin)(api.registrar, c"answer".as_ptr(), answer, std::ptr::null_mut())
+    }
+
+    unsafe extern "C" fn refuse(_: *const RinhaPluginApi) -> bool {
+        false
+    }
+
+    #[test]
+    fn plugin() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+
+        unsafe { install(&mut interpreter, init) }.unwrap();
+
+        let prog = parse("answer() + 1", "test").unwrap();
+
+        assert_eq!(
+            interpreter.eval(&prog.expression, &mut Context::default()).unwrap(),
+            Output::Int(43.into())
+        );
+        assert!(unsafe { install(&mut interpreter, refuse) }.is_err());
+        assert!(unsafe { load(&mut interpreter, Path::new("./json/fib.json")) }.is_err());
+    }
+}
@@ -0,0 +1,8 @@
+use crate::{ast::File, parser};
+
+const PRELUDE: &str = include_str!("prelude.rinha");
+
+/// Helpers loaded by the CLI before every program.
+pub fn default_prelude() -> File {
+    parser::parse(PRELUDE, "prelude.rinha").expect("The built-in prelude is valid rinha")
+}
@@ -0,0 +1,206 @@
+use num_bigint::BigInt;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyTypeError},
+    prelude::*,
+    types::{PyBool, PyDict, PyString, PyTuple},
+};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{ast::File, malformed, parse_json, parser, Closure, Context, Interpreter, Output};
+
+create_exception!(rinha, RinhaError, PyException);
+
+/// Interpreter and root scope a program ran in, closures handed to Python keep
+/// it alive so they can be called later.
+struct Session {
+    interpreter: Interpreter,
+    context: Context,
+}
+
+#[pyclass(unsendable, name = "Closure")]
+struct PyClosure {
+    closure: Closure,
+    session: Rc<RefCell<Session>>,
+}
+
+#[pymethods]
+impl PyClosure {
+    #[pyo3(signature = (*args))]
+    fn __call__(&self, py: Python<'_>, args: &Bound<'_, PyTuple>) -> PyResult<Py<PyAny>> {
+        let args = args
+            .iter()
+            .map(|arg| to_output(&arg))
+            .collect::<PyResult<Vec<_>>>()?;
+        let location = self.closure.body.location();
+
+        let res = {
+            let Session {
+                interpreter,
+                context,
+            } = &mut *self.session.borrow_mut();
+
+            interpreter
+                .apply(self.closure.clone(), args, context, location)
+                .map_err(|err| RinhaError::new_err(err.to_string()))?
+        };
+
+        to_python(py, res, &self.session)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<#closure/{}>", self.closure.args.len())
+    }
+}
+
+fn to_output(value: &Bound<'_, PyAny>) -> PyResult<Output> {
+    if value.is_none() {
+        Ok(Output::Void)
+    } else if let Ok(x) = value.cast::<PyBool>() {
+        Ok(Output::Bool(x.is_true()))
+    } else if let Ok(x) = value.extract::<BigInt>() {
+        Ok(Output::Int(x))
+    } else if let Ok(x) = value.cast::<PyString>() {
+        Ok(Output::Str(x.to_str()?.into()))
+    } else if let Ok(x) = value.cast::<PyTuple>() {
+        Ok(Output::Tuple(x.iter().map(|item| to_output(&item)).collect::<PyResult<_>>()?))
+    } else if let Ok(x) = value.cast::<PyClosure>() {
+        Ok(Output::Closure(x.borrow().closure.clone()))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Cannot convert {} into a rinha value",
+            value.get_type().name()?
+        )))
+    }
+}
+
+fn to_python(py: Python<'_>, output: Output, session: &Rc<RefCell<Session>>) -> PyResult<Py<PyAny>> {
+    Ok(match output {
+        Output::Bool(x) => PyBool::new(py, x).to_owned().into_any().unbind(),
+        Output::Int(x) => x.into_pyobject(py)?.into_any().unbind(),
+        Output::Str(x) => PyString::new(py, &x).into_any().unbind(),
+        Output::Tuple(elements) => {
+            let items = elements
+                .into_iter()
+                .map(|element| to_python(py, element, session))
+                .collect::<PyResult<Vec<_>>>()?;
+
+            PyTuple::new(py, items)?.into_any().unbind()
+        }
+        Output::Closure(closure) => Py::new(
+            py,
+            PyClosure {
+                closure,
+                session: Rc::clone(session),
+            },
+        )?
+        .into_any(),
+        Output::Module(module) => {
+            let exports = PyDict::new(py);
+
+            for name in module.exports.iter() {
+                if let Some(value) = module.context.lookup(*name) {
+                    exports.set_item(name.as_str(), to_python(py, value, session)?)?;
+                }
+            }
+
+            exports.into_any().unbind()
+        }
+        Output::Thunk(_) | Output::Task(_) | Output::Channel(_) | Output::Void => py.None(),
+    })
+}
+
+fn run(py: Python<'_>, prog: File) -> PyResult<Py<PyAny>> {
+    let session = Rc::new(RefCell::new(Session {
+        interpreter: Interpreter::default(),
+        context: Context::default(),
+    }));
+
+    let res = {
+        let Session {
+            interpreter,
+            context,
+        } = &mut *session.borrow_mut();
+
+        interpreter
+            .eval(&prog.expression, context)
+            .map_err(|err| RinhaError::new_err(err.to_string()))?
+    };
+
+    to_python(py, res, &session)
+}
+
+/// Evaluates a program given as its JSON AST.
+#[pyfunction]
+fn eval(py: Python<'_>, json: &str) -> PyResult<Py<PyAny>> {
+    let prog = parse_json(json).map_err(|err| RinhaError::new_err(malformed::explain(json, &err)))?;
+
+    run(py, prog)
+}
+
+/// Evaluates a program given as rinha source code.
+#[pyfunction]
+fn eval_source(py: Python<'_>, source: &str) -> PyResult<Py<PyAny>> {
+    let prog = parser::parse(source, "<string>").map_err(|err| RinhaError::new_err(err.to_string()))?;
+
+    run(py, prog)
+}
+
+#[pymodule]
+fn rinha(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(eval, m)?)?;
+    m.add_function(wrap_pyfunction!(eval_source, m)?)?;
+    m.add_class::<PyClosure>()?;
+    m.add("RinhaError", m.py().get_type::<RinhaError>())?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "python"))]
+mod tests {
+    use super::*;
+
+    fn source<'py>(py: Python<'py>, source: &str) -> Bound<'py, PyAny> {
+        eval_source(py, source).unwrap().into_bound(py)
+    }
+
+    #[test]
+    fn values() {
+        Python::initialize();
+        Python::attach(|py| {
+            let res = source(py, "(1, \"a\", true, 123456789012345678901234567890, print(\"\"))");
+
+            assert_eq!(res.repr().unwrap().to_string(), "(1, 'a', True, 123456789012345678901234567890, None)");
+            assert_eq!(to_output(&res).unwrap().to_string(), "(1, a, true, 123456789012345678901234567890, )");
+            assert!(to_output(&py.eval(c"[1]", None, None).unwrap()).is_err());
+        });
+    }
+
+    #[test]
+    fn closures() {
+        Python::initialize();
+        Python::attach(|py| {
+            let rest = source(py, "fn (x, ...rest) => { rest }");
+            let empty = rest.call1((1,)).unwrap();
+
+            assert_eq!(empty.repr().unwrap().to_string(), "()");
+
+            // tuples of any length go back in, the ones rest parameters make included
+            let single = rest.call1((1, &empty)).unwrap();
+
+            assert_eq!(single.repr().unwrap().to_string(), "((),)");
+            assert_eq!(rest.call1((&single, &single)).unwrap().repr().unwrap().to_string(), "(((),),)");
+
+            let add = source(py, "fn (a) => { fn (b) => { a + b } }");
+            let inc = add.call1((1,)).unwrap();
+
+            assert_eq!(inc.repr().unwrap().to_string(), "<#closure/1>");
+            assert_eq!(inc.call1((41,)).unwrap().extract::<i64>().unwrap(), 42);
+
+            let apply = source(py, "fn (f, x) => { f(x) }");
+
+            assert_eq!(apply.call1((&inc, 1)).unwrap().extract::<i64>().unwrap(), 2);
+            assert!(inc.call1(("a", "b")).unwrap_err().is_instance_of::<RinhaError>(py));
+        });
+    }
+}
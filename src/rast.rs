@@ -0,0 +1,651 @@
+//! Precompiled ASTs, the `.rast` files `interpreter precompile` writes. A
+//! node is a tag byte followed by its fields, numbers are LEB128 varints and
+//! every name, string and filename is stored once in a table up front, so
+//! reading a program back interns each name once and parses no text.
+
+use num_bigint::BigInt;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{ast::*, stack, symbol::Symbol};
+
+const MAGIC: &[u8] = b"RAST";
+
+/// Bumped whenever the layout changes, files of another version are refused
+/// rather than misread.
+const VERSION: u8 = 1;
+
+const OPS: [BinaryOp; 20] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::Rem,
+    BinaryOp::Eq,
+    BinaryOp::Neq,
+    BinaryOp::Lt,
+    BinaryOp::Gt,
+    BinaryOp::Lte,
+    BinaryOp::Gte,
+    BinaryOp::And,
+    BinaryOp::Or,
+    BinaryOp::Pipe,
+    BinaryOp::Compose,
+    BinaryOp::BitAnd,
+    BinaryOp::BitOr,
+    BinaryOp::BitXor,
+    BinaryOp::Shl,
+    BinaryOp::Shr,
+];
+
+const ACCESS: u8 = 0;
+const BINARY: u8 = 1;
+const BOOL: u8 = 2;
+const CALL: u8 = 3;
+const FIRST: u8 = 4;
+const FUNCTION: u8 = 5;
+const IF: u8 = 6;
+const IMPORT: u8 = 7;
+const INT: u8 = 8;
+const LET: u8 = 9;
+const PRINT: u8 = 10;
+const RECUR: u8 = 11;
+const SECOND: u8 = 12;
+const STR: u8 = 13;
+const TUPLE: u8 = 14;
+const VAR: u8 = 15;
+const SEQ: u8 = 16;
+const TRY: u8 = 17;
+
+/// Pattern tags, read where a pattern is expected.
+const PATTERN_VAR: u8 = 0;
+const PATTERN_TUPLE: u8 = 1;
+
+#[derive(Default)]
+struct Encoder<'a> {
+    strings: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+    out: Vec<u8>,
+}
+
+fn varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+
+    out.push(n as u8);
+}
+
+impl<'a> Encoder<'a> {
+    fn number(&mut self, n: usize) {
+        varint(&mut self.out, n);
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.out.push(u8::from(flag));
+    }
+
+    fn string(&mut self, text: &'a str) {
+        let next = self.strings.len();
+        let index = *self.indices.entry(text).or_insert(next);
+
+        if index == next {
+            self.strings.push(text);
+        }
+
+        self.number(index);
+    }
+
+    fn optional(&mut self, text: Option<&'a str>) {
+        match text {
+            Some(text) => {
+                self.flag(true);
+                self.string(text);
+            }
+            None => self.flag(false),
+        }
+    }
+
+    fn location(&mut self, location: &'a Location) {
+        self.string(&location.filename);
+        self.number(location.start);
+        self.number(location.end);
+    }
+
+    fn parameter(&mut self, param: &'a Parameter) {
+        self.string(param.text.as_str());
+        self.location(&param.location);
+        self.flag(param.rest);
+
+        match &param.default {
+            Some(default) => {
+                self.flag(true);
+                self.term(default);
+            }
+            None => self.flag(false),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &'a Pattern) {
+        match pattern {
+            Pattern::Var(x) => {
+                self.out.push(PATTERN_VAR);
+                self.parameter(x);
+            }
+            Pattern::Tuple(x) => {
+                self.out.push(PATTERN_TUPLE);
+                self.number(x.elements.len());
+                x.elements.iter().for_each(|element| self.pattern(element));
+                self.location(&x.location);
+            }
+        }
+    }
+
+    fn terms(&mut self, terms: &'a [Term]) {
+        self.number(terms.len());
+        terms.iter().for_each(|term| self.term(term));
+    }
+
+    fn call(&mut self, x: &'a Call) {
+        self.term(&x.callee);
+        self.terms(&x.arguments);
+        self.number(x.named.len());
+
+        for arg in &x.named {
+            self.string(arg.name.as_str());
+            self.term(&arg.value);
+            self.location(&arg.location);
+        }
+    }
+
+    fn term(&mut self, term: &'a Term) {
+        match term {
+            Term::Access(x) => {
+                self.out.push(ACCESS);
+                self.term(&x.module);
+                self.string(&x.name);
+            }
+            Term::Binary(x) => {
+                self.out.push(BINARY);
+                self.term(&x.lhs);
+                self.out.push(OPS.iter().position(|op| *op == x.op).unwrap_or_default() as u8);
+                self.term(&x.rhs);
+            }
+            Term::Bool(x) => {
+                self.out.push(BOOL);
+                self.flag(x.value);
+            }
+            Term::Call(x) => {
+                self.out.push(CALL);
+                self.call(x);
+            }
+            Term::First(x) => {
+                self.out.push(FIRST);
+                self.term(&x.value);
+            }
+            Term::Function(x) => {
+                self.out.push(FUNCTION);
+                self.number(x.parameters.len());
+                x.parameters.iter().for_each(|param| self.parameter(param));
+                self.term(&x.value);
+            }
+            Term::If(x) => {
+                self.out.push(IF);
+                self.term(&x.condition);
+                self.term(&x.then);
+                self.term(&x.otherwise);
+            }
+            Term::Import(x) => {
+                self.out.push(IMPORT);
+                self.string(&x.path);
+                self.optional(x.alias.as_deref());
+            }
+            Term::Int(x) => {
+                let bytes = x.value.to_signed_bytes_le();
+
+                self.out.push(INT);
+                self.number(bytes.len());
+                self.out.extend_from_slice(&bytes);
+            }
+            Term::Let(x) => {
+                self.out.push(LET);
+                self.parameter(&x.name);
+
+                match &x.pattern {
+                    Some(pattern) => {
+                        self.flag(true);
+                        self.pattern(pattern);
+                    }
+                    None => self.flag(false),
+                }
+
+                self.term(&x.value);
+                self.term(&x.next);
+                self.flag(x.export);
+            }
+            Term::Print(x) => {
+                self.out.push(PRINT);
+                self.term(&x.value);
+            }
+            Term::Recur(x) => {
+                self.out.push(RECUR);
+                self.call(x);
+            }
+            Term::Second(x) => {
+                self.out.push(SECOND);
+                self.term(&x.value);
+            }
+            Term::Str(x) => {
+                self.out.push(STR);
+                self.string(&x.value);
+            }
+            Term::Seq(x) => {
+                self.out.push(SEQ);
+                self.terms(&x.expressions);
+            }
+            Term::Try(x) => {
+                self.out.push(TRY);
+                self.term(&x.body);
+                self.term(&x.handler);
+            }
+            Term::Tuple(x) => {
+                self.out.push(TUPLE);
+                self.terms(&x.elements);
+            }
+            Term::Var(x) => {
+                self.out.push(VAR);
+                self.string(x.text.as_str());
+            }
+        }
+
+        self.location(term.location());
+    }
+}
+
+/// `file` in the precompiled format.
+pub fn encode(file: &File) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+
+    encoder.string(&file.name);
+    encoder.location(&file.location);
+    encoder.term(&file.expression);
+
+    let mut out = Vec::from(MAGIC);
+
+    out.push(VERSION);
+    varint(&mut out, encoder.strings.len());
+
+    for text in &encoder.strings {
+        varint(&mut out, text.len());
+        out.extend_from_slice(text.as_bytes());
+    }
+
+    out.extend(encoder.out);
+    out
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    strings: Vec<&'a str>,
+    symbols: Vec<Option<Symbol>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.at).ok_or("Truncated precompiled AST")?;
+
+        self.at += 1;
+
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .at
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.at..end))
+            .ok_or("Truncated precompiled AST")?;
+
+        self.at += len;
+
+        Ok(bytes)
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let mut n = 0usize;
+
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+
+            n |= usize::from(byte & 0x7f).checked_shl(shift).unwrap_or_default();
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(String::from("Number out of range in precompiled AST"))
+    }
+
+    /// Length of a list, each item taking a byte at least.
+    fn count(&mut self) -> Result<usize, String> {
+        let len = self.number()?;
+
+        if len > self.bytes.len() - self.at {
+            return Err(String::from("Truncated precompiled AST"));
+        }
+
+        Ok(len)
+    }
+
+    fn flag(&mut self) -> Result<bool, String> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(format!("Invalid flag {} in precompiled AST", byte)),
+        }
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        let index = self.number()?;
+
+        if index >= self.strings.len() {
+            return Err(format!("String {} is not in the table of the precompiled AST", index));
+        }
+
+        Ok(index)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let index = self.index()?;
+
+        Ok(String::from(self.strings[index]))
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, String> {
+        let index = self.index()?;
+        let symbol = self.symbols[index].get_or_insert_with(|| Symbol::new(self.strings[index]));
+
+        Ok(*symbol)
+    }
+
+    fn optional(&mut self) -> Result<Option<String>, String> {
+        match self.flag()? {
+            true => self.string().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn location(&mut self) -> Result<Location, String> {
+        Ok(Location {
+            filename: self.string()?,
+            start: self.number()?,
+            end: self.number()?,
+        })
+    }
+
+    fn boxed(&mut self) -> Result<Box<Term>, String> {
+        self.term().map(Box::new)
+    }
+
+    fn terms(&mut self) -> Result<Vec<Term>, String> {
+        let len = self.count()?;
+
+        (0..len).map(|_| self.term()).collect()
+    }
+
+    fn parameter(&mut self) -> Result<Parameter, String> {
+        Ok(Parameter {
+            text: self.symbol()?,
+            location: self.location()?,
+            rest: self.flag()?,
+            default: match self.flag()? {
+                true => Some(self.boxed()?),
+                false => None,
+            },
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        if stack::exhausted() {
+            return Err(String::from("Pattern nests too deep"));
+        }
+
+        match self.byte()? {
+            PATTERN_VAR => self.parameter().map(Pattern::Var),
+            PATTERN_TUPLE => {
+                let len = self.count()?;
+                let elements = (0..len).map(|_| self.pattern()).collect::<Result<_, _>>()?;
+
+                Ok(Pattern::Tuple(TuplePattern {
+                    elements,
+                    location: self.location()?,
+                }))
+            }
+            tag => Err(format!("Unknown pattern tag {} in precompiled AST", tag)),
+        }
+    }
+
+    fn call(&mut self) -> Result<Call, String> {
+        let callee = self.boxed()?;
+        let arguments = self.terms()?;
+        let len = self.count()?;
+        let named = (0..len)
+            .map(|_| {
+                Ok(NamedArgument {
+                    name: self.symbol()?,
+                    value: self.term()?,
+                    location: self.location()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Call {
+            callee,
+            arguments,
+            named,
+            location: self.location()?,
+        })
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        if stack::exhausted() {
+            return Err(String::from("Expression nests too deep"));
+        }
+
+        let term = match self.byte()? {
+            ACCESS => Term::Access(Access {
+                module: self.boxed()?,
+                name: self.string()?,
+                location: self.location()?,
+            }),
+            BINARY => Term::Binary(Binary {
+                lhs: self.boxed()?,
+                op: match OPS.get(usize::from(self.byte()?)) {
+                    Some(op) => op.clone(),
+                    None => return Err(String::from("Unknown operator in precompiled AST")),
+                },
+                rhs: self.boxed()?,
+                location: self.location()?,
+            }),
+            BOOL => Term::Bool(Bool {
+                value: self.flag()?,
+                location: self.location()?,
+            }),
+            CALL => Term::Call(self.call()?),
+            FIRST => Term::First(First {
+                value: self.boxed()?,
+                location: self.location()?,
+            }),
+            FUNCTION => {
+                let len = self.count()?;
+                let parameters = (0..len).map(|_| self.parameter()).collect::<Result<_, _>>()?;
+
+                Term::Function(Function {
+                    parameters,
+                    value: Arc::new(self.term()?),
+                    location: self.location()?,
+                })
+            }
+            IF => Term::If(If {
+                condition: self.boxed()?,
+                then: self.boxed()?,
+                otherwise: self.boxed()?,
+                location: self.location()?,
+            }),
+            IMPORT => Term::Import(Import {
+                path: self.string()?,
+                alias: self.optional()?,
+                location: self.location()?,
+            }),
+            INT => {
+                let len = self.number()?;
+
+                Term::Int(Int {
+                    value: BigInt::from_signed_bytes_le(self.take(len)?),
+                    location: self.location()?,
+                })
+            }
+            LET => Term::Let(Let {
+                name: self.parameter()?,
+                pattern: match self.flag()? {
+                    true => Some(self.pattern()?),
+                    false => None,
+                },
+                value: self.boxed()?,
+                next: self.boxed()?,
+                export: self.flag()?,
+                location: self.location()?,
+            }),
+            PRINT => Term::Print(Print {
+                value: self.boxed()?,
+                location: self.location()?,
+            }),
+            RECUR => Term::Recur(self.call()?),
+            SECOND => Term::Second(Second {
+                value: self.boxed()?,
+                location: self.location()?,
+            }),
+            STR => Term::Str(Str {
+                value: self.string()?,
+                location: self.location()?,
+            }),
+            SEQ => {
+                let expressions = self.terms()?;
+
+                if expressions.is_empty() {
+                    return Err(String::from("Empty sequence in precompiled AST"));
+                }
+
+                Term::Seq(Seq {
+                    expressions,
+                    location: self.location()?,
+                })
+            }
+            TRY => Term::Try(Try {
+                body: self.boxed()?,
+                handler: self.boxed()?,
+                location: self.location()?,
+            }),
+            TUPLE => Term::Tuple(Tuple {
+                elements: self.terms()?,
+                location: self.location()?,
+            }),
+            VAR => Term::Var(Var {
+                text: self.symbol()?,
+                location: self.location()?,
+            }),
+            tag => return Err(format!("Unknown node tag {} in precompiled AST", tag)),
+        };
+
+        Ok(term)
+    }
+}
+
+/// The file `encode` wrote into `bytes`.
+pub fn decode(bytes: &[u8]) -> Result<File, String> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err(String::from("Not a precompiled AST"));
+    };
+
+    let mut decoder = Decoder {
+        bytes: rest,
+        at: 0,
+        strings: Vec::new(),
+        symbols: Vec::new(),
+    };
+
+    let version = decoder.byte()?;
+
+    if version != VERSION {
+        return Err(format!(
+            "Precompiled AST of version {}, this interpreter reads version {}, precompile it again",
+            version, VERSION
+        ));
+    }
+
+    let len = decoder.count()?;
+
+    for _ in 0..len {
+        let size = decoder.number()?;
+        let text = std::str::from_utf8(decoder.take(size)?).map_err(|err| err.to_string())?;
+
+        decoder.strings.push(text);
+    }
+
+    decoder.symbols = vec![None; decoder.strings.len()];
+
+    let file = File {
+        name: decoder.string()?,
+        location: decoder.location()?,
+        expression: decoder.term()?,
+    };
+
+    if decoder.at != rest.len() {
+        return Err(String::from("Trailing bytes after the precompiled AST"));
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_json, parser::parse};
+
+    #[test]
+    fn roundtrip() {
+        let source = "import \"m.rinha\" as m; let (a, b) = (1, \"two\"); let f = fn (x, y = 2, ...rest) => { x + y }; \
+                      let big = 123456789012345678901234567890; let _ = print(f(a, y = -3) |> g); \
+                      let c = try { a / 0 } catch m.e; let d = a & 3 | a ^ 1 <<< 2 >>> 1; \
+                      if (true) { m.h } else { (first((a, b)), second((a, b)), big) }";
+        let prog = parse(source, "a.rinha").unwrap();
+        let decoded = decode(&encode(&prog)).unwrap();
+
+        assert_eq!(decoded.name, prog.name);
+        assert_eq!(decoded.location, prog.location);
+        assert_eq!(decoded.expression, prog.expression);
+
+        let prog = parse_json(&std::fs::read_to_string("./json/fib.json").unwrap()).unwrap();
+
+        assert_eq!(decode(&encode(&prog)).unwrap().expression, prog.expression);
+    }
+
+    #[test]
+    fn malformed() {
+        let prog = parse("let x = 1; print(x)", "a.rinha").unwrap();
+        let bytes = encode(&prog);
+
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err());
+        }
+
+        let mut newer = bytes.clone();
+
+        newer[MAGIC.len()] = VERSION + 1;
+
+        assert!(decode(&newer).unwrap_err().starts_with("Precompiled AST of version 2"));
+        assert_eq!(decode(b"{}").unwrap_err(), "Not a precompiled AST");
+    }
+}
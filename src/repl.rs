@@ -0,0 +1,324 @@
+//! Interactive sessions: each entry is parsed as rinha source and evaluated
+//! in a scope kept across entries, so its `let`s stay bound for the next ones.
+//! Line editing and history are up to the front-end, see `main.rs`.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    ast::{Location, Term},
+    format::quote,
+    host::Host,
+    parser::parse,
+    Context, Error, ErrorKind, Interpreter, Output,
+};
+
+/// What the session answers to an entry.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// The value of an expression, as printed.
+    Value(String),
+    /// A failure, rendered with the line and column of the entry or file.
+    Error(String),
+    /// The entry only bound names or ran a command with nothing to show.
+    Done,
+    Quit,
+}
+
+pub const HELP: &str = "\
+:load FILE  evaluate the bindings of a program into the session
+:env        list the bindings of the session and their values
+:type EXPR  show what EXPR evaluates to, as the checker infers it
+:help       show this message
+:quit       leave, same as Ctrl-D";
+
+/// Characters of a value `:env` shows before cutting it short.
+const PREVIEW: usize = 60;
+
+/// `value` as `:env` lists it, strings quoted so they are told apart from
+/// other values.
+fn preview(value: &Output) -> String {
+    let text = match value {
+        Output::Str(x) => quote(x),
+        value => value.to_string(),
+    };
+
+    if text.chars().count() <= PREVIEW {
+        return text;
+    }
+
+    text.chars().take(PREVIEW).chain("...".chars()).collect()
+}
+
+pub struct Session<H: Host> {
+    interpreter: Interpreter<H>,
+    context: Context,
+    entries: usize,
+    /// Text of each entry and loaded file, by the filename errors carry.
+    sources: HashMap<String, String>,
+}
+
+impl<H: Host + 'static> Session<H> {
+    pub fn new(interpreter: Interpreter<H>, context: Context) -> Self {
+        Self {
+            interpreter,
+            context,
+            entries: 0,
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<H> {
+        &mut self.interpreter
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Name errors give the entry `input`, kept so they can be rendered.
+    fn filename(&mut self, input: &str) -> String {
+        self.entries += 1;
+
+        let filename = format!("<repl:{}>", self.entries);
+
+        self.sources.insert(filename.clone(), String::from(input));
+
+        filename
+    }
+
+    fn render(&self, err: &Error) -> String {
+        match self.sources.get(&err.filename) {
+            Some(source) => err.render(source),
+            None => err.to_string(),
+        }
+    }
+
+    /// Evaluates `input`, a complete entry as told by `incomplete`, or runs
+    /// it when it is a `:command`.
+    pub fn entry(&mut self, input: &str) -> Reply {
+        let input = input.trim();
+
+        if let Some(command) = input.strip_prefix(':') {
+            let (name, arg) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+
+            return self.command(name, arg.trim());
+        }
+
+        if input.is_empty() {
+            return Reply::Done;
+        }
+
+        let filename = self.filename(input);
+        let (prog, bindings) = match parse(input, &filename) {
+            Ok(prog) => (prog, false),
+            // `let x = 1` on its own binds `x` for the entries to come
+            Err(err) => match parse(&format!("{}\n0", input), &filename) {
+                Ok(prog) if matches!(prog.expression, Term::Let(_)) => (prog, true),
+                _ => return Reply::Error(self.render(&err)),
+            },
+        };
+
+        match self.interpreter.eval(&prog.expression, &mut self.context) {
+            Ok(_) if bindings => Reply::Done,
+            Ok(Output::Void) => Reply::Done,
+            Ok(value) => Reply::Value(value.to_string()),
+            Err(err) if matches!(err.kind, ErrorKind::Exit(_)) => Reply::Quit,
+            Err(err) => Reply::Error(self.render(&err)),
+        }
+    }
+
+    fn command(&mut self, name: &str, arg: &str) -> Reply {
+        match name {
+            "load" | "l" if !arg.is_empty() => self.load(Path::new(arg)),
+            "load" | "l" => Reply::Error(String::from(":load takes the path of a program")),
+            "env" | "e" => Reply::Value(self.env()),
+            "type" | "t" if !arg.is_empty() => self.kind(arg),
+            "type" | "t" => Reply::Error(String::from(":type takes an expression")),
+            "help" | "h" | "?" => Reply::Value(String::from(HELP)),
+            "quit" | "q" => Reply::Quit,
+            _ => Reply::Error(format!("Unknown command :{}, see :help", name)),
+        }
+    }
+
+    fn env(&self) -> String {
+        let mut bindings = self.context.bindings();
+
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let lines: Vec<String> = bindings
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, preview(value)))
+            .collect();
+
+        lines.join("\n")
+    }
+
+    /// What `input` evaluates to, as the checker infers it from the values
+    /// bound so far, without running it.
+    fn kind(&mut self, input: &str) -> Reply {
+        let filename = self.filename(input);
+        let prog = match parse(input, &filename) {
+            Ok(prog) => prog,
+            Err(err) => return Reply::Error(self.render(&err)),
+        };
+
+        match self.interpreter.infer(&prog.expression, &self.context) {
+            Ok(Some(kind)) => Reply::Value(kind.to_string()),
+            Ok(None) => Reply::Value(String::from("unknown until it runs")),
+            Err(errors) => {
+                let errors: Vec<String> = errors.iter().map(|err| self.render(err)).collect();
+
+                Reply::Error(errors.join("\n"))
+            }
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Reply {
+        let location = Location {
+            start: 0,
+            end: 0,
+            filename: path.display().to_string(),
+        };
+
+        if let Ok(source) = self.interpreter.host_mut().read_to_string(path) {
+            self.sources.insert(location.filename.clone(), source);
+        }
+
+        let res = self
+            .interpreter
+            .load(path, &location)
+            .and_then(|prog| self.interpreter.prelude(prog, &mut self.context));
+
+        match res {
+            Ok(()) => Reply::Done,
+            Err(err) => Reply::Error(self.render(&err)),
+        }
+    }
+}
+
+/// Whether `input` needs more lines: a bracket, string or comment is left
+/// open, or the last line ends in an operator.
+pub fn incomplete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            '"' => loop {
+                match chars.next() {
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some('"') => break,
+                    Some(_) => (),
+                    None => return true,
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                let mut star = false;
+
+                loop {
+                    match chars.next() {
+                        Some('/') if star => break,
+                        Some(c) => star = c == '*',
+                        None => return true,
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // more closing than opening brackets is an error for the parser to report
+    let trimmed = input.trim_end();
+    let operator = ["+", "-", "*", "/", "%", "=", "<", ">", "&&", "||", "|>", ">>", ",", "=>"]
+        .iter()
+        .any(|op| trimmed.ends_with(op));
+
+    depth > 0 || (operator && !trimmed.ends_with("*/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::{CaptureHost, StdHost};
+
+    fn session() -> Session<CaptureHost> {
+        Session::new(Interpreter::new(CaptureHost::default()), Context::default())
+    }
+
+    #[test]
+    fn bindings() {
+        let mut session = session();
+
+        assert_eq!(session.entry("let double = fn (n) => { n * 2 }"), Reply::Done);
+        assert_eq!(session.entry("let x = double(21); x + 0"), Reply::Value(String::from("42")));
+        assert_eq!(session.entry("x"), Reply::Value(String::from("42")));
+        assert_eq!(session.entry("print(x)"), Reply::Done);
+        assert_eq!(session.interpreter_mut().host().output(), "42\n");
+        assert_eq!(
+            session.entry("let y = 1;\nx + z"),
+            Reply::Error(String::from("<repl:5>:2:5: Variable z is not declared"))
+        );
+        assert_eq!(session.entry(":nope"), Reply::Error(String::from("Unknown command :nope, see :help")));
+        assert_eq!(session.entry(":q"), Reply::Quit);
+        assert_eq!(session.entry("exit(0)"), Reply::Quit);
+    }
+
+    #[test]
+    fn load() {
+        let mut session = Session::new(Interpreter::new(StdHost::default()), Context::default());
+
+        // the bindings only, the print ending the file is not evaluated
+        assert_eq!(session.entry(":load ./json/modules/math.rinha"), Reply::Done);
+        assert!(session.context().contains("add"));
+        assert_eq!(session.entry("double(4)"), Reply::Value(String::from("8")));
+        assert!(matches!(session.entry(":load ./missing.rinha"), Reply::Error(_)));
+    }
+
+    #[test]
+    fn inspection() {
+        let mut session = session();
+
+        session.entry("let add = fn (a, b) => { a + b }");
+        session.entry("let name = \"rinha\"");
+        session.entry(&format!("let long = \"{}\"", "x".repeat(80)));
+
+        let Reply::Value(env) = session.entry(":env") else {
+            panic!("no bindings listed");
+        };
+
+        assert_eq!(
+            env,
+            format!("add = <#closure/2>\nlong = \"{}...\nname = \"rinha\"", "x".repeat(59))
+        );
+        assert_eq!(session.entry(":type add"), Reply::Value(String::from("closure/2")));
+        assert_eq!(session.entry(":type name + 1"), Reply::Value(String::from("str")));
+        assert_eq!(session.entry(":type first((1, 2))"), Reply::Value(String::from("unknown until it runs")));
+        assert_eq!(
+            session.entry(":type name - 1"),
+            Reply::Error(String::from("<repl:7>:1:1: Cannot perform sub operation"))
+        );
+        // nothing ran
+        assert_eq!(session.entry(":type print(1)"), Reply::Value(String::from("unknown until it runs")));
+        assert_eq!(session.interpreter_mut().host().output(), "");
+    }
+
+    #[test]
+    fn continuation() {
+        assert!(incomplete("let f = fn (n) => {"));
+        assert!(incomplete("let s = \"open"));
+        assert!(incomplete("1 +"));
+        assert!(incomplete("/* note"));
+        assert!(!incomplete("let f = fn (n) => { n }"));
+        assert!(!incomplete("\"{\" // {"));
+        assert!(!incomplete(")"));
+    }
+}
@@ -0,0 +1,404 @@
+//! Newline-delimited JSON-RPC 2.0 service, one request per line on the input
+//! and one response per line on the output.
+
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, Write},
+    panic,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    ast::{File, Position},
+    format,
+    host::CaptureHost,
+    lint, malformed, parser,
+    task::STACK,
+    Context, Error, Interpreter,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const PROGRAM_ERROR: i64 = 1;
+
+struct Failure {
+    code: i64,
+    message: String,
+    data: Value,
+}
+
+impl Failure {
+    fn new(code: i64, message: &str) -> Self {
+        Self {
+            code,
+            message: String::from(message),
+            data: Value::Null,
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Self {
+            code: PROGRAM_ERROR,
+            message: err.message.clone(),
+            data: diagnostic(&err),
+        }
+    }
+}
+
+fn diagnostic(err: &Error) -> Value {
+    json!({
+        "filename": err.filename,
+        "start": err.start,
+        "end": err.end,
+        "message": err.message,
+    })
+}
+
+/// Adds the 1-based `line` and `column` where a diagnostic starts when it
+/// points into the `source` of the request.
+fn locate(diagnostic: &mut Value, params: &Value) {
+    let Some(source) = params.get("source").and_then(Value::as_str) else {
+        return;
+    };
+    let filename = params.get("filename").and_then(Value::as_str).unwrap_or("<rpc>");
+
+    if diagnostic["filename"] != filename {
+        return;
+    }
+
+    if let Some(at) = diagnostic["start"].as_u64().and_then(|start| Position::of(source, start as usize)) {
+        diagnostic["line"] = Value::from(at.line);
+        diagnostic["column"] = Value::from(at.column);
+    }
+}
+
+/// Programs are given either as a JSON AST in `program` or as rinha source
+/// code in `source`.
+fn load(params: &Value) -> Result<File, Failure> {
+    if let Some(program) = params.get("program") {
+        serde_json::from_value::<File>(program.clone()).map_err(|err| {
+            let reason = malformed::fault(program).unwrap_or_else(|| err.to_string());
+
+            Failure::new(INVALID_PARAMS, &format!("Invalid program: {}", reason))
+        })
+    } else if let Some(source) = params.get("source") {
+        let source = source
+            .as_str()
+            .ok_or_else(|| Failure::new(INVALID_PARAMS, "Expected source to be a string"))?;
+        let filename = params
+            .get("filename")
+            .and_then(Value::as_str)
+            .unwrap_or("<rpc>");
+
+        Ok(parser::parse(source, filename)?)
+    } else {
+        Err(Failure::new(INVALID_PARAMS, "Expected either a program or a source"))
+    }
+}
+
+fn eval(params: &Value) -> Result<Value, Failure> {
+    let prog = load(params)?;
+    let mut interpreter = Interpreter::new(CaptureHost::default());
+
+    match interpreter.eval(&prog.expression, &mut Context::default()) {
+        Ok(res) => Ok(json!({
+            "value": res.to_string(),
+            "output": interpreter.host().output(),
+        })),
+        Err(err) => {
+            let mut failure = Failure::from(err);
+            failure.data["output"] = Value::from(interpreter.host().output());
+
+            Err(failure)
+        }
+    }
+}
+
+/// Errors stopping the program from loading or running go in `diagnostics`,
+/// all of them sorted by location, lints in `warnings`.
+fn check(params: &Value) -> Result<Value, Failure> {
+    let (mut diagnostics, mut warnings): (Vec<Value>, Vec<Value>) = match load(params) {
+        Ok(prog) => {
+            let diagnostics = Interpreter::new(CaptureHost::default())
+                .check(&prog.expression, &Context::default())
+                .iter()
+                .map(diagnostic)
+                .collect();
+            let warnings = lint::warnings(&prog.expression)
+                .into_iter()
+                .map(|warning| {
+                    json!({
+                        "filename": warning.location.filename,
+                        "start": warning.location.start,
+                        "end": warning.location.end,
+                        "message": warning.message,
+                    })
+                })
+                .collect();
+
+            (diagnostics, warnings)
+        }
+        Err(failure) if failure.code == PROGRAM_ERROR => (vec![failure.data], vec![]),
+        Err(failure) => return Err(failure),
+    };
+
+    diagnostics
+        .iter_mut()
+        .chain(warnings.iter_mut())
+        .for_each(|diagnostic| locate(diagnostic, params));
+
+    Ok(json!({ "diagnostics": diagnostics, "warnings": warnings }))
+}
+
+fn handle(request: &Value) -> Result<Value, Failure> {
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Failure::new(INVALID_REQUEST, "Missing method"))?;
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+    let result = match method {
+        "eval" => eval(&params),
+        "check" => check(&params),
+        "format" => Ok(json!({ "source": format::format(&load(&params)?.expression) })),
+        _ => Err(Failure::new(METHOD_NOT_FOUND, &format!("Unknown method {}", method))),
+    };
+
+    result.map_err(|mut failure| {
+        if failure.code == PROGRAM_ERROR {
+            locate(&mut failure.data, &params);
+        }
+
+        failure
+    })
+}
+
+fn respond(line: &str) -> Option<Value> {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request) => request,
+        Err(err) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": { "code": PARSE_ERROR, "message": err.to_string() },
+            }))
+        }
+    };
+
+    // requests without an id are notifications and get no response
+    let id = request.get("id")?.clone();
+
+    Some(match handle(&request) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(failure) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": failure.code, "message": failure.message, "data": failure.data },
+        }),
+    })
+}
+
+/// Serves requests until `input` is exhausted, flushing after every response.
+/// Requests are handled by a pool of as many threads as the machine runs at
+/// once, so a slow evaluation does not hold up the ones after it and
+/// responses come as they are ready.
+pub fn serve(input: impl BufRead, output: impl Write + Send) -> io::Result<()> {
+    let workers = thread::available_parallelism().map_or(1, usize::from);
+
+    dispatch(input, output, workers, respond)
+}
+
+/// Hands the lines of `input` to `workers` threads answering them with
+/// `respond`. Reading stops while every worker is busy and a line is already
+/// waiting for one, and for good once every worker failed to write.
+fn dispatch(
+    input: impl BufRead,
+    output: impl Write + Send,
+    workers: usize,
+    respond: impl Fn(&str) -> Option<Value> + Sync,
+) -> io::Result<()> {
+    let output = Mutex::new(output);
+    let (lines, queue) = mpsc::sync_channel::<String>(1);
+    // dropped with the last worker, so sending fails once none is left
+    let queue = Arc::new(Mutex::new(queue));
+
+    thread::scope(|scope| {
+        let work = |queue: Arc<Mutex<Receiver<String>>>| loop {
+            let Ok(line) = queue.lock().unwrap_or_else(|err| err.into_inner()).recv() else {
+                return Ok(());
+            };
+            let Some(response) = respond(&line) else {
+                continue;
+            };
+            let mut output = output.lock().unwrap_or_else(|err| err.into_inner());
+
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        };
+        let handlers = (0..workers)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+
+                thread::Builder::new().stack_size(STACK).spawn_scoped(scope, move || work(queue))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        drop(queue);
+
+        // an error returns with `lines` dropped, so the workers stop
+        // before the scope waits for them
+        for line in input.lines() {
+            let line = line?;
+
+            // every worker failed to write its response
+            if !line.trim().is_empty() && lines.send(line).is_err() {
+                break;
+            }
+        }
+
+        drop(lines);
+
+        handlers
+            .into_iter()
+            .try_for_each(|handler| handler.join().unwrap_or_else(|panic| panic::resume_unwind(panic)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    fn call(request: &str) -> Value {
+        let mut output = Vec::new();
+
+        serve(request.as_bytes(), &mut output).unwrap();
+
+        serde_json::from_slice(&output).unwrap()
+    }
+
+    #[test]
+    fn eval() {
+        let res = call(r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"source": "let _ = print(1 + 2); 4"}}"#);
+
+        assert_eq!(res["id"], 1);
+        assert_eq!(res["result"]["value"], "4");
+        assert_eq!(res["result"]["output"], "3\n");
+    }
+
+    #[test]
+    fn eval_program() {
+        let program = std::fs::read_to_string("./json/fib.json").unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "eval", "params": { "program": serde_json::from_str::<Value>(&program).unwrap() } });
+
+        let res = call(&request.to_string());
+
+        assert_eq!(res["result"]["value"], "55");
+    }
+
+    #[test]
+    fn check() {
+        let res = call(r#"{"jsonrpc": "2.0", "id": 3, "method": "check", "params": {"source": "let x = ;"}}"#);
+
+        assert_eq!(res["result"]["diagnostics"][0]["start"], 8);
+
+        let res = call(r#"{"jsonrpc": "2.0", "id": 3, "method": "check", "params": {"source": "let x = 1;\n\nlet y = ;"}}"#);
+
+        assert_eq!(res["result"]["diagnostics"][0]["line"], 3);
+        assert_eq!(res["result"]["diagnostics"][0]["column"], 9);
+
+        let res = call(r#"{"jsonrpc": "2.0", "id": 3, "method": "check", "params": {"source": "let x = 1; 2"}}"#);
+
+        assert_eq!(res["result"]["warnings"][0]["message"], "binding x is never read");
+
+        let res = call(r#"{"jsonrpc": "2.0", "id": 3, "method": "check", "params": {"source": "let _ = print(x); y - \"a\""}}"#);
+
+        assert_eq!(res["result"]["diagnostics"][0]["message"], "Variable x is not declared");
+        assert_eq!(res["result"]["diagnostics"][1]["message"], "Variable y is not declared");
+        assert_eq!(res["result"]["diagnostics"][2]["message"], "Cannot perform sub operation");
+    }
+
+    #[test]
+    fn errors() {
+        let res = call(r#"{"jsonrpc": "2.0", "id": 4, "method": "eval", "params": {"source": "1 / 0"}}"#);
+
+        assert_eq!(res["error"]["code"], PROGRAM_ERROR);
+        assert_eq!(res["error"]["message"], "Arithmetic error, dividing by zero");
+
+        let res = call(r#"{"jsonrpc": "2.0", "id": 5, "method": "run"}"#);
+
+        assert_eq!(res["error"]["code"], METHOD_NOT_FOUND);
+
+        let res = call("{");
+
+        assert_eq!(res["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn concurrent() {
+        let (one, from_one) = mpsc::channel();
+        let (two, from_two) = mpsc::channel();
+        let (from_one, from_two) = (Mutex::new(from_one), Mutex::new(from_two));
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let mut output = Vec::new();
+
+        // 1 and 2 only finish once both are running, 3 waits for a worker
+        dispatch("1\n\n2\n3\n".as_bytes(), &mut output, 2, |line| {
+            peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+
+            // fails rather than hangs when the two never run at once
+            let meet = |tx: &mpsc::Sender<()>, rx: &Mutex<mpsc::Receiver<()>>| {
+                tx.send(()).unwrap();
+                rx.lock().unwrap().recv_timeout(Duration::from_secs(30)).unwrap();
+            };
+
+            match line {
+                "1" => meet(&one, &from_two),
+                "2" => meet(&two, &from_one),
+                _ => (),
+            }
+
+            active.fetch_sub(1, Ordering::SeqCst);
+
+            Some(json!(line))
+        })
+        .unwrap();
+
+        let mut ids: Vec<String> = String::from_utf8(output).unwrap().lines().map(String::from).collect();
+        ids.sort();
+
+        assert_eq!(ids, ["\"1\"", "\"2\"", "\"3\""]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closed() {
+        // more lines than the workers and the queue take before failing
+        let err = dispatch("1\n".repeat(100).as_bytes(), Closed, 2, |line| Some(json!(line))).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
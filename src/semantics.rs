@@ -0,0 +1,114 @@
+//! Behaviors where this crate goes beyond the rinha spec, grouped so the
+//! evaluator can follow the spec to the letter when asked to.
+
+use serde::Deserialize;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Exactly what the rinha spec describes.
+    Strict,
+    /// The spec along with this crate's additions.
+    #[default]
+    Extended,
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "strict" => Ok(Profile::Strict),
+            "extended" => Ok(Profile::Extended),
+            _ => Err(format!("Unknown profile {:?}, expected strict or extended", name)),
+        }
+    }
+}
+
+/// What to do when a program relies on a behavior some users would rather
+/// rule out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lint {
+    #[default]
+    Allow,
+    /// Collected as a warning, see `Interpreter::take_warnings`.
+    Warn,
+    /// Stops the program with an error.
+    Deny,
+}
+
+/// Settings read by the evaluator, see `Interpreter::set_semantics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Semantics {
+    /// `print(x)` evaluates to `x` as in the spec, rather than to nothing.
+    pub print_returns_value: bool,
+    /// `&&` and `||` take any value, passing the right-hand side through,
+    /// instead of bools only.
+    pub logic_on_any: bool,
+    /// Tuples of any size, the spec only has pairs.
+    pub tuples_of_any_size: bool,
+    /// `+` turning an int into a string to concatenate it.
+    pub coercion: Lint,
+    /// `if` takes conditions of any kind, `false`, `0`, `""` and nothing
+    /// count as false and everything else as true. Off in both profiles.
+    pub truthy_conditions: bool,
+    /// Int arithmetic on 64-bit operands leaving the 64-bit range, where a
+    /// fixed-width implementation would overflow. Ints never do here.
+    pub overflow: Lint,
+    /// Calling a closure with fewer arguments than parameters gives a closure
+    /// taking the rest. Off in both profiles.
+    pub partial_application: bool,
+    /// A function with two parameters of the same name, the last one getting
+    /// its argument. Denied by the strict profile.
+    pub duplicate_parameters: Lint,
+}
+
+impl Semantics {
+    pub fn strict() -> Self {
+        Self {
+            print_returns_value: true,
+            logic_on_any: false,
+            tuples_of_any_size: false,
+            coercion: Lint::Allow,
+            truthy_conditions: false,
+            overflow: Lint::Allow,
+            partial_application: false,
+            duplicate_parameters: Lint::Deny,
+        }
+    }
+
+    pub fn extended() -> Self {
+        Self {
+            print_returns_value: false,
+            logic_on_any: true,
+            tuples_of_any_size: true,
+            coercion: Lint::Allow,
+            truthy_conditions: false,
+            overflow: Lint::Allow,
+            partial_application: false,
+            duplicate_parameters: Lint::Warn,
+        }
+    }
+
+    /// Whether passing `given` arguments to a closure of `arity` parameters
+    /// partially applies it.
+    pub fn partial(&self, arity: usize, given: usize) -> bool {
+        self.partial_application && given > 0 && given < arity
+    }
+}
+
+impl Default for Semantics {
+    fn default() -> Self {
+        Self::extended()
+    }
+}
+
+impl From<Profile> for Semantics {
+    fn from(profile: Profile) -> Self {
+        match profile {
+            Profile::Strict => Self::strict(),
+            Profile::Extended => Self::extended(),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! In-language test runner: every top-level `let test_* = fn () => { .. }` of
+//! a spec file is a test case, failing when calling it is an error.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Location, Term},
+    host::Host,
+    Context, Error, Interpreter, Output,
+};
+
+pub struct Outcome {
+    pub name: String,
+    pub location: Location,
+    pub result: Result<(), Error>,
+}
+
+/// Spec files of `dir` (rinha sources and JSON ASTs) in a stable order.
+pub fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "rinha" || ext == "json") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+fn cases(term: &Term) -> Vec<(String, Location)> {
+    let mut cases = Vec::new();
+    let mut term = term;
+
+    while let Term::Let(x) = term {
+        if x.name.text.starts_with("test_") {
+            cases.push((x.name.text.to_string(), x.name.location.clone()));
+        }
+
+        term = &x.next;
+    }
+
+    cases
+}
+
+/// Runs the test cases of the file at `path`. Each case gets the interpreter
+/// and scope built by `setup` and evaluates the file's bindings from scratch,
+/// so cases cannot observe each other.
+pub fn run<H, F>(path: &Path, mut setup: F) -> Result<Vec<Outcome>, Error>
+where
+    H: Host + 'static,
+    F: FnMut() -> Result<(Interpreter<H>, Context), Error>,
+{
+    let location = Location {
+        start: 0,
+        end: 0,
+        filename: path.display().to_string(),
+    };
+
+    let (mut interpreter, _) = setup()?;
+    let prog = interpreter.load(path, &location)?;
+
+    let outcomes = cases(&prog.expression)
+        .into_iter()
+        .map(|(name, location)| {
+            let mut case = || {
+                let (mut interpreter, mut context) = setup()?;
+                let prog = interpreter.load(path, &location)?;
+
+                interpreter.prelude(prog, &mut context)?;
+
+                match context.lookup(name.as_str()) {
+                    Some(Output::Closure(closure)) => interpreter
+                        .apply(closure, vec![], &context, &location)
+                        .map(|_| ()),
+                    _ => Err(Error::new("Test cases must be functions", location.clone())),
+                }
+            };
+
+            Outcome {
+                result: case(),
+                name,
+                location,
+            }
+        })
+        .collect();
+
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::StdHost;
+
+    #[test]
+    fn spec() {
+        let outcomes = run(Path::new("./json/spec/math.rinha"), || {
+            Ok((Interpreter::new(StdHost::default()), Context::default()))
+        })
+        .unwrap();
+
+        let names: Vec<&str> = outcomes.iter().map(|x| x.name.as_str()).collect();
+
+        assert_eq!(names, ["test_double", "test_broken"]);
+        assert!(outcomes[0].result.is_ok());
+        assert_eq!(
+            outcomes[1].result.as_ref().unwrap_err().message,
+            "Assertion failed: double(2) is 5"
+        );
+    }
+}
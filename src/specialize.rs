@@ -0,0 +1,214 @@
+//! Fast paths for hot sites. While a program runs the evaluator records what
+//! each operator and call site sees, and once a site ran `HOT` times with the
+//! same kind of values it takes a shorter path guarded by a cheap check. A
+//! site failing its guard goes back to the generic path for good.
+
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::ast::{BinaryOp, Term};
+use crate::Output;
+
+/// Runs of a site before it gets specialized.
+pub(crate) const HOT: u32 = 16;
+
+/// What a binary operator was given so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operands {
+    /// Two ints on each of that many runs.
+    Warming(u32),
+    Int,
+    Generic,
+}
+
+/// What a call site called so far.
+#[derive(Debug, Clone)]
+pub(crate) enum Callee {
+    /// Closures of that body on each of that many runs.
+    Warming(u32, Arc<Term>),
+    Monomorphic(Arc<Term>),
+    Generic,
+}
+
+/// Profile of the sites run by an interpreter, by the address of their term.
+/// A term freed and its address reused only costs a failed guard.
+#[derive(Default)]
+pub(crate) struct Sites {
+    operands: HashMap<*const Term, Operands>,
+    callees: HashMap<*const Term, Callee>,
+}
+
+impl Sites {
+    pub(crate) fn operands(&self, site: &Term) -> Operands {
+        self.operands.get(&(site as *const Term)).copied().unwrap_or(Operands::Warming(0))
+    }
+
+    /// Records a run of `site` while it warms up, `ints` telling whether it
+    /// was given two ints.
+    pub(crate) fn observe_operands(&mut self, site: &Term, ints: bool) {
+        let state = self.operands.entry(site).or_insert(Operands::Warming(0));
+
+        *state = match *state {
+            Operands::Warming(_) if !ints => Operands::Generic,
+            Operands::Warming(runs) if runs + 1 >= HOT => Operands::Int,
+            Operands::Warming(runs) => Operands::Warming(runs + 1),
+            state => state,
+        };
+    }
+
+    /// Body every closure called from `site` had, once it is hot.
+    pub(crate) fn callee(&self, site: &Term) -> Option<&Arc<Term>> {
+        match self.callees.get(&(site as *const Term)) {
+            Some(Callee::Monomorphic(body)) => Some(body),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn observe_callee(&mut self, site: &Term, body: &Arc<Term>) {
+        let state = self.callees.entry(site).or_insert_with(|| Callee::Warming(0, body.clone()));
+
+        *state = match state {
+            Callee::Warming(runs, seen) if Arc::ptr_eq(seen, body) && *runs + 1 >= HOT => {
+                Callee::Monomorphic(body.clone())
+            }
+            Callee::Warming(runs, seen) if Arc::ptr_eq(seen, body) => Callee::Warming(*runs + 1, body.clone()),
+            Callee::Monomorphic(seen) if Arc::ptr_eq(seen, body) => return,
+            _ => Callee::Generic,
+        };
+    }
+
+    /// Sends `site` back to the generic path after its guard failed.
+    pub(crate) fn deoptimize(&mut self, site: &Term) {
+        if let Some(state) = self.operands.get_mut(&(site as *const Term)) {
+            *state = Operands::Generic;
+        }
+
+        if let Some(state) = self.callees.get_mut(&(site as *const Term)) {
+            *state = Callee::Generic;
+        }
+    }
+}
+
+/// Operators an int site is specialized for.
+pub(crate) fn arithmetic(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Add
+            | BinaryOp::Sub
+            | BinaryOp::Mul
+            | BinaryOp::Div
+            | BinaryOp::Rem
+            | BinaryOp::BitAnd
+            | BinaryOp::BitOr
+            | BinaryOp::BitXor
+            | BinaryOp::Eq
+            | BinaryOp::Neq
+            | BinaryOp::Lt
+            | BinaryOp::Gt
+            | BinaryOp::Lte
+            | BinaryOp::Gte
+    )
+}
+
+/// `a op b` in 64 bits, None when the operands or the result do not fit or
+/// the generic path has an error to report, such as dividing by zero.
+pub(crate) fn int(op: &BinaryOp, a: &BigInt, b: &BigInt) -> Option<Output> {
+    let compared = match op {
+        BinaryOp::Eq => Some(a == b),
+        BinaryOp::Neq => Some(a != b),
+        BinaryOp::Lt => Some(a < b),
+        BinaryOp::Gt => Some(a > b),
+        BinaryOp::Lte => Some(a <= b),
+        BinaryOp::Gte => Some(a >= b),
+        _ => None,
+    };
+
+    if let Some(res) = compared {
+        return Some(Output::Bool(res));
+    }
+
+    let (x, y) = (a.to_i64()?, b.to_i64()?);
+    let res = match op {
+        BinaryOp::Add => x.checked_add(y),
+        BinaryOp::Sub => x.checked_sub(y),
+        BinaryOp::Mul => x.checked_mul(y),
+        BinaryOp::Div if b.is_positive() => x.checked_div(y),
+        BinaryOp::Rem if b.is_positive() => x.checked_rem(y),
+        BinaryOp::BitAnd => Some(x & y),
+        BinaryOp::BitOr => Some(x | y),
+        BinaryOp::BitXor => Some(x ^ y),
+        _ => None,
+    }?;
+
+    Some(Output::Int(res.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Location, Var};
+
+    fn site() -> Term {
+        Term::Var(Var {
+            text: "site".into(),
+            location: Location {
+                start: 0,
+                end: 0,
+                filename: String::from("test"),
+            },
+        })
+    }
+
+    #[test]
+    fn operands() {
+        let (hot, mixed) = (site(), site());
+        let mut sites = Sites::default();
+
+        for _ in 0..HOT {
+            assert_ne!(sites.operands(&hot), Operands::Int);
+
+            sites.observe_operands(&hot, true);
+        }
+
+        sites.observe_operands(&mixed, true);
+        sites.observe_operands(&mixed, false);
+
+        assert_eq!(sites.operands(&hot), Operands::Int);
+        assert_eq!(sites.operands(&mixed), Operands::Generic);
+
+        sites.deoptimize(&hot);
+
+        assert_eq!(sites.operands(&hot), Operands::Generic);
+    }
+
+    #[test]
+    fn callee() {
+        let (site, body, other) = (site(), Arc::new(self::site()), Arc::new(self::site()));
+        let mut sites = Sites::default();
+
+        for _ in 0..HOT {
+            assert!(sites.callee(&site).is_none());
+
+            sites.observe_callee(&site, &body);
+        }
+
+        assert!(sites.callee(&site).is_some_and(|seen| Arc::ptr_eq(seen, &body)));
+
+        sites.observe_callee(&site, &other);
+
+        assert!(sites.callee(&site).is_none());
+    }
+
+    #[test]
+    fn int() {
+        let int = |op, a: i64, b: i64| super::int(&op, &a.into(), &b.into());
+
+        assert_eq!(int(BinaryOp::Add, 40, 2), Some(Output::Int(42.into())));
+        assert_eq!(int(BinaryOp::Rem, -7, 2), Some(Output::Int((-1).into())));
+        assert_eq!(int(BinaryOp::Lte, 2, 2), Some(Output::Bool(true)));
+        assert_eq!(int(BinaryOp::BitAnd, -4, 7), Some(Output::Int(4.into())));
+        assert_eq!(int(BinaryOp::Add, i64::MAX, 1), None);
+        assert_eq!(int(BinaryOp::Div, 1, 0), None);
+    }
+}
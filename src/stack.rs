@@ -0,0 +1,20 @@
+//! Programs nesting or recursing deeper than the stack of their thread allows
+//! fail with an error rather than aborting the whole process.
+
+/// Stack left when parsing or evaluating one more level gives up, room for
+/// the frames between two checks and for reporting the error, debug builds
+/// included.
+#[cfg(not(target_arch = "wasm32"))]
+const RED_ZONE: usize = 256 * 1024;
+
+/// Whether the current thread is about to run out of stack, never where the
+/// bounds of the stack are not known.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn exhausted() -> bool {
+    stacker::remaining_stack().is_some_and(|left| left < RED_ZONE)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn exhausted() -> bool {
+    false
+}
@@ -0,0 +1,92 @@
+//! Size metrics of a program's AST, reported by `interpreter stats`.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use crate::ast::Term;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Node count per `kind`, e.g. `Let` or `Call`.
+    pub kinds: BTreeMap<&'static str, usize>,
+    /// Nodes on the longest path from the root, the root alone being 1.
+    pub depth: usize,
+    /// Distinct names bound by `let`, patterns and parameters.
+    pub variables: usize,
+    /// Function literals, each one a closure once evaluated.
+    pub closures: usize,
+}
+
+impl Stats {
+    pub fn nodes(&self) -> usize {
+        self.kinds.values().sum()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nodes: {}", self.nodes())?;
+        writeln!(f, "depth: {}", self.depth)?;
+        writeln!(f, "variables: {}", self.variables)?;
+        writeln!(f, "closures: {}", self.closures)?;
+
+        for (kind, count) in &self.kinds {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn stats(term: &Term) -> Stats {
+    let mut stats = Stats::default();
+    let mut names = HashSet::new();
+    // long programs are long chains of lets, walk them without recursing
+    let mut stack = vec![(term, 1)];
+
+    while let Some((term, depth)) = stack.pop() {
+        *stats.kinds.entry(term.kind()).or_default() += 1;
+        stats.depth = stats.depth.max(depth);
+
+        match term {
+            Term::Let(x) => match &x.pattern {
+                Some(pattern) => names.extend(pattern.names().into_iter().map(|name| &name.text)),
+                None => {
+                    names.insert(&x.name.text);
+                }
+            },
+            Term::Function(x) => {
+                stats.closures += 1;
+                names.extend(x.parameters.iter().map(|param| &param.text));
+            }
+            _ => (),
+        }
+
+        stack.extend(term.children().into_iter().map(|child| (child, depth + 1)));
+    }
+
+    stats.variables = names.len();
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn fib() {
+        let prog = parse("let fib = fn (n) => { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)", "test").unwrap();
+        let stats = stats(&prog.expression);
+
+        assert_eq!(stats.nodes(), 21);
+        assert_eq!(stats.depth, 7);
+        assert_eq!(stats.variables, 2);
+        assert_eq!(stats.closures, 1);
+        assert_eq!(stats.kinds["Call"], 3);
+        assert_eq!(stats.kinds["Var"], 7);
+    }
+}
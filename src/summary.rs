@@ -0,0 +1,109 @@
+//! What a run did, reported by `--stats` once the program finishes.
+
+use serde_json::{json, Map, Value};
+use std::{fmt, time::Duration};
+
+/// Work done by the evaluator so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Terms evaluated, a term run twice counting twice.
+    pub evaluated: usize,
+    /// Closure bodies entered, tail calls run in place included.
+    pub calls: usize,
+    /// Most closure calls running at once.
+    pub max_depth: usize,
+    /// Operators and calls run by a specialized fast path.
+    pub specialized: usize,
+    /// Specialized sites sent back to the generic path by a failed guard.
+    pub deoptimized: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub counters: Counters,
+    /// Runs whose output came from `--cache-dir` instead.
+    pub cache_hits: usize,
+    /// Cycle collections, see `Interpreter::gc_stats`.
+    pub collections: usize,
+    /// Wall time of each phase the run went through, in order.
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Summary {
+    pub fn json(&self) -> Value {
+        let phases: Map<String, Value> = self
+            .phases
+            .iter()
+            .map(|(phase, duration)| (phase.to_string(), json!(millis(*duration))))
+            .collect();
+
+        json!({
+            "evaluated": self.counters.evaluated,
+            "calls": self.counters.calls,
+            "max_depth": self.counters.max_depth,
+            "specialized": self.counters.specialized,
+            "deoptimized": self.counters.deoptimized,
+            "cache_hits": self.cache_hits,
+            "collections": self.collections,
+            "phases": phases,
+        })
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "evaluated: {}", self.counters.evaluated)?;
+        writeln!(f, "calls: {}", self.counters.calls)?;
+        writeln!(f, "max depth: {}", self.counters.max_depth)?;
+        writeln!(f, "specialized: {}", self.counters.specialized)?;
+        writeln!(f, "deoptimized: {}", self.counters.deoptimized)?;
+        writeln!(f, "cache hits: {}", self.cache_hits)?;
+        writeln!(f, "gc collections: {}", self.collections)?;
+
+        for (phase, duration) in &self.phases {
+            writeln!(f, "{}: {:.3} ms", phase, millis(*duration))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let summary = Summary {
+            counters: Counters {
+                evaluated: 21,
+                calls: 2,
+                max_depth: 2,
+                specialized: 4,
+                deoptimized: 1,
+            },
+            cache_hits: 0,
+            collections: 1,
+            phases: vec![("parse", Duration::from_millis(2)), ("eval", Duration::from_micros(500))],
+        };
+
+        assert_eq!(
+            summary.json(),
+            json!({
+                "evaluated": 21,
+                "calls": 2,
+                "max_depth": 2,
+                "specialized": 4,
+                "deoptimized": 1,
+                "cache_hits": 0,
+                "collections": 1,
+                "phases": { "parse": 2.0, "eval": 0.5 },
+            })
+        );
+        assert!(summary.to_string().ends_with("parse: 2.000 ms\neval: 0.500 ms\n"));
+    }
+}
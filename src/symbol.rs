@@ -0,0 +1,176 @@
+//! Interned names. Every identifier of a program is stored once for the whole
+//! process, so the AST, scopes and diagnostics share it and comparing or
+//! hashing a name does not look at its text.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Mutex, OnceLock},
+};
+
+/// A name, equal to another one exactly when their texts are equal. Symbols
+/// are made by interning, which never frees the text.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+fn interned() -> &'static Mutex<HashSet<&'static str>> {
+    static INTERNED: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+    INTERNED.get_or_init(Default::default)
+}
+
+impl Symbol {
+    pub fn new(name: &str) -> Self {
+        let mut interned = interned().lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(text) = interned.get(name) {
+            return Symbol(text);
+        }
+
+        let text: &'static str = Box::leak(name.into());
+
+        interned.insert(text);
+
+        Symbol(text)
+    }
+
+    /// The symbol for `name` if it was ever made, without making it: no
+    /// binding can have a name that was never interned.
+    pub fn get(name: &str) -> Option<Self> {
+        interned().lock().unwrap_or_else(|err| err.into_inner()).get(name).map(|text| Symbol(text))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+// the text of an interned name is unique, its address identifies it
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0.as_ptr() as usize);
+    }
+}
+
+/// By text, so anything sorted by name comes out the same on every run.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(other.0)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::new(&name)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.0, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+/// Names are interned as the JSON AST is read.
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+
+        Ok(Symbol::new(&name))
+    }
+}
+
+/// Names a fuzzed program is made of: a few for bindings, so variables read
+/// what the program bound, and the builtins that cannot block. Arbitrary
+/// text would never match a binding and, being interned, never be freed.
+#[cfg(feature = "arbitrary")]
+const ARBITRARY: &[&str] = &[
+    "a", "b", "f", "n", "x", "argc", "argv", "assert", "exit", "format", "getenv", "len", "now", "nth", "panic",
+    "parse_int", "slice", "to_string", "typeof",
+];
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Symbol {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.choose(ARBITRARY).map(|name| Symbol::new(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let a = Symbol::new("symbol_interning");
+        let b = Symbol::from(String::from("symbol_interning"));
+
+        assert_eq!(a, b);
+        assert!(std::ptr::eq(a.as_str(), b.as_str()));
+        assert_eq!(Symbol::get("symbol_interning"), Some(a));
+        assert_eq!(Symbol::get("symbol_never_interned"), None);
+        assert!(Symbol::new("a") < Symbol::new("b"));
+    }
+}
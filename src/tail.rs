@@ -0,0 +1,103 @@
+//! Turns the calls a function makes to itself in tail position into
+//! `Term::Recur`, so deep self recursion such as accumulator loops runs in
+//! constant stack.
+
+use crate::ast::*;
+use std::sync::Arc;
+
+/// `value` as bound by `let name = value`, its tail calls to `name` made
+/// into `Term::Recur` when it is a function. Anything else is left as is.
+pub fn optimize(name: &str, value: Term) -> Term {
+    match value {
+        Term::Function(mut x) if x.parameters.iter().all(|param| param.text != name) => {
+            x.value = Arc::new(tail(name, Arc::unwrap_or_clone(x.value)));
+
+            Term::Function(x)
+        }
+        value => value,
+    }
+}
+
+fn tail(name: &str, term: Term) -> Term {
+    match term {
+        Term::Call(x) if matches!(&*x.callee, Term::Var(callee) if callee.text == name) => Term::Recur(x),
+        // `x |> f` is the call `f(x)`
+        Term::Binary(x) if x.op == BinaryOp::Pipe && matches!(&*x.rhs, Term::Var(callee) if callee.text == name) => {
+            Term::Recur(Call {
+                callee: x.rhs,
+                arguments: vec![*x.lhs],
+                named: Vec::new(),
+                location: x.location,
+            })
+        }
+        Term::If(mut x) => {
+            x.then = Box::new(tail(name, *x.then));
+            x.otherwise = Box::new(tail(name, *x.otherwise));
+
+            Term::If(x)
+        }
+        Term::Let(mut x) => {
+            if !binds(&x, name) {
+                x.next = Box::new(tail(name, *x.next));
+            }
+
+            Term::Let(x)
+        }
+        // bindings of a `let` earlier in the sequence stay in scope while
+        // running the rest of it
+        Term::Seq(mut x) => {
+            let shadowed = x.expressions.iter().any(|term| matches!(term, Term::Let(x) if binds(x, name)));
+
+            if let Some(last) = x.expressions.last_mut().filter(|_| !shadowed) {
+                *last = tail(name, std::mem::replace(last, Term::bool(false)));
+            }
+
+            Term::Seq(x)
+        }
+        term => term,
+    }
+}
+
+fn binds(x: &Let, name: &str) -> bool {
+    match &x.pattern {
+        Some(pattern) => pattern.names().iter().any(|param| param.text == name),
+        None => x.name.text == name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn recurs(source: &str) -> usize {
+        fn count(term: &Term) -> usize {
+            let own = matches!(term, Term::Recur(_)) as usize;
+
+            own + term.children().into_iter().map(count).sum::<usize>()
+        }
+
+        let Term::Let(x) = parse(source, "test").unwrap().expression else {
+            panic!("expected a let")
+        };
+
+        count(&optimize(&x.name.text, *x.value))
+    }
+
+    #[test]
+    fn tail_calls() {
+        assert_eq!(recurs("let f = fn (n) => { if (n == 0) { 0 } else { f(n - 1) } }; f"), 1);
+        assert_eq!(recurs("let f = fn (n) => { let m = n - 1; m |> f }; f"), 1);
+        // not in tail position
+        assert_eq!(recurs("let f = fn (n) => { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f"), 0);
+        // another function
+        assert_eq!(recurs("let f = fn (n) => { g(n) }; f"), 0);
+        // shadowed
+        assert_eq!(recurs("let f = fn (f) => { f(1) }; f"), 0);
+        assert_eq!(recurs("let f = fn (n) => { let f = fn (m) => { m }; f(n) }; f"), 0);
+        // calls inside nested functions run on their own
+        assert_eq!(recurs("let f = fn (n) => { fn (m) => { f(m) } }; f"), 0);
+        assert_eq!(recurs("let f = fn (n) => { print(n); f(n - 1) }; f"), 1);
+        assert_eq!(recurs("let f = fn (n) => { f(n); print(n) }; f"), 0);
+    }
+}
@@ -0,0 +1,507 @@
+//! `spawn(f)` runs the closure `f` on a thread of its own and evaluates to a
+//! task, `join(task)` waits for it and evaluates to what `f` returned. Tasks
+//! talk through channels, `send(ch, v)` queues a value that `recv(ch)` takes
+//! out. Values are not shared between threads: the closure, everything it can
+//! see, results and messages are copied over as a `Parcel`.
+
+use num_bigint::BigInt;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::ast::{Location, Parameter, Term};
+use crate::host::Host;
+use crate::interpreter::{Scope, Suspension};
+use crate::symbol::Symbol;
+use crate::{Closure, Context, Error, Interpreter, Output};
+
+/// Stack of the threads tasks run on, as big as the main thread usually gets
+/// since evaluation recurses as deep as the program does.
+pub(crate) const STACK: usize = 8 * 1024 * 1024;
+
+pub(crate) fn register<H: Host + 'static>(interpreter: &mut Interpreter<H>) {
+    interpreter.native("spawn", spawn);
+    interpreter.native("join", join);
+    interpreter.native("channel", channel);
+    interpreter.native("send", send);
+    interpreter.native("recv", recv);
+}
+
+/// What crosses threads. Bools, ints, strings, tuples, closures, channels and
+/// lazy bindings, pending or done, are copied, channels staying the same
+/// channel. Tasks and modules are not transferable.
+#[derive(Debug, Clone)]
+enum Value {
+    Bool(bool),
+    Int(BigInt),
+    Str(String),
+    Tuple(Vec<Value>),
+    Closure {
+        body: Arc<Term>,
+        args: Arc<[Parameter]>,
+        scope: usize,
+    },
+    Thunk {
+        term: Term,
+        scope: usize,
+    },
+    Channel(Channel),
+    Void,
+}
+
+/// A value that can cross to another thread. Scopes are flattened to the
+/// bindings visible from them and referred to by index, so closures seeing
+/// themselves, recursive functions, survive the trip.
+#[derive(Debug, Clone)]
+pub(crate) struct Parcel {
+    value: Value,
+    scopes: Vec<Vec<(Symbol, Value)>>,
+    // a channel on its way to another task can still be sent to
+    claims: Vec<Claim>,
+}
+
+#[derive(Default)]
+struct Packer {
+    scopes: Vec<Vec<(Symbol, Value)>>,
+    packed: HashMap<*const (), usize>,
+    channels: Vec<Channel>,
+}
+
+impl Packer {
+    fn scope(&mut self, context: &Context) -> Result<usize, String> {
+        if let Some(&i) = self.packed.get(&context.address()) {
+            return Ok(i);
+        }
+
+        let i = self.scopes.len();
+
+        self.packed.insert(context.address(), i);
+        self.scopes.push(Vec::new());
+
+        let mut bindings = Vec::new();
+
+        for (name, value) in context.visible() {
+            bindings.push((name, self.value(&value)?));
+        }
+
+        self.scopes[i] = bindings;
+
+        Ok(i)
+    }
+
+    fn value(&mut self, value: &Output) -> Result<Value, String> {
+        Ok(match value {
+            Output::Bool(x) => Value::Bool(*x),
+            Output::Int(x) => Value::Int(x.clone()),
+            Output::Str(x) => Value::Str(x.to_string()),
+            Output::Tuple(x) => Value::Tuple(x.iter().map(|element| self.value(element)).collect::<Result<_, _>>()?),
+            Output::Closure(x) => Value::Closure {
+                body: Arc::clone(&x.body),
+                args: Arc::clone(&x.args),
+                scope: self.scope(&x.context)?,
+            },
+            Output::Thunk(x) => match &*x.suspension() {
+                Suspension::Done(value) => self.value(value)?,
+                Suspension::Pending(term, context) => Value::Thunk {
+                    term: (**term).clone(),
+                    scope: self.scope(context)?,
+                },
+                Suspension::Forcing => return Err(String::from("Cannot send a value still being computed")),
+            },
+            Output::Module(_) => return Err(String::from("Cannot send modules between tasks")),
+            Output::Task(_) => return Err(String::from("Cannot send tasks between tasks")),
+            Output::Channel(x) => {
+                if !self.channels.contains(x) {
+                    self.channels.push(x.clone());
+                }
+
+                Value::Channel(x.clone())
+            }
+            Output::Void => Value::Void,
+        })
+    }
+}
+
+impl Parcel {
+    pub(crate) fn pack(value: &Output) -> Result<Parcel, String> {
+        let mut packer = Packer::default();
+        let value = packer.value(value)?;
+
+        Ok(Parcel {
+            value,
+            scopes: packer.scopes,
+            claims: packer.channels.into_iter().map(Claim::new).collect(),
+        })
+    }
+
+    /// The value again, its scopes made anew in `interpreter`, which can send
+    /// to the channels in it from then on.
+    pub(crate) fn open<H: Host + 'static>(self, interpreter: &mut Interpreter<H>) -> Output {
+        for claim in &self.claims {
+            interpreter.claims().hold(&claim.0);
+        }
+
+        let contexts: Vec<Context> = self.scopes.iter().map(|_| interpreter.scope(Scope::default())).collect();
+
+        for (context, bindings) in contexts.iter().zip(self.scopes) {
+            for (name, value) in bindings {
+                let value = unpack(interpreter, value, &contexts);
+
+                context.insert(name, value);
+            }
+        }
+
+        unpack(interpreter, self.value, &contexts)
+    }
+}
+
+fn unpack<H: Host + 'static>(interpreter: &mut Interpreter<H>, value: Value, contexts: &[Context]) -> Output {
+    match value {
+        Value::Bool(x) => Output::Bool(x),
+        Value::Int(x) => Output::Int(x),
+        Value::Str(x) => Output::Str(x.into()),
+        Value::Tuple(x) => Output::Tuple(x.into_iter().map(|element| unpack(interpreter, element, contexts)).collect()),
+        Value::Closure { body, args, scope } => Output::Closure(Closure {
+            body,
+            args,
+            context: contexts[scope].clone(),
+        }),
+        Value::Thunk { term, scope } => interpreter.suspend(term, contexts[scope].clone()),
+        Value::Channel(x) => Output::Channel(x),
+        Value::Void => Output::Void,
+    }
+}
+
+/// A closure running on another thread, see `spawn`. Joining it again gives
+/// the same result.
+#[derive(Clone)]
+pub struct Task(Rc<RefCell<Joining>>);
+
+struct Joining {
+    handle: Option<JoinHandle<Result<Parcel, Error>>>,
+    result: Option<Result<Parcel, Error>>,
+}
+
+impl Task {
+    /// The task's result, its channels claimed by the joining interpreter
+    /// rather than kept by the task for later joins.
+    fn wait(&self, claims: &mut Claims, location: &Location) -> Result<Parcel, Error> {
+        let panicked = || Err(Error::new("Task panicked", location.clone()));
+        let joining = &mut *self.0.borrow_mut();
+        let handle = &mut joining.handle;
+        let result = joining.result.get_or_insert_with(|| match handle.take() {
+            Some(handle) => handle.join().unwrap_or_else(|_| panicked()),
+            None => panicked(),
+        });
+
+        if let Ok(parcel) = result {
+            for claim in parcel.claims.drain(..) {
+                claims.hold(&claim.0);
+            }
+        }
+
+        result.clone()
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let done = self.0.try_borrow().map(|joining| joining.result.is_some()).unwrap_or_default();
+
+        f.debug_struct("Task").field("done", &done).finish()
+    }
+}
+
+/// Queue of messages between tasks, every copy of it being the same channel
+/// and able to both send and receive.
+#[derive(Clone)]
+pub struct Channel(Arc<Ends>);
+
+struct Ends {
+    // None only wakes a receiver up to count the claims again
+    sender: Sender<Option<Parcel>>,
+    receiver: Mutex<Receiver<Option<Parcel>>>,
+    // interpreters and parcels in flight that can send to the channel
+    claims: AtomicUsize,
+}
+
+impl Channel {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Channel(Arc::new(Ends {
+            sender,
+            receiver: Mutex::new(receiver),
+            claims: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Next message, waiting for one while a task other than the one running
+    /// `claims` could still send it.
+    fn take(&self, claims: &Claims) -> Result<Parcel, String> {
+        let receiver = self.0.receiver.lock().unwrap_or_else(|err| err.into_inner());
+
+        loop {
+            match receiver.try_recv() {
+                Ok(Some(parcel)) => return Ok(parcel),
+                Ok(None) => continue,
+                Err(TryRecvError::Empty) => (),
+                Err(err) => return Err(err.to_string()),
+            }
+
+            // a claim dropped after this count wakes the wait below
+            if self.0.claims.load(Ordering::SeqCst) == claims.holds(self) as usize {
+                return Err(String::from("Receiving from an empty channel no task can send to"));
+            }
+
+            match receiver.recv() {
+                Ok(Some(parcel)) => return Ok(parcel),
+                Ok(None) => (),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+}
+
+/// An interpreter or a parcel able to send to a channel. Receiving waits only
+/// while someone else holds one.
+#[derive(Debug)]
+struct Claim(Channel);
+
+impl Claim {
+    fn new(channel: Channel) -> Self {
+        channel.0.claims.fetch_add(1, Ordering::SeqCst);
+
+        Claim(channel)
+    }
+}
+
+impl Clone for Claim {
+    fn clone(&self) -> Self {
+        Claim::new(self.0.clone())
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.0 .0.claims.fetch_sub(1, Ordering::SeqCst);
+
+        let _ = self.0 .0.sender.send(None);
+    }
+}
+
+/// The channels an interpreter made or was handed, released with it.
+#[derive(Debug, Default)]
+pub(crate) struct Claims(Vec<Claim>);
+
+impl Claims {
+    fn hold(&mut self, channel: &Channel) {
+        if !self.holds(channel) {
+            self.0.push(Claim::new(channel.clone()));
+        }
+    }
+
+    fn holds(&self, channel: &Channel) -> bool {
+        self.0.iter().any(|claim| claim.0 == *channel)
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Channel").field(&Arc::as_ptr(&self.0)).finish()
+    }
+}
+
+/// `spawn(f)` starts running `f`, a function taking no arguments, on another
+/// thread and evaluates to its task. The task's interpreter has the default
+/// builtins and the settings of this one, and prints through the host.
+fn spawn<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let fail = |msg: &str| Error::new(msg, location.clone());
+
+    let [Output::Closure(closure)] = &args[..] else {
+        return Err(fail("spawn expects a single function argument"));
+    };
+
+    if !closure.accepts(0) {
+        return Err(fail("spawn expects a function taking no arguments"));
+    }
+
+    let parcel = Parcel::pack(&args[0]).map_err(|msg| fail(&msg))?;
+    let host = interpreter
+        .host_mut()
+        .spawn()
+        .ok_or_else(|| fail("Spawning tasks is not available on this host"))?;
+    let (semantics, lazy, intrinsics) = (interpreter.semantics(), interpreter.lazy(), interpreter.intrinsics());
+    let specialization = interpreter.specialization();
+    let cancellation = interpreter.cancellation();
+    let at = location.clone();
+
+    let handle = thread::Builder::new()
+        .name(String::from("rinha-task"))
+        .stack_size(STACK)
+        .spawn(move || {
+            let mut worker = Interpreter::new(host);
+
+            worker.set_semantics(semantics);
+            worker.set_lazy(lazy);
+            worker.set_intrinsics(intrinsics);
+            worker.set_specialization(specialization);
+            worker.set_cancellation(cancellation);
+
+            let Output::Closure(closure) = parcel.open(&mut worker) else {
+                return Err(Error::new("spawn expects a single function argument", at));
+            };
+            let value = worker.apply(closure, Vec::new(), &Context::default(), &at)?;
+
+            Parcel::pack(&value).map_err(|msg| Error::new(&msg, at))
+        })
+        .map_err(|err| fail(&format!("Cannot spawn a task, {}", err)))?;
+
+    Ok(Output::Task(Task(Rc::new(RefCell::new(Joining {
+        handle: Some(handle),
+        result: None,
+    })))))
+}
+
+/// `join(task)` waits for the task to finish and evaluates to its result, an
+/// error in the task being reported where it happened.
+fn join<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let [Output::Task(task)] = &args[..] else {
+        return Err(Error::new("join expects a single task argument", location.clone()));
+    };
+
+    Ok(task.wait(interpreter.claims(), location)?.open(interpreter))
+}
+
+/// `channel()` evaluates to a new, empty channel.
+fn channel<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    if !args.is_empty() {
+        return Err(Error::new("channel expects no arguments", location.clone()));
+    }
+
+    let channel = Channel::new();
+
+    interpreter.claims().hold(&channel);
+
+    Ok(Output::Channel(channel))
+}
+
+/// `send(ch, v)` queues a copy of `v` on the channel without waiting for it to
+/// be received.
+fn send<H: Host + 'static>(_: &mut Interpreter<H>, args: Vec<Output>, location: &Location) -> Result<Output, Error> {
+    let fail = |msg: &str| Error::new(msg, location.clone());
+
+    let [Output::Channel(channel), value] = &args[..] else {
+        return Err(fail("send expects a channel and a value"));
+    };
+
+    let parcel = Parcel::pack(value).map_err(|msg| fail(&msg))?;
+
+    channel.0.sender.send(Some(parcel)).map_err(|err| fail(&err.to_string()))?;
+
+    Ok(Output::Void)
+}
+
+/// `recv(ch)` evaluates to the oldest value queued on the channel, waiting for
+/// one to be sent when there is none.
+fn recv<H: Host + 'static>(
+    interpreter: &mut Interpreter<H>,
+    args: Vec<Output>,
+    location: &Location,
+) -> Result<Output, Error> {
+    let [Output::Channel(channel)] = &args[..] else {
+        return Err(Error::new("recv expects a single channel argument", location.clone()));
+    };
+
+    let parcel = channel.take(interpreter.claims()).map_err(|msg| Error::new(&msg, location.clone()))?;
+
+    Ok(parcel.open(interpreter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{host::CaptureHost, parse_json, parser};
+
+    #[test]
+    fn spawn_join() {
+        let mut interpreter = Interpreter::default();
+        let prog = parse_json(&std::fs::read_to_string("./json/tasks.json").unwrap()).unwrap();
+
+        let res = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "(true, (100, done))");
+    }
+
+    #[test]
+    fn channels() {
+        let mut interpreter = Interpreter::default();
+        let prog = parse_json(&std::fs::read_to_string("./json/channels.json").unwrap()).unwrap();
+
+        let res = interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        assert_eq!(res.to_string(), "((2, 1), (40, 42))");
+    }
+
+    #[test]
+    fn errors() {
+        let run = |source: &str| {
+            let prog = parser::parse(source, "tasks.rinha").unwrap();
+
+            Interpreter::default().eval(&prog.expression, &mut Context::default()).unwrap_err().message
+        };
+
+        assert_eq!(run("join(spawn(fn () => { 1 / 0 }))"), "Arithmetic error, dividing by zero");
+        assert_eq!(run("spawn(fn (x) => { x })"), "spawn expects a function taking no arguments");
+        assert_eq!(run("let m = (1, spawn(fn () => { 1 })); spawn(fn () => { m })"), "Cannot send tasks between tasks");
+        assert_eq!(run("join(1)"), "join expects a single task argument");
+        assert_eq!(run("recv(channel())"), "Receiving from an empty channel no task can send to");
+        assert_eq!(run("let c = channel(); print(recv(c))"), "Receiving from an empty channel no task can send to");
+        assert_eq!(
+            run("let c = channel(); let t = spawn(fn () => { (c, 1) }); let _ = join(t); recv(c)"),
+            "Receiving from an empty channel no task can send to"
+        );
+        assert_eq!(run("send(channel(), spawn(fn () => { 1 }))"), "Cannot send tasks between tasks");
+        assert_eq!(run("channel() == channel()"), "Cannot compare channels");
+
+        let prog = parser::parse("spawn(fn () => { 1 })", "tasks.rinha").unwrap();
+        let err = Interpreter::new(CaptureHost::default())
+            .eval(&prog.expression, &mut Context::default())
+            .unwrap_err();
+
+        assert_eq!(err.message, "Spawning tasks is not available on this host");
+    }
+}
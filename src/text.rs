@@ -0,0 +1,164 @@
+//! Rope backing string values. `+` links both sides in O(1) and the text is
+//! flattened once, the first time it is read, so building a string in a loop
+//! stays linear.
+
+use std::{
+    cell::{OnceCell, RefCell},
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+/// Below this many bytes concatenation just copies, ropes only pay off for
+/// long strings.
+const FLAT: usize = 64;
+
+struct Node {
+    flat: OnceCell<String>,
+    parts: RefCell<Option<(Text, Text)>>,
+    len: usize,
+}
+
+#[derive(Clone)]
+pub struct Text(Rc<Node>);
+
+impl Text {
+    pub fn concat(lhs: &Text, rhs: &Text) -> Text {
+        let len = lhs.len() + rhs.len();
+
+        if len < FLAT {
+            return Text::from(format!("{}{}", lhs.as_str(), rhs.as_str()));
+        }
+
+        Text(Rc::new(Node {
+            flat: OnceCell::new(),
+            parts: RefCell::new(Some((lhs.clone(), rhs.clone()))),
+            len,
+        }))
+    }
+
+    /// Length in bytes, known without flattening.
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.flat.get_or_init(|| {
+            let mut flat = String::with_capacity(self.0.len);
+            let mut stack = vec![self.clone()];
+
+            while let Some(text) = stack.pop() {
+                if let Some(s) = text.0.flat.get() {
+                    flat.push_str(s);
+                } else if let Some((lhs, rhs)) = &*text.0.parts.borrow() {
+                    stack.push(rhs.clone());
+                    stack.push(lhs.clone());
+                }
+            }
+
+            // the pieces are no longer needed once flattened
+            self.0.parts.borrow_mut().take();
+
+            flat
+        })
+    }
+}
+
+impl Drop for Node {
+    // long ropes are deep, drop them without recursing
+    fn drop(&mut self) {
+        let mut stack: Vec<Text> = self.parts.get_mut().take().into_iter().flat_map(|(a, b)| [a, b]).collect();
+
+        while let Some(text) = stack.pop() {
+            if let Ok(mut node) = Rc::try_unwrap(text.0) {
+                stack.extend(node.parts.get_mut().take().into_iter().flat_map(|(a, b)| [a, b]));
+            }
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Self {
+        Text(Rc::new(Node {
+            len: s.len(),
+            flat: OnceCell::from(s),
+            parts: RefCell::new(None),
+        }))
+    }
+}
+
+impl From<&str> for Text {
+    fn from(s: &str) -> Self {
+        Text::from(String::from(s))
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Text {}
+
+impl PartialOrd for Text {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Text {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Text {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat() {
+        let mut text = Text::from("");
+
+        for i in 0..100_000 {
+            text = Text::concat(&text, &Text::from(format!("{}", i % 10)));
+        }
+
+        assert_eq!(text.len(), 100_000);
+        assert!(text.starts_with("0123456789012"));
+        assert_eq!(Text::concat(&"ab".into(), &"c".into()), Text::from("abc"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Timeline of the closure calls of a run, written by `--trace-out` in the
+//! Chrome Trace Event format (chrome://tracing, Perfetto, speedscope) or as
+//! folded stacks for flamegraph tools.
+
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{ast::Term, hooks::EvalHooks, Closure, Error, Output};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: Rc<str>,
+    /// A call starting, or else the innermost running one returning.
+    pub begin: bool,
+    /// Since the tracer was made.
+    pub at: Duration,
+}
+
+/// Events recorded by a `Tracer`, shared with it so they can be read once
+/// the hooks are installed.
+#[derive(Debug, Clone, Default)]
+pub struct Trace(Rc<RefCell<Vec<Event>>>);
+
+impl Trace {
+    pub fn events(&self) -> Vec<Event> {
+        self.0.borrow().clone()
+    }
+
+    /// Trace Event format document, one begin and one end event per call.
+    pub fn chrome(&self) -> Value {
+        let events: Vec<Value> = self
+            .0
+            .borrow()
+            .iter()
+            .map(|event| {
+                json!({
+                    "name": &*event.name,
+                    "cat": "call",
+                    "ph": if event.begin { "B" } else { "E" },
+                    "ts": event.at.as_micros() as u64,
+                    "pid": 1,
+                    "tid": 1,
+                })
+            })
+            .collect();
+
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+
+    /// One `outer;inner count` line per call stack, counting the microseconds
+    /// spent in the innermost call itself.
+    pub fn folded(&self) -> String {
+        let mut stacks: BTreeMap<String, u128> = BTreeMap::new();
+        let mut stack: Vec<&str> = Vec::new();
+        let mut last = Duration::ZERO;
+        let events = self.0.borrow();
+
+        for event in events.iter() {
+            if !stack.is_empty() {
+                *stacks.entry(stack.join(";")).or_default() += (event.at - last).as_micros();
+            }
+
+            if event.begin {
+                stack.push(&event.name);
+            } else {
+                stack.pop();
+            }
+
+            last = event.at;
+        }
+
+        stacks
+            .into_iter()
+            .filter(|(_, micros)| *micros > 0)
+            .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+            .collect()
+    }
+}
+
+/// Hooks recording a `Trace`. Calls are named after the first name their
+/// closure was bound to, anonymous ones after where the function starts.
+pub struct Tracer {
+    trace: Trace,
+    start: Instant,
+    names: HashMap<*const Term, Rc<str>>,
+}
+
+impl Tracer {
+    pub fn new(trace: Trace) -> Self {
+        Self {
+            trace,
+            start: Instant::now(),
+            names: HashMap::new(),
+        }
+    }
+
+    fn name(&mut self, closure: &Closure) -> Rc<str> {
+        self.names
+            .entry(Arc::as_ptr(&closure.body))
+            .or_insert_with(|| {
+                let location = closure.body.location();
+
+                format!("<fn {}:{}>", location.filename, location.start).into()
+            })
+            .clone()
+    }
+
+    fn record(&mut self, name: Rc<str>, begin: bool) {
+        let at = self.start.elapsed();
+
+        self.trace.0.borrow_mut().push(Event { name, begin, at });
+    }
+}
+
+impl EvalHooks for Tracer {
+    fn on_call(&mut self, closure: &Closure, _: &[Output]) {
+        let name = self.name(closure);
+
+        self.record(name, true);
+    }
+
+    fn on_return(&mut self, closure: &Closure, _: &Result<Output, Error>) {
+        let name = self.name(closure);
+
+        self.record(name, false);
+    }
+
+    fn on_bind(&mut self, name: &str, value: &Output) {
+        if let Output::Closure(closure) = value {
+            self.names.entry(Arc::as_ptr(&closure.body)).or_insert_with(|| name.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{host::CaptureHost, parse_json, Context, Interpreter};
+
+    #[test]
+    fn calls() {
+        let mut interpreter = Interpreter::new(CaptureHost::default());
+        let prog = parse_json(&std::fs::read_to_string("./json/allocations.json").unwrap()).unwrap();
+        let trace = Trace::default();
+
+        interpreter.set_hooks(Tracer::new(trace.clone()));
+        interpreter.eval(&prog.expression, &mut Context::default()).unwrap();
+
+        let events: Vec<(String, bool)> =
+            trace.events().into_iter().map(|event| (event.name.to_string(), event.begin)).collect();
+
+        assert_eq!(
+            events,
+            [
+                ("f".to_string(), true),
+                ("pair".to_string(), true),
+                ("pair".to_string(), false),
+                ("f".to_string(), false)
+            ]
+        );
+        assert_eq!(trace.chrome()["traceEvents"][1]["ph"], "B");
+        assert!(trace.folded().lines().all(|line| line.starts_with("f ") || line.starts_with("f;pair ")));
+    }
+}
@@ -0,0 +1,49 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{host::CaptureHost, malformed, parse_json, Context, Interpreter};
+
+#[wasm_bindgen]
+pub struct Evaluation {
+    result: Option<String>,
+    error: Option<String>,
+    output: String,
+}
+
+#[wasm_bindgen]
+impl Evaluation {
+    #[wasm_bindgen(getter)]
+    pub fn result(&self) -> Option<String> {
+        self.result.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> String {
+        self.output.clone()
+    }
+}
+
+/// Evaluates a program given as its JSON AST, everything printed is captured
+/// into `output` instead of going to the console.
+#[wasm_bindgen]
+pub fn eval_json(source: &str) -> JsValue {
+    let mut interpreter = Interpreter::new(CaptureHost::default());
+
+    let (result, error) = match parse_json(source) {
+        Ok(prog) => match interpreter.eval(&prog.expression, &mut Context::default()) {
+            Ok(res) => (Some(res.to_string()), None),
+            Err(err) => (None, Some(err.to_string())),
+        },
+        Err(err) => (None, Some(malformed::explain(source, &err))),
+    };
+
+    JsValue::from(Evaluation {
+        result,
+        error,
+        output: interpreter.host().output().to_string(),
+    })
+}